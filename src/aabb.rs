@@ -0,0 +1,92 @@
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    min: Vector3,
+    max: Vector3,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn min(&self) -> Vector3 {
+        self.min
+    }
+
+    pub fn max(&self) -> Vector3 {
+        self.max
+    }
+
+    pub fn extent(&self) -> Vector3 {
+        self.max - self.min
+    }
+
+    pub fn surrounding(a: Aabb, b: Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                a.min.x().min(b.min.x()),
+                a.min.y().min(b.min.y()),
+                a.min.z().min(b.min.z()),
+            ),
+            max: Vector3::new(
+                a.max.x().max(b.max.x()),
+                a.max.y().max(b.max.y()),
+                a.max.z().max(b.max.z()),
+            ),
+        }
+    }
+
+    /// Box around this one after mapping its corners through `transform`.
+    pub fn transformed<F: Fn(Vector3) -> Vector3>(&self, transform: F) -> Aabb {
+        let mut bounds: Option<Aabb> = None;
+        for corner in 0..8 {
+            let p = transform(Vector3::new(
+                if corner & 1 == 0 {
+                    self.min.x()
+                } else {
+                    self.max.x()
+                },
+                if corner & 2 == 0 {
+                    self.min.y()
+                } else {
+                    self.max.y()
+                },
+                if corner & 4 == 0 {
+                    self.min.z()
+                } else {
+                    self.max.z()
+                },
+            ));
+            let point = Aabb::new(p, p);
+            bounds = Some(match bounds {
+                Some(b) => Aabb::surrounding(b, point),
+                None => point,
+            });
+        }
+        bounds.unwrap()
+    }
+
+    /// Slab test. Returns the parametric interval of the ray inside the box,
+    /// clipped to `[t_min, t_max]`.
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let mut t0 = t_min;
+        let mut t1 = t_max;
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction()[axis];
+            let mut near = (self.min[axis] - ray.origin()[axis]) * inv_d;
+            let mut far = (self.max[axis] - ray.origin()[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut near, &mut far);
+            }
+            t0 = if near > t0 { near } else { t0 };
+            t1 = if far < t1 { far } else { t1 };
+            if t1 <= t0 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+}
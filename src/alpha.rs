@@ -0,0 +1,90 @@
+use crate::hittables::{Evaluation, HitRecord, Material, Scatter};
+use crate::nested::Interior;
+use crate::ray::Ray;
+use crate::rng::random_f64;
+use crate::textures::Texture;
+use crate::vector::Vector3;
+
+enum AlphaMode {
+    /// Solid where the opacity reaches the threshold, clear elsewhere.
+    Threshold(f64),
+    /// Solid for a fraction of rays equal to the opacity, which averages out
+    /// to soft edges and partial transparency.
+    Stochastic,
+}
+
+/// Cuts holes into the surface of another material with an opacity texture,
+/// read from the first channel. Used on flat cards this gives leaves,
+/// fences and the like without modelling them.
+pub struct AlphaMask {
+    material: Box<dyn Material>,
+    opacity: Box<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl AlphaMask {
+    pub fn new<T: Texture + 'static>(material: Box<dyn Material>, opacity: T) -> AlphaMask {
+        AlphaMask {
+            material,
+            opacity: Box::new(opacity),
+            mode: AlphaMode::Threshold(0.5),
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> AlphaMask {
+        self.mode = AlphaMode::Threshold(threshold);
+        self
+    }
+
+    pub fn stochastic(mut self) -> AlphaMask {
+        self.mode = AlphaMode::Stochastic;
+        self
+    }
+}
+
+impl Material for AlphaMask {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        self.material.scatter(ray_in, hit_record)
+    }
+
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        self.material.evaluate(ray_in, hit_record, direction)
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Vector3 {
+        self.material.emitted(hit_record)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vector3 {
+        self.material.albedo(hit_record)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.material.is_dispersive()
+    }
+
+    fn interior(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Interior> {
+        self.material.interior(ray_in, hit_record)
+    }
+
+    fn is_opaque_at(&self, hit_record: &HitRecord) -> bool {
+        let opacity = self
+            .opacity
+            .value(hit_record.u, hit_record.v, hit_record.p)
+            .x();
+        let opaque = match self.mode {
+            AlphaMode::Threshold(threshold) => opacity >= threshold,
+            AlphaMode::Stochastic => random_f64() < opacity,
+        };
+        opaque && self.material.is_opaque_at(hit_record)
+    }
+}
@@ -0,0 +1,171 @@
+use crate::camera::PerspectiveCamera;
+use crate::transform::Transform;
+use crate::vector::Vector3;
+use std::ops::{Add, Mul, Sub};
+
+/// How a track moves between its keys.
+#[derive(Clone, Copy)]
+pub enum Interpolation {
+    /// Straight from one key to the next, turning sharply at the keys.
+    Linear,
+    /// Along cubic Bezier curves through the keys, with Catmull-Rom tangents
+    /// so that speed and direction change smoothly across them.
+    Bezier,
+}
+
+/// A value changing over time in seconds, set at keyframes and held before
+/// the first and after the last.
+#[derive(Clone)]
+pub struct Track<T> {
+    /// Sorted by time.
+    keys: Vec<(f64, T)>,
+    interpolation: Interpolation,
+}
+
+impl<T> Track<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    /// Starts a linear track with its first key. With no other keys the
+    /// value never changes.
+    pub fn new(time: f64, value: T) -> Track<T> {
+        Track {
+            keys: vec![(time, value)],
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    /// Adds a key, replacing any already at the same time.
+    pub fn with_key(mut self, time: f64, value: T) -> Track<T> {
+        let index = self.keys.partition_point(|(t, _)| *t < time);
+        match self.keys.get_mut(index) {
+            Some(key) if key.0 == time => key.1 = value,
+            _ => self.keys.insert(index, (time, value)),
+        }
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Track<T> {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Rate of change through key `i`, from its neighbours.
+    fn tangent(&self, i: usize) -> T {
+        let before = self.keys[i.saturating_sub(1)];
+        let after = self.keys[(i + 1).min(self.keys.len() - 1)];
+        (after.1 - before.1) * (1.0 / (after.0 - before.0))
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let last = self.keys.len() - 1;
+        let index = self.keys.partition_point(|(t, _)| *t <= time);
+        if index == 0 {
+            return self.keys[0].1;
+        }
+        if index > last {
+            return self.keys[last].1;
+        }
+        let (t0, p0) = self.keys[index - 1];
+        let (t1, p3) = self.keys[index];
+        let span = t1 - t0;
+        let u = (time - t0) / span;
+        match self.interpolation {
+            Interpolation::Linear => p0 * (1.0 - u) + p3 * u,
+            Interpolation::Bezier => {
+                let p1 = p0 + self.tangent(index - 1) * (span / 3.0);
+                let p2 = p3 - self.tangent(index) * (span / 3.0);
+                let v = 1.0 - u;
+                p0 * (v * v * v)
+                    + p1 * (3.0 * v * v * u)
+                    + p2 * (3.0 * v * u * u)
+                    + p3 * (u * u * u)
+            }
+        }
+    }
+}
+
+/// Keyframed placement of an object: scaled, then turned by Euler angles in
+/// degrees and then moved.
+#[derive(Clone)]
+pub struct AnimatedTransform {
+    translation: Track<Vector3>,
+    rotation: Track<Vector3>,
+    scale: Track<Vector3>,
+}
+
+impl AnimatedTransform {
+    pub fn new() -> AnimatedTransform {
+        AnimatedTransform {
+            translation: Track::new(0.0, Vector3::new(0.0, 0.0, 0.0)),
+            rotation: Track::new(0.0, Vector3::new(0.0, 0.0, 0.0)),
+            scale: Track::new(0.0, Vector3::new(1.0, 1.0, 1.0)),
+        }
+    }
+
+    pub fn with_translation(mut self, translation: Track<Vector3>) -> AnimatedTransform {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Track<Vector3>) -> AnimatedTransform {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Track<Vector3>) -> AnimatedTransform {
+        self.scale = scale;
+        self
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        Transform::scaling(self.scale.at(time))
+            .then(&Transform::euler(self.rotation.at(time)))
+            .then(&Transform::translation(self.translation.at(time)))
+    }
+}
+
+/// Keyframed thin lens camera, focused on whatever it looks at.
+#[derive(Clone)]
+pub struct AnimatedCamera {
+    lookfrom: Track<Vector3>,
+    lookat: Track<Vector3>,
+    vfov: Track<f64>,
+    aperture: Track<f64>,
+}
+
+impl AnimatedCamera {
+    pub fn new(lookfrom: Track<Vector3>, lookat: Track<Vector3>) -> AnimatedCamera {
+        AnimatedCamera {
+            lookfrom,
+            lookat,
+            vfov: Track::new(0.0, 20.0),
+            aperture: Track::new(0.0, 0.0),
+        }
+    }
+
+    /// Vertical field of view in degrees.
+    pub fn with_vfov(mut self, vfov: Track<f64>) -> AnimatedCamera {
+        self.vfov = vfov;
+        self
+    }
+
+    pub fn with_aperture(mut self, aperture: Track<f64>) -> AnimatedCamera {
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn at(&self, time: f64, aspect: f64) -> PerspectiveCamera {
+        let lookfrom = self.lookfrom.at(time);
+        let lookat = self.lookat.at(time);
+        PerspectiveCamera::new(
+            lookfrom,
+            lookat,
+            Vector3::new(0.0, 1.0, 0.0),
+            self.vfov.at(time),
+            aspect,
+            self.aperture.at(time),
+            (lookfrom - lookat).length(),
+        )
+    }
+}
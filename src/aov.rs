@@ -0,0 +1,164 @@
+use crate::hittables::{HitId, HitRecord, Scatter};
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// Arbitrary output variable: a pass rendered alongside the image for
+/// compositing, gathered from the first hit of each camera ray.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Aov {
+    /// Distance from the camera.
+    Depth,
+    /// World space shading normal.
+    Normal,
+    Albedo,
+    /// World space hit point.
+    Position,
+    /// A flat colour for each object, numbered in the order they were added
+    /// to the world, looking inside lists, hierarchies and transforms.
+    ObjectId,
+    /// A flat colour for each material, numbered likewise.
+    MaterialId,
+    /// Light that reached the camera off the diffuse part of the first
+    /// surface, directly from lights or after further bounces.
+    Diffuse,
+    /// Light off glossy and mirror-like reflection at the first surface.
+    Specular,
+    /// Light that came through the first surface.
+    Transmission,
+    /// Light emitted by the first surface, or the environment behind it.
+    Emission,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 10] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Position,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Diffuse,
+        Aov::Specular,
+        Aov::Transmission,
+        Aov::Emission,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Diffuse => "diffuse",
+            Aov::Specular => "specular",
+            Aov::Transmission => "transmission",
+            Aov::Emission => "emission",
+        }
+    }
+
+    /// Whether the pass identifies things rather than measures them, so
+    /// it is taken from a single sample instead of averaged over the pixel.
+    pub fn is_id(self) -> bool {
+        matches!(self, Aov::ObjectId | Aov::MaterialId)
+    }
+}
+
+/// Part of a material's response that a scattered ray was sampled from, or
+/// that an evaluation is split by.
+#[derive(Clone, Copy)]
+pub enum Lobe {
+    Diffuse,
+    Specular,
+    Transmission,
+}
+
+impl Lobe {
+    pub const ALL: [Lobe; 3] = [Lobe::Diffuse, Lobe::Specular, Lobe::Transmission];
+
+    /// Sorts a scatter off the surface hit by `ray_in`: through the surface
+    /// is transmission, mirror-like or glossy reflection is specular and
+    /// anything else diffuse.
+    pub fn of(ray_in: &Ray, hit_record: &HitRecord, scatter: &Scatter) -> Lobe {
+        let entering = ray_in.direction().dot(hit_record.geometric_normal) < 0.0;
+        let inward = scatter.ray.direction().dot(hit_record.geometric_normal) < 0.0;
+        if inward == entering {
+            Lobe::Transmission
+        } else if scatter.pdf.is_none() || scatter.glossy {
+            Lobe::Specular
+        } else {
+            Lobe::Diffuse
+        }
+    }
+
+    pub fn aov(self) -> Aov {
+        match self {
+            Lobe::Diffuse => Aov::Diffuse,
+            Lobe::Specular => Aov::Specular,
+            Lobe::Transmission => Aov::Transmission,
+        }
+    }
+}
+
+/// The first surface a camera ray hit, as the integrators shaded it.
+#[derive(Clone, Copy)]
+pub struct Surface {
+    pub position: Vector3,
+    pub normal: Vector3,
+    pub albedo: Vector3,
+    pub id: HitId,
+}
+
+impl Surface {
+    pub fn of(hit_record: &HitRecord) -> Surface {
+        Surface {
+            position: hit_record.p,
+            normal: hit_record.normal,
+            albedo: hit_record.material.albedo(hit_record),
+            id: hit_record.id,
+        }
+    }
+}
+
+/// How the light along a camera ray splits at its first surface, filled in
+/// by the integrators for the output variables.
+#[derive(Clone, Copy)]
+pub struct FirstBounce {
+    pub surface: Option<Surface>,
+    /// Emitted by the surface, or the environment when there is none.
+    pub emission: Vector3,
+    /// Light scattered towards the camera by each lobe, indexed by `Lobe`:
+    /// what it passed on directly from the lights, and the light found
+    /// further along the path for the lobe that was sampled.
+    pub lobes: [Vector3; 3],
+}
+
+impl FirstBounce {
+    pub fn new() -> FirstBounce {
+        FirstBounce {
+            surface: None,
+            emission: Vector3::new(0.0, 0.0, 0.0),
+            lobes: [Vector3::new(0.0, 0.0, 0.0); 3],
+        }
+    }
+
+    /// Attenuates all of the light, as by a medium in front of the surface.
+    pub fn attenuate(&mut self, transmission: Vector3) {
+        self.emission = transmission * self.emission;
+        for lobe in self.lobes.iter_mut() {
+            *lobe = transmission * *lobe;
+        }
+    }
+}
+
+/// Spreads an identifier over colours that tell neighbours apart.
+pub fn id_color(id: u64) -> Vector3 {
+    // SplitMix64 finalizer.
+    let mut h = id.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^= h >> 31;
+    let channel = |shift: u32| ((h >> shift) & 0xff) as f64 / 255.0;
+    Vector3::new(channel(0), channel(8), channel(16))
+}
@@ -0,0 +1,210 @@
+use crate::hittables::{Evaluation, HitRecord, Material, Scatter};
+use crate::nested::Interior;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::textures::Texture;
+use crate::vector::Vector3;
+
+/// Tangent, bitangent and normal of the shading frame at a hit, with the
+/// tangent following increasing `u` and the bitangent increasing `v`.
+fn tangent_frame(hit_record: &HitRecord) -> (Vector3, Vector3, Vector3) {
+    let n = hit_record.normal;
+    let projected = hit_record.dpdu - n.dot(hit_record.dpdu) * n;
+    let tangent = if projected.squared_length() > 1e-16 {
+        projected.unit_vector()
+    } else {
+        Onb::from_w(n).world(Vector3::new(1.0, 0.0, 0.0))
+    };
+    let bitangent = n.cross(tangent);
+    if bitangent.dot(hit_record.dpdv) < 0.0 {
+        (tangent, -bitangent, n)
+    } else {
+        (tangent, bitangent, n)
+    }
+}
+
+/// Scatters off `material` with a perturbed shading normal. Rays that end up
+/// on different sides of the shading and geometric surfaces would leak light
+/// through the object or hit it from behind, so they are dropped.
+fn scatter_shaded(material: &dyn Material, ray_in: &Ray, shaded: &HitRecord) -> Option<Scatter> {
+    let scatter = material.scatter(ray_in, shaded)?;
+    let geometric = scatter.ray.direction().dot(shaded.geometric_normal);
+    let shading = scatter.ray.direction().dot(shaded.normal);
+    if geometric * shading > 0.0 {
+        Some(scatter)
+    } else {
+        None
+    }
+}
+
+/// Evaluates `material` with a perturbed shading normal, dropping the same
+/// directions as `scatter_shaded`.
+fn evaluate_shaded(
+    material: &dyn Material,
+    ray_in: &Ray,
+    shaded: &HitRecord,
+    direction: Vector3,
+) -> Option<Evaluation> {
+    let mut evaluation = material.evaluate(ray_in, shaded, direction)?;
+    if direction.dot(shaded.geometric_normal) * direction.dot(shaded.normal) <= 0.0 {
+        evaluation.lobes = [Vector3::new(0.0, 0.0, 0.0); 3];
+    }
+    Some(evaluation)
+}
+
+/// Perturbs the shading normal of another material with a tangent space
+/// normal map, stored as `0.5 * n + 0.5` with blue pointing out of the
+/// surface.
+pub struct NormalMap {
+    material: Box<dyn Material>,
+    map: Box<dyn Texture>,
+}
+
+impl NormalMap {
+    pub fn new<T: Texture + 'static>(material: Box<dyn Material>, map: T) -> NormalMap {
+        NormalMap {
+            material,
+            map: Box::new(map),
+        }
+    }
+
+    fn shade<'a>(&self, hit_record: &HitRecord<'a>) -> HitRecord<'a> {
+        let (tangent, bitangent, normal) = tangent_frame(hit_record);
+        let mapped = 2.0 * self.map.value(hit_record.u, hit_record.v, hit_record.p)
+            - Vector3::new(1.0, 1.0, 1.0);
+        let perturbed =
+            mapped.x() * tangent + mapped.y() * bitangent + mapped.z().max(1e-3) * normal;
+        let mut shaded = hit_record.clone();
+        shaded.normal = perturbed.unit_vector();
+        shaded
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        scatter_shaded(&*self.material, ray_in, &self.shade(hit_record))
+    }
+
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        evaluate_shaded(&*self.material, ray_in, &self.shade(hit_record), direction)
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Vector3 {
+        self.material.emitted(hit_record)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vector3 {
+        self.material.albedo(hit_record)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.material.is_dispersive()
+    }
+
+    fn interior(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Interior> {
+        self.material.interior(ray_in, hit_record)
+    }
+
+    fn is_opaque_at(&self, hit_record: &HitRecord) -> bool {
+        self.material.is_opaque_at(hit_record)
+    }
+}
+
+/// Perturbs the shading normal of another material as if the surface were
+/// displaced along its normal by `scale` times a scalar height texture.
+pub struct BumpMap {
+    material: Box<dyn Material>,
+    height: Box<dyn Texture>,
+    scale: f64,
+}
+
+impl BumpMap {
+    pub fn new<T: Texture + 'static>(
+        material: Box<dyn Material>,
+        height: T,
+        scale: f64,
+    ) -> BumpMap {
+        BumpMap {
+            material,
+            height: Box::new(height),
+            scale,
+        }
+    }
+
+    fn shade<'a>(&self, hit_record: &HitRecord<'a>) -> HitRecord<'a> {
+        let delta = 1e-3;
+        let (u, v, p) = (hit_record.u, hit_record.v, hit_record.p);
+        let height = self.height.value(u, v, p).x();
+        let height_u = self
+            .height
+            .value(u + delta, v, p + delta * hit_record.dpdu)
+            .x();
+        let height_v = self
+            .height
+            .value(u, v + delta, p + delta * hit_record.dpdv)
+            .x();
+
+        let normal = hit_record.normal;
+        let dpdu = hit_record.dpdu + self.scale * (height_u - height) / delta * normal;
+        let dpdv = hit_record.dpdv + self.scale * (height_v - height) / delta * normal;
+        let perturbed = dpdu.cross(dpdv);
+        let mut shaded = hit_record.clone();
+        if perturbed.squared_length() > 0.0 {
+            let perturbed = perturbed.unit_vector();
+            shaded.normal = if perturbed.dot(normal) < 0.0 {
+                -perturbed
+            } else {
+                perturbed
+            };
+        }
+        shaded
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        scatter_shaded(&*self.material, ray_in, &self.shade(hit_record))
+    }
+
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        evaluate_shaded(&*self.material, ray_in, &self.shade(hit_record), direction)
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Vector3 {
+        self.material.emitted(hit_record)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vector3 {
+        self.material.albedo(hit_record)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.material.is_dispersive()
+    }
+
+    fn interior(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Interior> {
+        self.material.interior(ray_in, hit_record)
+    }
+
+    fn is_opaque_at(&self, hit_record: &HitRecord) -> bool {
+        self.material.is_opaque_at(hit_record)
+    }
+}
@@ -0,0 +1,180 @@
+use crate::aabb::Aabb;
+use crate::hittables::{masked_hit, HitId, HitRecord, Hittable, HittableList};
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// An object and the identifiers its hits are offset by.
+type BoxedHittable = (HitId, Box<dyn Hittable + Send + Sync>);
+
+/// Most objects tested together at the bottom of the hierarchy.
+const LEAF_SIZE: usize = 4;
+
+enum Node {
+    /// Range of objects.
+    Leaf { first: usize, count: usize },
+    /// Index of the second child, the first following right after its
+    /// parent, and the axis the children were split along.
+    Interior { second: usize, axis: usize },
+}
+
+/// Bounding volume hierarchy over a list of objects, stored depth first, so
+/// that a ray only tests the objects whose boxes it passes through. It is
+/// built once and can be shared between the frames of an animation.
+pub struct Bvh {
+    objects: Vec<BoxedHittable>,
+    nodes: Vec<(Aabb, Node)>,
+    /// Objects without bounds, like infinite planes, tested by every ray.
+    unbounded: Vec<BoxedHittable>,
+    ids: HitId,
+}
+
+impl Bvh {
+    pub fn new(list: HittableList) -> Bvh {
+        let ids = list.ids();
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+        for object in list.into_objects() {
+            match object.1.bounding_box() {
+                Some(bounds) => {
+                    // So that flat objects still have some thickness to hit.
+                    let pad = Vector3::new(1e-4, 1e-4, 1e-4);
+                    bounded.push((Aabb::new(bounds.min() - pad, bounds.max() + pad), object));
+                }
+                None => unbounded.push(object),
+            }
+        }
+        let mut bvh = Bvh {
+            objects: vec![],
+            nodes: vec![],
+            unbounded,
+            ids,
+        };
+        if !bounded.is_empty() {
+            bvh.build(&mut bounded, 0);
+        }
+        bvh.objects = bounded.into_iter().map(|(_, object)| object).collect();
+        bvh
+    }
+
+    /// Splits `objects`, which start at `first` in the final order, in half
+    /// along the widest spread of their centres.
+    fn build(&mut self, objects: &mut [(Aabb, BoxedHittable)], first: usize) -> Aabb {
+        let bounds = objects[1..]
+            .iter()
+            .fold(objects[0].0, |bounds, (b, _)| Aabb::surrounding(bounds, *b));
+        if objects.len() <= LEAF_SIZE {
+            let count = objects.len();
+            self.nodes.push((bounds, Node::Leaf { first, count }));
+            return bounds;
+        }
+        let centre = |bounds: &Aabb| 0.5 * (bounds.min() + bounds.max());
+        let spread = objects[1..].iter().fold(
+            Aabb::new(centre(&objects[0].0), centre(&objects[0].0)),
+            |spread, (bounds, _)| {
+                let c = centre(bounds);
+                Aabb::surrounding(spread, Aabb::new(c, c))
+            },
+        );
+        let extent = spread.extent();
+        let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        };
+        objects.sort_by(|a, b| centre(&a.0)[axis].total_cmp(&centre(&b.0)[axis]));
+
+        let node = self.nodes.len();
+        // Placeholder until both children are built.
+        self.nodes
+            .push((bounds, Node::Interior { second: 0, axis }));
+        let half = objects.len() / 2;
+        let (lower, upper) = objects.split_at_mut(half);
+        self.build(lower, first);
+        let second = self.nodes.len();
+        self.build(upper, first + half);
+        self.nodes[node].1 = Node::Interior { second, axis };
+        bounds
+    }
+
+    /// Calls `visit` with the objects in every leaf whose box the ray passes
+    /// through within `t_min` and the `t_max` that `visit` returns, nearer
+    /// children first.
+    fn traverse<'a, F>(&'a self, ray: &Ray, t_min: f64, t_max: f64, mut visit: F)
+    where
+        F: FnMut(&'a [BoxedHittable]) -> f64,
+    {
+        let mut t_max = t_max.min(visit(&self.unbounded));
+        let mut stack = [0; 64];
+        let mut depth = usize::from(!self.nodes.is_empty());
+        while depth > 0 {
+            depth -= 1;
+            let (bounds, node) = &self.nodes[stack[depth]];
+            if bounds.hit(ray, t_min, t_max).is_none() {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count } => {
+                    t_max = t_max.min(visit(&self.objects[first..first + count]));
+                }
+                Node::Interior { second, axis } => {
+                    let first = stack[depth] + 1;
+                    let (near, far) = if ray.direction()[axis] < 0.0 {
+                        (second, first)
+                    } else {
+                        (first, second)
+                    };
+                    stack[depth] = far;
+                    stack[depth + 1] = near;
+                    depth += 2;
+                }
+            }
+        }
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut hit_record: Option<HitRecord> = None;
+        let mut closest_so_far = t_max;
+        self.traverse(ray, t_min, t_max, |objects| {
+            for (offset, object) in objects {
+                if let Some(mut hit) = masked_hit(object.as_ref(), ray, t_min, closest_so_far) {
+                    closest_so_far = hit.t;
+                    hit.id = hit.id + *offset;
+                    hit_record = Some(hit);
+                }
+            }
+            closest_so_far
+        });
+        hit_record
+    }
+
+    fn ids(&self) -> HitId {
+        self.ids
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.nodes.first().map(|(bounds, _)| *bounds)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        self.traverse(ray, t_min, t_max, |objects| {
+            for (_, object) in objects {
+                transmittance *= object.transmittance(ray, t_min, t_max);
+            }
+            // Nothing left to look for once the light is blocked.
+            if transmittance <= 0.0 {
+                f64::MIN
+            } else {
+                t_max
+            }
+        });
+        transmittance
+    }
+}
@@ -0,0 +1,543 @@
+use crate::environment::Distribution1D;
+use crate::ppm;
+use crate::ray::Ray;
+use crate::rng::{random_f64, random_in_unit_disk};
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Turns a point on the image, with `s` running left to right and `t` bottom
+/// to top over `[0, 1]`, into a ray leaving the camera.
+pub trait Camera: Send + Sync {
+    /// `None` where the projection does not cover the image, like the corners
+    /// outside a circular fisheye.
+    fn generate_ray(&self, s: f64, t: f64) -> Option<Ray>;
+}
+
+/// Where a camera is and how it is turned: `u` to the right, `v` up and `w`
+/// backwards, away from where it looks.
+#[derive(Clone, Copy)]
+pub struct CameraFrame {
+    origin: Vector3,
+    u: Vector3,
+    v: Vector3,
+    w: Vector3,
+}
+
+impl CameraFrame {
+    pub fn look_at(lookfrom: Vector3, lookat: Vector3, vup: Vector3) -> CameraFrame {
+        let w = (lookfrom - lookat).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        CameraFrame {
+            origin: lookfrom,
+            u,
+            v: w.cross(u),
+            w,
+        }
+    }
+
+    /// The same frame moved `offset` to the right.
+    fn shifted(&self, offset: f64) -> CameraFrame {
+        CameraFrame {
+            origin: self.origin + offset * self.u,
+            ..*self
+        }
+    }
+
+    /// Maps a direction from camera space, with -z forwards, to the world.
+    pub fn world(&self, direction: Vector3) -> Vector3 {
+        direction.x() * self.u + direction.y() * self.v + direction.z() * self.w
+    }
+
+    /// Maps a point from camera space, centred on the camera, to the world.
+    pub fn point(&self, point: Vector3) -> Vector3 {
+        self.origin + self.world(point)
+    }
+}
+
+/// Greyscale image of a lens opening, letting light through where it is
+/// bright. Points on the opening are picked in proportion to how much light
+/// each texel lets through, by inverting a CDF over its rows and then over
+/// the texels in the row.
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    rows: Distribution1D,
+    columns: Vec<Distribution1D>,
+}
+
+impl ApertureMask {
+    pub fn load(path: &Path) -> Result<ApertureMask, io::Error> {
+        let (width, height, colors) = ppm::read_ppm(path)?;
+        let (width, height) = (width as usize, height as usize);
+        let columns: Vec<Distribution1D> = colors
+            .chunks_exact(width)
+            .map(|row| {
+                let weights: Vec<f64> = row.iter().map(|c| c.luminance().clamp(0.0, 1.0)).collect();
+                Distribution1D::new(&weights)
+            })
+            .collect();
+        let row_weights: Vec<f64> = columns.iter().map(Distribution1D::total).collect();
+        Ok(ApertureMask {
+            width,
+            height,
+            rows: Distribution1D::new(&row_weights),
+            columns,
+        })
+    }
+
+    /// Point in `[-1, 1]` with the top row at `y = 1`, or `None` if the mask
+    /// lets no light through at all.
+    fn sample(&self) -> Option<(f64, f64)> {
+        if self.rows.total() <= 0.0 {
+            return None;
+        }
+        let (row, dy) = self.rows.sample(random_f64());
+        let (column, dx) = self.columns[row].sample(random_f64());
+        Some((
+            2.0 * (column as f64 + dx) / self.width as f64 - 1.0,
+            1.0 - 2.0 * (row as f64 + dy) / self.height as f64,
+        ))
+    }
+}
+
+/// Shape of the lens opening, which out of focus highlights take on.
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    /// Regular polygon formed by `blades` straight diaphragm blades, turned
+    /// by `rotation` radians.
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    /// Image covering the square around the opening. Like the other shapes
+    /// it lets the same light through however much of it is open, as if the
+    /// exposure were adjusted to match.
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    pub fn polygon(blades: u32, rotation: f64) -> Aperture {
+        if blades < 3 {
+            return Aperture::Circle;
+        }
+        Aperture::Polygon {
+            blades,
+            rotation: rotation.to_radians(),
+        }
+    }
+
+    /// Point on the opening scaled to the unit disk, or `None` for a mask
+    /// that is closed everywhere.
+    fn sample(&self) -> Option<(f64, f64)> {
+        match self {
+            Aperture::Circle => {
+                let p = random_in_unit_disk();
+                Some((p.x(), p.y()))
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // A uniform point in one of the equal triangles fanning out
+                // from the centre.
+                let wedge = ((random_f64() * *blades as f64) as u32).min(blades - 1);
+                let angle = |k: u32| rotation + 2.0 * PI * k as f64 / *blades as f64;
+                let (a, b) = (angle(wedge), angle(wedge + 1));
+                let r = random_f64().sqrt();
+                let along = random_f64();
+                Some((
+                    r * ((1.0 - along) * a.cos() + along * b.cos()),
+                    r * ((1.0 - along) * a.sin() + along * b.sin()),
+                ))
+            }
+            Aperture::Mask(mask) => mask.sample(),
+        }
+    }
+}
+
+/// Thin lens perspective camera, focused at `focus_dist` and blurring what
+/// is nearer or further the wider its `aperture`.
+#[derive(Clone)]
+pub struct PerspectiveCamera {
+    frame: CameraFrame,
+    focus_dist: f64,
+    lower_left_corner: Vector3,
+    horizontal: Vector3,
+    vertical: Vector3,
+    lens_radius: f64,
+    aperture: Aperture,
+    cats_eye: f64,
+    /// Point and normal of the plane in focus.
+    focus_point: Vector3,
+    focus_normal: Vector3,
+}
+
+impl PerspectiveCamera {
+    pub fn new(
+        lookfrom: Vector3,
+        lookat: Vector3,
+        vup: Vector3,
+        vfov: f64,
+        aspect: f64,
+        aperture: f64,
+        focus_dist: f64,
+    ) -> PerspectiveCamera {
+        let frame = CameraFrame::look_at(lookfrom, lookat, vup);
+        let theta = vfov * PI / 180.0;
+        let half_height = (theta / 2.0).tan();
+        let half_width = aspect * half_height;
+        let CameraFrame { u, v, w, .. } = frame;
+        let lower_left_corner =
+            lookfrom - half_width * focus_dist * u - half_height * focus_dist * v - focus_dist * w;
+
+        PerspectiveCamera {
+            frame,
+            focus_dist,
+            lower_left_corner,
+            horizontal: 2.0 * half_width * focus_dist * u,
+            vertical: 2.0 * half_height * focus_dist * v,
+            lens_radius: aperture / 2.0,
+            aperture: Aperture::Circle,
+            cats_eye: 0.0,
+            focus_point: lookfrom - focus_dist * w,
+            focus_normal: -w,
+        }
+    }
+
+    pub fn with_aperture(mut self, aperture: Aperture) -> PerspectiveCamera {
+        self.aperture = aperture;
+        self
+    }
+
+    /// Clips the opening towards the edges of the image, as the lens barrel
+    /// does, turning out of focus highlights there into cat's eyes and
+    /// darkening the corners. `amount` is how far the barrel's circle moves
+    /// across the opening at the edges, in aperture radii.
+    pub fn with_cats_eye(mut self, amount: f64) -> PerspectiveCamera {
+        self.cats_eye = amount.max(0.0);
+        self
+    }
+
+    /// Tilts the plane in focus, as a tilt-shift lens does, by `tilt`
+    /// degrees about the horizontal axis, bringing its top towards the
+    /// camera for positive angles, and `swing` degrees about the vertical.
+    pub fn with_tilt(mut self, tilt: f64, swing: f64) -> PerspectiveCamera {
+        let (tilt, swing) = (tilt.to_radians(), swing.to_radians());
+        let CameraFrame { u, v, w, .. } = self.frame;
+        self.focus_normal =
+            (-tilt.cos() * swing.cos() * w + tilt.sin() * v + swing.sin() * u).unit_vector();
+        self
+    }
+
+    /// The view of an eye `offset` to the right, looking the same way with
+    /// its image window shifted so that things at distance `convergence`
+    /// line up in both eyes, and `widen` times as wide.
+    fn eye(&self, offset: f64, convergence: f64, widen: f64) -> PerspectiveCamera {
+        let center = self.lower_left_corner + 0.5 * (self.horizontal + self.vertical);
+        let horizontal = widen * self.horizontal;
+        let shift = offset * (1.0 - self.focus_dist / convergence) * self.frame.u;
+        PerspectiveCamera {
+            frame: self.frame.shifted(offset),
+            lower_left_corner: center - 0.5 * (horizontal + self.vertical) + shift,
+            horizontal,
+            ..self.clone()
+        }
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn generate_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let (x, y) = self.aperture.sample()?;
+        if self.cats_eye > 0.0 {
+            let barrel_x = -self.cats_eye * (2.0 * s - 1.0);
+            let barrel_y = -self.cats_eye * (2.0 * t - 1.0);
+            if (x - barrel_x).powi(2) + (y - barrel_y).powi(2) > 1.0 {
+                return None;
+            }
+        }
+        // Where the ray through the centre of the lens meets the plane in
+        // focus, which every ray through the lens for this pixel goes to.
+        let target = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        let through = target - self.frame.origin;
+        let facing = through.dot(self.focus_normal);
+        let focus = if facing > 1e-9 {
+            let distance = (self.focus_point - self.frame.origin).dot(self.focus_normal);
+            self.frame.origin + (distance / facing) * through
+        } else {
+            target
+        };
+        let offset = self.lens_radius * (x * self.frame.u + y * self.frame.v);
+        let origin = self.frame.origin + offset;
+        Some(Ray::new(origin, focus - origin))
+    }
+}
+
+/// Parallel projection showing a `width` by `height` window of the scene
+/// without perspective, as in technical elevations.
+pub struct OrthographicCamera {
+    frame: CameraFrame,
+    width: f64,
+    height: f64,
+}
+
+impl OrthographicCamera {
+    pub fn new(frame: CameraFrame, width: f64, height: f64) -> OrthographicCamera {
+        OrthographicCamera {
+            frame,
+            width,
+            height,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn generate_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let origin = self.frame.origin
+            + (s - 0.5) * self.width * self.frame.u
+            + (t - 0.5) * self.height * self.frame.v;
+        Some(Ray::new(origin, -self.frame.w))
+    }
+}
+
+/// How a fisheye lens spaces angles from its axis across the image circle.
+#[derive(Clone, Copy)]
+pub enum FisheyeMapping {
+    /// Distance from the centre proportional to the angle.
+    Equidistant,
+    /// Equal areas on the image for equal solid angles.
+    Equisolid,
+}
+
+/// Circular fisheye covering `fov` degrees across the image circle, which
+/// fills the height of the image.
+pub struct FisheyeCamera {
+    frame: CameraFrame,
+    half_fov: f64,
+    aspect: f64,
+    mapping: FisheyeMapping,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        frame: CameraFrame,
+        fov: f64,
+        aspect: f64,
+        mapping: FisheyeMapping,
+    ) -> FisheyeCamera {
+        FisheyeCamera {
+            frame,
+            half_fov: 0.5 * fov.to_radians().min(2.0 * PI),
+            aspect,
+            mapping,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn generate_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let x = (2.0 * s - 1.0) * self.aspect;
+        let y = 2.0 * t - 1.0;
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => radius * self.half_fov,
+            FisheyeMapping::Equisolid => {
+                2.0 * (radius * (0.5 * self.half_fov).sin())
+                    .clamp(-1.0, 1.0)
+                    .asin()
+            }
+        };
+        let phi = y.atan2(x);
+        let direction = Vector3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            -theta.cos(),
+        );
+        Some(Ray::new(self.frame.origin, self.frame.world(direction)))
+    }
+}
+
+/// Equirectangular panorama of everything around the camera, 360 degrees
+/// across and 180 up, with the view direction in the middle. Images twice
+/// as wide as they are tall keep texels square.
+pub struct PanoramaCamera {
+    frame: CameraFrame,
+    eye_offset: f64,
+}
+
+impl PanoramaCamera {
+    pub fn new(frame: CameraFrame) -> PanoramaCamera {
+        PanoramaCamera {
+            frame,
+            eye_offset: 0.0,
+        }
+    }
+
+    /// Makes this one eye of an omni-directional stereo panorama, looking
+    /// out from `offset` to the right of the centre whichever way it faces.
+    pub fn with_eye_offset(mut self, offset: f64) -> PanoramaCamera {
+        self.eye_offset = offset;
+        self
+    }
+}
+
+impl Camera for PanoramaCamera {
+    fn generate_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let phi = 2.0 * PI * (s - 0.5);
+        let latitude = PI * (t - 0.5);
+        let direction = Vector3::new(
+            latitude.cos() * phi.sin(),
+            latitude.sin(),
+            -latitude.cos() * phi.cos(),
+        );
+        // The eyes come together towards the poles, where they would
+        // otherwise see different things for every direction around them.
+        let right = Vector3::new(phi.cos(), 0.0, phi.sin());
+        let eye = self.eye_offset * latitude.cos() * right;
+        Some(Ray::new(
+            self.frame.origin + self.frame.world(eye),
+            self.frame.world(direction),
+        ))
+    }
+}
+
+/// Projection to render with in place of the perspective camera a renderer
+/// sets up, keeping its position and framing.
+#[derive(Clone, Copy, Default)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    /// Showing the window the perspective camera sees at its focus distance.
+    Orthographic,
+    /// 180 degree circular fisheye.
+    Fisheye(FisheyeMapping),
+    Panorama,
+}
+
+impl Projection {
+    /// Camera for this projection standing in for `perspective`, or a pair
+    /// of them for `stereo`.
+    pub fn camera(self, perspective: PerspectiveCamera, stereo: Option<Stereo>) -> Box<dyn Camera> {
+        let stereo = match stereo {
+            Some(stereo) => stereo,
+            None => return self.eye(&perspective, 0.0, perspective.focus_dist, 1.0),
+        };
+        let convergence = stereo.convergence.unwrap_or(perspective.focus_dist);
+        // Each eye gets half the image, but keeps the height it would have
+        // had. Panoramas always cover everything.
+        let widen = match (self, stereo.layout) {
+            (Projection::Panorama, _) => 1.0,
+            (_, StereoLayout::SideBySide) => 0.5,
+            (_, StereoLayout::TopBottom) => 2.0,
+        };
+        let half = 0.5 * stereo.eye_separation;
+        Box::new(StereoCamera {
+            left: self.eye(&perspective, -half, convergence, widen),
+            right: self.eye(&perspective, half, convergence, widen),
+            layout: stereo.layout,
+        })
+    }
+
+    /// Camera for an eye `offset` to the right of `perspective`, `widen`
+    /// times as wide.
+    fn eye(
+        self,
+        perspective: &PerspectiveCamera,
+        offset: f64,
+        convergence: f64,
+        widen: f64,
+    ) -> Box<dyn Camera> {
+        let frame = perspective.frame.shifted(offset);
+        let width = widen * perspective.horizontal.length();
+        let height = perspective.vertical.length();
+        match self {
+            Projection::Perspective => Box::new(perspective.eye(offset, convergence, widen)),
+            Projection::Orthographic => Box::new(OrthographicCamera::new(frame, width, height)),
+            Projection::Fisheye(mapping) => {
+                Box::new(FisheyeCamera::new(frame, 180.0, width / height, mapping))
+            }
+            Projection::Panorama => {
+                Box::new(PanoramaCamera::new(perspective.frame).with_eye_offset(offset))
+            }
+        }
+    }
+}
+
+impl FromStr for Projection {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Projection, String> {
+        match name {
+            "perspective" => Ok(Projection::Perspective),
+            "orthographic" => Ok(Projection::Orthographic),
+            "fisheye" => Ok(Projection::Fisheye(FisheyeMapping::Equidistant)),
+            "equisolid" => Ok(Projection::Fisheye(FisheyeMapping::Equisolid)),
+            "panorama" => Ok(Projection::Panorama),
+            _ => Err(format!("unknown camera projection {}", name)),
+        }
+    }
+}
+
+/// How the two views of a stereo pair are packed into one image.
+#[derive(Clone, Copy)]
+pub enum StereoLayout {
+    /// Left eye on the left half.
+    SideBySide,
+    /// Left eye on the top half.
+    TopBottom,
+}
+
+/// Settings for rendering a stereo pair instead of a single view.
+#[derive(Clone, Copy)]
+pub struct Stereo {
+    pub layout: StereoLayout,
+    /// Interpupillary distance, in scene units.
+    pub eye_separation: f64,
+    /// Distance at which both eyes see the same point of the image, by
+    /// default the focus distance.
+    pub convergence: Option<f64>,
+}
+
+impl Stereo {
+    pub fn new(layout: StereoLayout) -> Stereo {
+        Stereo {
+            layout,
+            eye_separation: 0.064,
+            convergence: None,
+        }
+    }
+}
+
+impl FromStr for Stereo {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Stereo, String> {
+        match name {
+            "side-by-side" => Ok(Stereo::new(StereoLayout::SideBySide)),
+            "top-bottom" => Ok(Stereo::new(StereoLayout::TopBottom)),
+            _ => Err(format!("unknown stereo layout {}", name)),
+        }
+    }
+}
+
+/// Both eyes of a stereo pair packed into one image.
+struct StereoCamera {
+    left: Box<dyn Camera>,
+    right: Box<dyn Camera>,
+    layout: StereoLayout,
+}
+
+impl Camera for StereoCamera {
+    fn generate_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        match self.layout {
+            StereoLayout::SideBySide if s < 0.5 => self.left.generate_ray(2.0 * s, t),
+            StereoLayout::SideBySide => self.right.generate_ray(2.0 * s - 1.0, t),
+            StereoLayout::TopBottom if t >= 0.5 => self.left.generate_ray(s, 2.0 * t - 1.0),
+            StereoLayout::TopBottom => self.right.generate_ray(s, 2.0 * t),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use crate::aabb::Aabb;
+use crate::hittables::{HitId, HitRecord, Hittable, Interval, Solid};
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+type BoxedSolid = Box<dyn Solid + Send + Sync>;
+
+fn first_boundary<'a>(
+    intervals: Vec<Interval<'a>>,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    for interval in intervals {
+        if interval.enter.t < t_max && interval.enter.t > t_min {
+            return Some(interval.enter);
+        }
+        if interval.exit.t < t_max && interval.exit.t > t_min {
+            return Some(interval.exit);
+        }
+    }
+    None
+}
+
+/// A boundary taken from the subtracted solid faces the other way.
+fn flipped(mut hit_record: HitRecord) -> HitRecord {
+    hit_record.normal = -hit_record.normal;
+    hit_record.geometric_normal = -hit_record.geometric_normal;
+    hit_record
+}
+
+/// Numbers the materials of the right solid after those of the left, the
+/// whole being a single object.
+fn renumbered<'a>(left: &BoxedSolid, intervals: Vec<Interval<'a>>) -> Vec<Interval<'a>> {
+    let offset = HitId {
+        object: 0,
+        material: left.ids().material,
+    };
+    intervals
+        .into_iter()
+        .map(|mut interval| {
+            interval.enter.id = interval.enter.id + offset;
+            interval.exit.id = interval.exit.id + offset;
+            interval
+        })
+        .collect()
+}
+
+/// One object with the materials of both solids.
+fn combined_ids(left: &BoxedSolid, right: &BoxedSolid) -> HitId {
+    HitId {
+        object: 1,
+        material: left.ids().material + right.ids().material,
+    }
+}
+
+fn union<'a>(a: Vec<Interval<'a>>, b: Vec<Interval<'a>>) -> Vec<Interval<'a>> {
+    let mut all: Vec<Interval> = a.into_iter().chain(b).collect();
+    all.sort_by(|i1, i2| i1.enter.t.total_cmp(&i2.enter.t));
+
+    let mut merged: Vec<Interval> = Vec::with_capacity(all.len());
+    for interval in all {
+        match merged.last_mut() {
+            Some(last) if interval.enter.t <= last.exit.t => {
+                if interval.exit.t > last.exit.t {
+                    last.exit = interval.exit;
+                }
+            }
+            _ => merged.push(interval),
+        }
+    }
+    merged
+}
+
+fn intersection<'a>(a: Vec<Interval<'a>>, b: Vec<Interval<'a>>) -> Vec<Interval<'a>> {
+    let mut result = vec![];
+    for ia in &a {
+        for ib in &b {
+            let enter = if ia.enter.t > ib.enter.t {
+                &ia.enter
+            } else {
+                &ib.enter
+            };
+            let exit = if ia.exit.t < ib.exit.t {
+                &ia.exit
+            } else {
+                &ib.exit
+            };
+            if enter.t < exit.t {
+                result.push(Interval {
+                    enter: enter.clone(),
+                    exit: exit.clone(),
+                });
+            }
+        }
+    }
+    result.sort_by(|i1, i2| i1.enter.t.total_cmp(&i2.enter.t));
+    result
+}
+
+fn difference<'a>(a: Vec<Interval<'a>>, b: Vec<Interval<'a>>) -> Vec<Interval<'a>> {
+    let mut result = vec![];
+    for ia in a {
+        let mut pieces = vec![ia];
+        for ib in &b {
+            let mut remaining = vec![];
+            for piece in pieces {
+                if ib.exit.t <= piece.enter.t || ib.enter.t >= piece.exit.t {
+                    remaining.push(piece);
+                    continue;
+                }
+                if ib.enter.t > piece.enter.t {
+                    remaining.push(Interval {
+                        enter: piece.enter.clone(),
+                        exit: flipped(ib.enter.clone()),
+                    });
+                }
+                if ib.exit.t < piece.exit.t {
+                    remaining.push(Interval {
+                        enter: flipped(ib.exit.clone()),
+                        exit: piece.exit,
+                    });
+                }
+            }
+            pieces = remaining;
+        }
+        result.append(&mut pieces);
+    }
+    result
+}
+
+/// Points inside either solid.
+pub struct Union {
+    left: BoxedSolid,
+    right: BoxedSolid,
+}
+
+impl Union {
+    pub fn new<A, B>(left: A, right: B) -> Union
+    where
+        A: Solid + Send + Sync + 'static,
+        B: Solid + Send + Sync + 'static,
+    {
+        Union {
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+}
+
+impl Solid for Union {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        union(
+            self.left.intervals(ray),
+            renumbered(&self.left, self.right.intervals(ray)),
+        )
+    }
+}
+
+impl Hittable for Union {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_boundary(self.intervals(ray), t_min, t_max)
+    }
+
+    fn ids(&self) -> HitId {
+        combined_ids(&self.left, &self.right)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let left = self.left.bounding_box()?;
+        let right = self.right.bounding_box()?;
+        Some(Aabb::surrounding(left, right))
+    }
+}
+
+/// Points inside both solids.
+pub struct Intersection {
+    left: BoxedSolid,
+    right: BoxedSolid,
+}
+
+impl Intersection {
+    pub fn new<A, B>(left: A, right: B) -> Intersection
+    where
+        A: Solid + Send + Sync + 'static,
+        B: Solid + Send + Sync + 'static,
+    {
+        Intersection {
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+}
+
+impl Solid for Intersection {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        intersection(
+            self.left.intervals(ray),
+            renumbered(&self.left, self.right.intervals(ray)),
+        )
+    }
+}
+
+impl Hittable for Intersection {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_boundary(self.intervals(ray), t_min, t_max)
+    }
+
+    fn ids(&self) -> HitId {
+        combined_ids(&self.left, &self.right)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match (self.left.bounding_box(), self.right.bounding_box()) {
+            (Some(left), Some(right)) => {
+                let min = Vector3::new(
+                    left.min().x().max(right.min().x()),
+                    left.min().y().max(right.min().y()),
+                    left.min().z().max(right.min().z()),
+                );
+                let max = Vector3::new(
+                    left.max().x().min(right.max().x()),
+                    left.max().y().min(right.max().y()),
+                    left.max().z().min(right.max().z()),
+                );
+                Some(Aabb::new(min, max))
+            }
+            (Some(bounds), None) | (None, Some(bounds)) => Some(bounds),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Points inside the left solid but not the right one. Surfaces carved out by
+/// the right solid keep its material.
+pub struct Difference {
+    left: BoxedSolid,
+    right: BoxedSolid,
+}
+
+impl Difference {
+    pub fn new<A, B>(left: A, right: B) -> Difference
+    where
+        A: Solid + Send + Sync + 'static,
+        B: Solid + Send + Sync + 'static,
+    {
+        Difference {
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+}
+
+impl Solid for Difference {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let left = self.left.intervals(ray);
+        if left.is_empty() {
+            return left;
+        }
+        difference(left, renumbered(&self.left, self.right.intervals(ray)))
+    }
+}
+
+impl Hittable for Difference {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_boundary(self.intervals(ray), t_min, t_max)
+    }
+
+    fn ids(&self) -> HitId {
+        combined_ids(&self.left, &self.right)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.left.bounding_box()
+    }
+}
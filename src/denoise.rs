@@ -0,0 +1,126 @@
+use crate::image::Framebuffer;
+use crate::vector::Vector3;
+
+/// Half width in pixels of the window each pixel is filtered over.
+const WINDOW_RADIUS: i64 = 7;
+/// Half width of the patches compared to decide how alike two pixels are.
+const PATCH_RADIUS: i64 = 1;
+/// How many standard deviations of noise apart two patches may be and still
+/// be blended.
+const COLOR_TOLERANCE: f64 = 0.6;
+const SPATIAL_SIGMA: f64 = 4.0;
+const NORMAL_SIGMA: f64 = 0.4;
+const ALBEDO_SIGMA: f64 = 0.1;
+/// Albedo below which a channel is filtered as it is rather than divided.
+const MIN_ALBEDO: f64 = 0.02;
+
+/// Per channel albedo to divide lighting by, one where there is too little.
+fn albedo_factor(albedo: Vector3) -> Vector3 {
+    let factor = |a: f64| if a > MIN_ALBEDO { a } else { 1.0 };
+    Vector3::new(factor(albedo.x()), factor(albedo.y()), factor(albedo.z()))
+}
+
+/// Averages each pixel with its neighbours in a `radius` wide box.
+fn box_blur(image: &Framebuffer, radius: i64) -> Framebuffer {
+    let (width, height) = (image.get_width() as i64, image.get_height() as i64);
+    let mut blurred = Framebuffer::new(image.get_width(), image.get_height());
+    for x in 0..height {
+        for y in 0..width {
+            let mut sum = Vector3::new(0.0, 0.0, 0.0);
+            let mut count = 0.0;
+            for nx in (x - radius).max(0)..=(x + radius).min(height - 1) {
+                for ny in (y - radius).max(0)..=(y + radius).min(width - 1) {
+                    sum += image.get(nx as u32, ny as u32);
+                    count += 1.0;
+                }
+            }
+            blurred.set(x as u32, y as u32, sum / count);
+        }
+    }
+    blurred
+}
+
+/// Removes Monte Carlo noise from a rendered image, given the variance of
+/// each pixel's estimate and its albedo and normal passes as guides.
+///
+/// Each pixel is replaced by a weighted average over a window around it, a
+/// joint non-local means filter after Rousselle et al., "Robust Denoising
+/// using Feature and Color Information": neighbours count for less the
+/// further away they are, the more their surroundings differ by more than
+/// the noise explains, and the more their normal and albedo differ. The
+/// lighting is filtered with the albedo divided out and multiplied back in
+/// afterwards, so texture detail is kept sharp.
+pub fn denoise(
+    color: &Framebuffer,
+    variance: &Framebuffer,
+    albedo: &Framebuffer,
+    normal: &Framebuffer,
+) -> Framebuffer {
+    let (width, height) = (color.get_width(), color.get_height());
+    let mut lighting = Framebuffer::new(width, height);
+    let mut lighting_variance = Framebuffer::new(width, height);
+    for x in 0..height {
+        for y in 0..width {
+            let factor = albedo_factor(albedo.get(x, y));
+            lighting.set(x, y, color.get(x, y) / factor);
+            lighting_variance.set(x, y, variance.get(x, y) / (factor * factor));
+        }
+    }
+    // The variance is estimated from few samples too, so it is smoothed.
+    let lighting_variance = box_blur(&lighting_variance, 1);
+
+    let (w, h) = (width as i64, height as i64);
+    let at = |image: &Framebuffer, x: i64, y: i64| {
+        image.get(x.clamp(0, h - 1) as u32, y.clamp(0, w - 1) as u32)
+    };
+    // Noise normalized squared distance between single pixels, which can
+    // come out negative where they differ less than the noise would.
+    let pixel_distance = |p: (i64, i64), q: (i64, i64)| {
+        let difference = at(&lighting, p.0, p.1) - at(&lighting, q.0, q.1);
+        let variance_p = at(&lighting_variance, p.0, p.1);
+        let variance_q = at(&lighting_variance, q.0, q.1);
+        let mut distance = 0.0;
+        for c in 0..3 {
+            let (vp, vq) = (variance_p[c], variance_q[c]);
+            let cancelled = vp + vp.min(vq);
+            let scale = 1e-4 + COLOR_TOLERANCE * COLOR_TOLERANCE * (vp + vq);
+            distance += (difference[c] * difference[c] - cancelled) / scale;
+        }
+        distance / 3.0
+    };
+    let patch_size = ((2 * PATCH_RADIUS + 1) * (2 * PATCH_RADIUS + 1)) as f64;
+
+    let mut filtered = Framebuffer::new(width, height);
+    for x in 0..h {
+        for y in 0..w {
+            let normal_p = normal.get(x as u32, y as u32);
+            let albedo_p = albedo.get(x as u32, y as u32);
+            let mut sum = Vector3::new(0.0, 0.0, 0.0);
+            let mut total = 0.0;
+            for nx in (x - WINDOW_RADIUS).max(0)..=(x + WINDOW_RADIUS).min(h - 1) {
+                for ny in (y - WINDOW_RADIUS).max(0)..=(y + WINDOW_RADIUS).min(w - 1) {
+                    let mut patch = 0.0;
+                    for dx in -PATCH_RADIUS..=PATCH_RADIUS {
+                        for dy in -PATCH_RADIUS..=PATCH_RADIUS {
+                            patch += pixel_distance((x + dx, y + dy), (nx + dx, ny + dy));
+                        }
+                    }
+                    let color_distance = (patch / patch_size).max(0.0);
+                    let spatial = ((nx - x).pow(2) + (ny - y).pow(2)) as f64;
+                    let normal_q = normal.get(nx as u32, ny as u32);
+                    let albedo_q = albedo.get(nx as u32, ny as u32);
+                    let weight = (-spatial / (2.0 * SPATIAL_SIGMA * SPATIAL_SIGMA)
+                        - color_distance
+                        - (normal_p - normal_q).squared_length() / (NORMAL_SIGMA * NORMAL_SIGMA)
+                        - (albedo_p - albedo_q).squared_length() / (ALBEDO_SIGMA * ALBEDO_SIGMA))
+                        .exp();
+                    sum += weight * lighting.get(nx as u32, ny as u32);
+                    total += weight;
+                }
+            }
+            let factor = albedo_factor(albedo_p);
+            filtered.set(x as u32, y as u32, factor * sum / total);
+        }
+    }
+    filtered
+}
@@ -0,0 +1,251 @@
+use crate::hdr;
+use crate::rng::random_f64;
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+
+/// A direction towards a light picked by sampling it, with the radiance
+/// arriving from there and the solid angle density it was picked with.
+pub struct LightSample {
+    pub direction: Vector3,
+    pub radiance: Vector3,
+    pub pdf: f64,
+    /// How far along `direction` the light is, infinite for the environment.
+    pub distance: f64,
+}
+
+/// Light arriving from infinitely far away, seen by rays that leave the
+/// scene.
+pub trait Environment: Send + Sync {
+    fn radiance(&self, direction: Vector3) -> Vector3;
+
+    /// Picks a direction to send a shadow ray towards, or `None` if the
+    /// environment is only found by rays escaping the scene.
+    fn sample(&self) -> Option<LightSample> {
+        None
+    }
+
+    /// Density with which `sample` picks `direction`.
+    fn pdf(&self, _direction: Vector3) -> f64 {
+        0.0
+    }
+}
+
+/// The default sky, a gradient from white at the horizon to light blue.
+pub struct SkyGradient;
+
+impl Environment for SkyGradient {
+    fn radiance(&self, direction: Vector3) -> Vector3 {
+        let t = 0.5 * (direction.unit_vector().y() + 1.0);
+        (1.0 - t) * Vector3::new(1.0, 1.0, 1.0) + t * Vector3::new(0.5, 0.7, 1.0)
+    }
+}
+
+/// The same light from every direction, black for scenes lit only by
+/// lights.
+pub struct ConstantEnvironment {
+    radiance: Vector3,
+}
+
+impl ConstantEnvironment {
+    pub fn new(radiance: Vector3) -> ConstantEnvironment {
+        ConstantEnvironment { radiance }
+    }
+}
+
+impl Environment for ConstantEnvironment {
+    fn radiance(&self, _direction: Vector3) -> Vector3 {
+        self.radiance
+    }
+}
+
+/// Piecewise constant distribution over `[0, 1)` for inverting a CDF.
+pub struct Distribution1D {
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution1D {
+    pub fn new(weights: &[f64]) -> Distribution1D {
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        let mut total = 0.0;
+        cdf.push(0.0);
+        for weight in weights {
+            total += weight.max(0.0);
+            cdf.push(total);
+        }
+        Distribution1D { cdf, total }
+    }
+
+    /// Sum of the weights.
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    fn len(&self) -> usize {
+        self.cdf.len() - 1
+    }
+
+    /// Probability of picking `index`.
+    fn probability(&self, index: usize) -> f64 {
+        if self.total <= 0.0 {
+            return 0.0;
+        }
+        (self.cdf[index + 1] - self.cdf[index]) / self.total
+    }
+
+    /// Picks a bin, returning it with the offset of `u` inside of it.
+    pub fn sample(&self, u: f64) -> (usize, f64) {
+        let target = u * self.total;
+        let index = self.cdf[1..]
+            .partition_point(|&value| value <= target)
+            .min(self.len() - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (target - self.cdf[index]) / width
+        } else {
+            0.5
+        };
+        (index, offset.clamp(0.0, 1.0))
+    }
+}
+
+/// An equirectangular (latitude-longitude) image of the light around the
+/// scene, with the top row straight up. Bright regions like the sun are
+/// importance sampled through a luminance CDF over the texels.
+pub struct ImageEnvironment {
+    width: usize,
+    height: usize,
+    texels: Vec<Vector3>,
+    intensity: f64,
+    /// Rotation around the vertical axis in radians.
+    rotation: f64,
+    rows: Distribution1D,
+    columns: Vec<Distribution1D>,
+}
+
+impl ImageEnvironment {
+    /// Texels are given row by row from the top.
+    pub fn new(width: u32, height: u32, texels: Vec<Vector3>) -> ImageEnvironment {
+        let (width, height) = (width as usize, height as usize);
+        assert!(
+            width > 0 && height > 0,
+            "environment image must not be empty"
+        );
+        assert_eq!(texels.len(), width * height);
+        // Rows near the poles cover less solid angle.
+        let columns: Vec<Distribution1D> = texels
+            .chunks_exact(width)
+            .enumerate()
+            .map(|(row, texels)| {
+                let sin_theta = (PI * (row as f64 + 0.5) / height as f64).sin();
+                let weights: Vec<f64> = texels.iter().map(|t| t.luminance() * sin_theta).collect();
+                Distribution1D::new(&weights)
+            })
+            .collect();
+        let row_weights: Vec<f64> = columns.iter().map(|c| c.total).collect();
+        ImageEnvironment {
+            width,
+            height,
+            texels,
+            intensity: 1.0,
+            rotation: 0.0,
+            rows: Distribution1D::new(&row_weights),
+            columns,
+        }
+    }
+
+    /// Loads a Radiance `.hdr` or a `.pfm` image.
+    pub fn load(path: &Path) -> Result<ImageEnvironment, io::Error> {
+        let is_pfm = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("pfm"));
+        let (width, height, texels) = if is_pfm {
+            hdr::read_pfm(path)?
+        } else {
+            hdr::read_hdr(path)?
+        };
+        Ok(ImageEnvironment::new(width, height, texels))
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> ImageEnvironment {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Turns the environment around the vertical axis.
+    pub fn with_rotation(mut self, degrees: f64) -> ImageEnvironment {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    /// Image coordinates in `[0, 1)` of a direction.
+    fn direction_to_uv(&self, direction: Vector3) -> (f64, f64) {
+        let direction = direction.unit_vector();
+        let phi = direction.z().atan2(direction.x()) - self.rotation;
+        let u = (phi / (2.0 * PI)).rem_euclid(1.0);
+        let v = direction.y().clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vector3 {
+        let phi = 2.0 * PI * u + self.rotation;
+        let theta = PI * v;
+        Vector3::new(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    }
+
+    fn texel(&self, u: f64, v: f64) -> (usize, usize) {
+        let column = ((u * self.width as f64) as usize).min(self.width - 1);
+        let row = ((v * self.height as f64) as usize).min(self.height - 1);
+        (row, column)
+    }
+}
+
+impl Environment for ImageEnvironment {
+    fn radiance(&self, direction: Vector3) -> Vector3 {
+        let (u, v) = self.direction_to_uv(direction);
+        let (row, column) = self.texel(u, v);
+        self.intensity * self.texels[row * self.width + column]
+    }
+
+    fn sample(&self) -> Option<LightSample> {
+        if self.rows.total <= 0.0 {
+            return None;
+        }
+        let (row, row_offset) = self.rows.sample(random_f64());
+        let (column, column_offset) = self.columns[row].sample(random_f64());
+        let u = (column as f64 + column_offset) / self.width as f64;
+        let v = (row as f64 + row_offset) / self.height as f64;
+        let direction = self.uv_to_direction(u, v);
+        let pdf = self.pdf(direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            radiance: self.radiance(direction),
+            pdf,
+            distance: f64::INFINITY,
+        })
+    }
+
+    fn pdf(&self, direction: Vector3) -> f64 {
+        if self.rows.total <= 0.0 {
+            return 0.0;
+        }
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (row, column) = self.texel(u, v);
+        let probability = self.rows.probability(row) * self.columns[row].probability(column);
+        // From the density over the image to one over the sphere.
+        probability * (self.width * self.height) as f64 / (2.0 * PI * PI * sin_theta)
+    }
+}
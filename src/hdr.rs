@@ -0,0 +1,335 @@
+use crate::vector::Vector3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Reads a text line ending in `\n`, without it.
+fn next_line<'a>(bytes: &'a [u8], position: &mut usize) -> Result<&'a str, io::Error> {
+    let start = *position;
+    while bytes.get(*position).is_some_and(|&b| b != b'\n') {
+        *position += 1;
+    }
+    if *position >= bytes.len() {
+        return Err(invalid_data("truncated header"));
+    }
+    *position += 1;
+    std::str::from_utf8(&bytes[start..*position - 1])
+        .map_err(|_| invalid_data("header is not text"))
+}
+
+fn rgbe_to_rgb(rgbe: &[u8]) -> Vector3 {
+    if rgbe[3] == 0 {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - 136);
+    Vector3::new(
+        rgbe[0] as f64 * scale,
+        rgbe[1] as f64 * scale,
+        rgbe[2] as f64 * scale,
+    )
+}
+
+/// Decodes one adaptive run length encoded scanline, where each of the four
+/// components is stored separately as runs and literal spans.
+fn read_rle_scanline(
+    bytes: &[u8],
+    position: &mut usize,
+    scanline: &mut [u8],
+    width: usize,
+) -> Result<(), io::Error> {
+    let truncated = || invalid_data("truncated hdr scanline");
+    for component in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *bytes.get(*position).ok_or_else(truncated)? as usize;
+            *position += 1;
+            if count > 128 {
+                let run = count - 128;
+                let value = *bytes.get(*position).ok_or_else(truncated)?;
+                *position += 1;
+                if x + run > width {
+                    return Err(invalid_data("hdr run overflows its scanline"));
+                }
+                for pixel in x..x + run {
+                    scanline[pixel * 4 + component] = value;
+                }
+                x += run;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid_data("invalid hdr span"));
+                }
+                let span = bytes
+                    .get(*position..*position + count)
+                    .ok_or_else(truncated)?;
+                for (offset, value) in span.iter().enumerate() {
+                    scanline[(x + offset) * 4 + component] = *value;
+                }
+                *position += count;
+                x += count;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that an image has pixels and returns how many, which `bytes`
+/// must be able to hold at `min_bytes_per_pixel` each.
+fn pixel_count(
+    width: usize,
+    height: usize,
+    min_bytes_per_pixel: usize,
+    bytes: &[u8],
+) -> Result<usize, io::Error> {
+    if width == 0 || height == 0 {
+        return Err(invalid_data("image has no pixels"));
+    }
+    width
+        .checked_mul(height)
+        .filter(|pixels| {
+            pixels
+                .checked_mul(min_bytes_per_pixel)
+                .is_some_and(|size| size <= bytes.len())
+        })
+        .ok_or_else(|| invalid_data("payload does not match its header"))
+}
+
+/// Loads a Radiance `.hdr` (RGBE) image, flat or run length encoded,
+/// returning its width, height and linear colours row by row from the top.
+pub fn read_hdr(path: &Path) -> Result<(u32, u32, Vec<Vector3>), io::Error> {
+    decode_hdr(&fs::read(path)?)
+}
+
+fn decode_hdr(bytes: &[u8]) -> Result<(u32, u32, Vec<Vector3>), io::Error> {
+    let mut position = 0;
+    let magic = next_line(bytes, &mut position)?;
+    if !magic.starts_with("#?") {
+        return Err(invalid_data("not a Radiance hdr file"));
+    }
+    loop {
+        let line = next_line(bytes, &mut position)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data("only rgbe hdr files are supported"));
+            }
+        }
+    }
+    let resolution: Vec<&str> = next_line(bytes, &mut position)?
+        .split_whitespace()
+        .collect();
+    let (height, width) = match resolution.as_slice() {
+        ["-Y", height, "+X", width] => (height.parse::<usize>(), width.parse::<usize>()),
+        _ => return Err(invalid_data("only -Y +X hdr orientation is supported")),
+    };
+    let (height, width) = match (height, width) {
+        (Ok(height), Ok(width)) => (height, width),
+        _ => return Err(invalid_data("invalid hdr resolution")),
+    };
+    // Run length encoding packs up to 127 pixels in two bytes per component,
+    // which bounds how many pixels the file can hold.
+    pixel_count(width.div_ceil(127), height, 8, bytes)?;
+    let pixels = width * height;
+
+    let mut colors = Vec::with_capacity(pixels);
+    let mut scanline = vec![0u8; width * 4];
+    for _ in 0..height {
+        let encoded = (8..32768).contains(&width)
+            && bytes.get(position..position + 2) == Some(&[2, 2][..])
+            && bytes
+                .get(position + 2..position + 4)
+                .is_some_and(|b| b[0] & 0x80 == 0);
+        if encoded {
+            let stored_width = ((bytes[position + 2] as usize) << 8) | bytes[position + 3] as usize;
+            if stored_width != width {
+                return Err(invalid_data("hdr scanline width does not match"));
+            }
+            position += 4;
+            read_rle_scanline(bytes, &mut position, &mut scanline, width)?;
+        } else {
+            let flat = bytes
+                .get(position..)
+                .and_then(|rest| rest.get(..width * 4))
+                .ok_or_else(|| invalid_data("hdr payload does not match its header"))?;
+            scanline.copy_from_slice(flat);
+            position += width * 4;
+        }
+        colors.extend(scanline.chunks_exact(4).map(rgbe_to_rgb));
+    }
+    Ok((width as u32, height as u32, colors))
+}
+
+/// Loads a portable float map, colour (`PF`) or greyscale (`Pf`), returning
+/// its width, height and colours row by row from the top, multiplied by the
+/// scale in the header.
+pub fn read_pfm(path: &Path) -> Result<(u32, u32, Vec<Vector3>), io::Error> {
+    decode_pfm(&fs::read(path)?)
+}
+
+fn decode_pfm(bytes: &[u8]) -> Result<(u32, u32, Vec<Vector3>), io::Error> {
+    let mut position = 0;
+    let channels = match next_line(bytes, &mut position)?.trim() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid_data("not a pfm file")),
+    };
+    let size: Vec<usize> = next_line(bytes, &mut position)?
+        .split_whitespace()
+        .map(|token| token.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid_data("invalid pfm size"))?;
+    let (width, height) = match size.as_slice() {
+        [width, height] => (*width, *height),
+        _ => return Err(invalid_data("invalid pfm size")),
+    };
+    let scale: f64 = next_line(bytes, &mut position)?
+        .trim()
+        .parse()
+        .map_err(|_| invalid_data("invalid pfm scale"))?;
+    if scale == 0.0 || !scale.is_finite() {
+        return Err(invalid_data("invalid pfm scale"));
+    }
+    // The sign gives the byte order and the magnitude scales the values.
+    let little_endian = scale < 0.0;
+    let scale = scale.abs();
+
+    let pixels = pixel_count(width, height, channels * 4, &bytes[position..])?;
+    let payload = &bytes[position..position + pixels * channels * 4];
+    let floats: Vec<f64> = payload
+        .chunks_exact(4)
+        .map(|b| {
+            let b = [b[0], b[1], b[2], b[3]];
+            let value = if little_endian {
+                f32::from_le_bytes(b)
+            } else {
+                f32::from_be_bytes(b)
+            };
+            value as f64 * scale
+        })
+        .collect();
+    // Rows are stored from the bottom up.
+    let mut colors = Vec::with_capacity(pixels);
+    for row in floats.chunks_exact(width * channels).rev() {
+        colors.extend(row.chunks_exact(channels).map(|c| match c {
+            [r, g, b] => Vector3::new(*r, *g, *b),
+            _ => Vector3::new(c[0], c[0], c[0]),
+        }));
+    }
+    Ok((width as u32, height as u32, colors))
+}
+
+/// Encodes colours given row by row from the top as a little endian colour
+/// portable float map.
+pub fn to_pfm(width: u32, height: u32, colors: &[Vector3]) -> Vec<u8> {
+    let mut bytes = format!("PF\n{} {}\n-1.0\n", width, height).into_bytes();
+    bytes.reserve(colors.len() * 12);
+    for row in colors.chunks_exact(width.max(1) as usize).rev() {
+        for color in row {
+            for channel in color.to_array() {
+                bytes.extend_from_slice(&(channel as f32).to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hdr(resolution: &str, payload: &[u8]) -> Vec<u8> {
+        let mut bytes =
+            format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes();
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn assert_colors(colors: &[Vector3], expected: &[[f64; 3]]) {
+        assert_eq!(colors.len(), expected.len());
+        for (color, expected) in colors.iter().zip(expected) {
+            assert_eq!(color.to_array(), *expected);
+        }
+    }
+
+    #[test]
+    fn flat_hdr_scanlines() {
+        let bytes = hdr("-Y 1 +X 2", &[128, 64, 32, 129, 0, 0, 0, 0]);
+        let (width, height, colors) = decode_hdr(&bytes).unwrap();
+        assert_eq!((width, height), (2, 1));
+        assert_colors(&colors, &[[1.0, 0.5, 0.25], [0.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn run_length_encoded_hdr_scanlines() {
+        let scanline = [
+            2, 2, 0, 8, // Scanline marker and width.
+            136, 128, // Red: a run of 8.
+            8, 0, 16, 32, 48, 64, 80, 96, 112, // Green: a span of 8.
+            132, 64, 4, 1, 2, 3, 4, // Blue: a run of 4, then a span of 4.
+            136, 129, // Exponent: a run of 8.
+        ];
+        let (width, height, colors) = decode_hdr(&hdr("-Y 1 +X 8", &scanline)).unwrap();
+        assert_eq!((width, height), (8, 1));
+        for (x, color) in colors.iter().enumerate() {
+            let blue = if x < 4 { 64.0 } else { (x - 3) as f64 };
+            assert_eq!(
+                color.to_array(),
+                [1.0, x as f64 * 16.0 / 128.0, blue / 128.0]
+            );
+        }
+    }
+
+    #[test]
+    fn hdr_runs_past_the_scanline_are_rejected() {
+        let scanline = [2, 2, 0, 8, 137, 128];
+        assert!(decode_hdr(&hdr("-Y 1 +X 8", &scanline)).is_err());
+    }
+
+    #[test]
+    fn empty_or_oversized_hdr_is_rejected() {
+        assert!(decode_hdr(&hdr("-Y 0 +X 8", &[])).is_err());
+        assert!(decode_hdr(&hdr("-Y 1 +X 0", &[])).is_err());
+        let huge = format!("-Y {} +X {}", usize::MAX, usize::MAX);
+        assert!(decode_hdr(&hdr(&huge, &[0; 16])).is_err());
+    }
+
+    #[test]
+    fn pfm_rows_are_stored_bottom_up() {
+        let colors = [Vector3::new(1.0, 2.0, 3.0), Vector3::new(-4.0, 0.5, 6.0)];
+        let bytes = to_pfm(1, 2, &colors);
+        let header = b"PF\n1 2\n-1.0\n".len();
+        let first = &bytes[header..header + 4];
+        assert_eq!(
+            f32::from_le_bytes([first[0], first[1], first[2], first[3]]),
+            -4.0
+        );
+
+        let (width, height, decoded) = decode_pfm(&bytes).unwrap();
+        assert_eq!((width, height), (1, 2));
+        assert_colors(&decoded, &[[1.0, 2.0, 3.0], [-4.0, 0.5, 6.0]]);
+    }
+
+    #[test]
+    fn big_endian_greyscale_pfm_is_scaled() {
+        let mut bytes = b"Pf\n2 1\n2.0\n".to_vec();
+        bytes.extend_from_slice(&1.5f32.to_be_bytes());
+        bytes.extend_from_slice(&0.25f32.to_be_bytes());
+        let (_, _, colors) = decode_pfm(&bytes).unwrap();
+        assert_colors(&colors, &[[3.0, 3.0, 3.0], [0.5, 0.5, 0.5]]);
+    }
+
+    #[test]
+    fn empty_or_oversized_pfm_is_rejected() {
+        assert!(decode_pfm(b"PF\n0 2\n-1.0\n").is_err());
+        assert!(decode_pfm(b"PF\n2 0\n-1.0\n").is_err());
+        assert!(decode_pfm(b"PF\n1 1\n0\n\0\0\0\0\0\0\0\0\0\0\0\0").is_err());
+        let huge = format!("PF\n{} {}\n-1.0\n", usize::MAX, 2);
+        assert!(decode_pfm(huge.as_bytes()).is_err());
+        assert!(decode_pfm(b"PF\n2 2\n-1.0\n\0\0\0\0").is_err());
+    }
+}
@@ -1,19 +1,248 @@
+use crate::aabb::Aabb;
+use crate::aov::Lobe;
+use crate::nested::Interior;
+use crate::onb::Onb;
 use crate::ray::Ray;
 use crate::rng;
+use crate::thinfilm::{Substrate, ThinFilm};
 use crate::vector::Vector3;
+use std::f64::consts::PI;
+use std::ops::Add;
+use std::sync::Arc;
+
+#[derive(Clone)]
 pub struct HitRecord<'a> {
     pub t: f64,
     pub p: Vector3,
+    /// Shading normal, which normal and bump maps may perturb.
     pub normal: Vector3,
-    pub material: &'a Box<dyn Material>,
+    /// Normal of the actual surface, for deciding which side a ray is on.
+    pub geometric_normal: Vector3,
+    /// Derivatives of the hit point along the texture coordinates.
+    pub dpdu: Vector3,
+    pub dpdv: Vector3,
+    pub u: f64,
+    pub v: f64,
+    pub material: &'a dyn Material,
+    /// Which object and material were hit, for the identifier passes.
+    pub id: HitId,
+}
+
+/// Numbers of an object and its material, counted through the world in the
+/// order things were added to it, so they stay the same from run to run and
+/// frame to frame. Primitives hit themselves as 0 and containers offset
+/// that by what comes before them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HitId {
+    pub object: usize,
+    pub material: usize,
+}
+
+impl Add for HitId {
+    type Output = HitId;
+
+    fn add(self, other: HitId) -> HitId {
+        HitId {
+            object: self.object + other.object,
+            material: self.material + other.material,
+        }
+    }
 }
 
 pub trait Hittable {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    /// World space bounds, or `None` for unbounded geometry.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// How many objects and materials are inside, for containers to number
+    /// what follows. A single one of each unless overridden.
+    fn ids(&self) -> HitId {
+        HitId {
+            object: 1,
+            material: 1,
+        }
+    }
+
+    /// Fraction of light that makes it through the segment `[t_min, t_max]`.
+    /// Surfaces are opaque; participating media override this.
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match masked_hit(self, ray, t_min, t_max) {
+            Some(_) => 0.0,
+            None => 1.0,
+        }
+    }
+}
+
+/// Shared objects, so that geometry built once can be placed in many worlds.
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn ids(&self) -> HitId {
+        (**self).ids()
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        (**self).transmittance(ray, t_min, t_max)
+    }
+}
+
+/// Nearest hit on `hittable` whose material does not cut the surface away
+/// there. Cut out hits are stepped past, so anything that gathers hits from
+/// primitives, like a list or acceleration structure, should go through this.
+pub fn masked_hit<'a, H: Hittable + ?Sized>(
+    hittable: &'a H,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    let mut t_min = t_min;
+    // Bounded in case a mask lets nothing through along a grazing ray.
+    for _ in 0..64 {
+        let hit_record = hittable.hit(ray, t_min, t_max)?;
+        if hit_record.material.is_opaque_at(&hit_record) {
+            return Some(hit_record);
+        }
+        t_min = hit_record.t;
+    }
+    None
+}
+
+/// Closed surface that can report every boundary crossing along a ray, as
+/// needed by constructive solid geometry.
+pub trait Solid: Hittable {
+    /// Spans of the whole line through `ray` that lie inside the solid, sorted
+    /// by `t` and disjoint. Parameters may be negative.
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>>;
+}
+
+#[derive(Clone)]
+pub struct Interval<'a> {
+    pub enter: HitRecord<'a>,
+    pub exit: HitRecord<'a>,
+}
+
+/// A scattered ray and the fraction of the light it brings back that is
+/// passed on, per channel, already divided by the probability of sampling it.
+pub struct Scatter {
+    pub attenuation: Vector3,
+    pub ray: Ray,
+    /// Solid angle density the direction was sampled with, matching
+    /// `Material::evaluate`. `None` for mirror-like lobes that light sampling
+    /// cannot reach, whose contribution is then never shared with it.
+    pub pdf: Option<f64>,
+    /// Sampled from a glossy reflection lobe rather than a diffuse one.
+    pub glossy: bool,
+}
+
+impl Scatter {
+    pub fn new(attenuation: Vector3, ray: Ray) -> Scatter {
+        Scatter {
+            attenuation,
+            ray,
+            pdf: None,
+            glossy: false,
+        }
+    }
+
+    pub fn with_pdf(mut self, pdf: f64) -> Scatter {
+        self.pdf = Some(pdf);
+        self
+    }
+
+    pub fn glossy(mut self) -> Scatter {
+        self.glossy = true;
+        self
+    }
+}
+
+/// A material's scattering function for one pair of directions.
+pub struct Evaluation {
+    /// Scattering function times the cosine to the normal, split by the
+    /// lobe it comes from and indexed by `Lobe`, for the lobe passes.
+    pub lobes: [Vector3; 3],
+    /// Density with which `Material::scatter` picks the same direction.
+    pub pdf: f64,
+}
+
+impl Evaluation {
+    /// Nothing scattered into the direction.
+    pub fn zero() -> Evaluation {
+        Evaluation {
+            lobes: [Vector3::new(0.0, 0.0, 0.0); 3],
+            pdf: 0.0,
+        }
+    }
+
+    /// Scattering through a single lobe.
+    pub fn from(lobe: Lobe, value: Vector3, pdf: f64) -> Evaluation {
+        let mut evaluation = Evaluation::zero();
+        evaluation.lobes[lobe as usize] = value;
+        evaluation.pdf = pdf;
+        evaluation
+    }
+
+    /// Scattering function of all the lobes together.
+    pub fn value(&self) -> Vector3 {
+        self.lobes[0] + self.lobes[1] + self.lobes[2]
+    }
 }
 
 pub trait Material {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> (Vector3, Ray, bool);
+    /// Samples how a ray continues after hitting the surface, or `None` if it
+    /// is absorbed.
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter>;
+
+    /// Evaluates scattering from `ray_in` into `direction`, so that lights
+    /// can be sampled directly. `None` for materials that can only be
+    /// sampled.
+    fn evaluate(
+        &self,
+        _ray_in: &Ray,
+        _hit_record: &HitRecord,
+        _direction: Vector3,
+    ) -> Option<Evaluation> {
+        None
+    }
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Vector3 {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Overall colour of the surface at a hit, for the albedo pass. White
+    /// unless overridden, which suits clear and mirror-like surfaces.
+    fn albedo(&self, _hit_record: &HitRecord) -> Vector3 {
+        Vector3::new(1.0, 1.0, 1.0)
+    }
+
+    /// Whether the glow is also sampled through a `Light`, so that rays
+    /// scattered onto it after sampling that light must not count it again.
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    /// Whether scattering depends on the ray's wavelength, in which case a
+    /// spectral render can only follow a single wavelength past this point.
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    /// The medium inside a closed dielectric, for absorption and nesting.
+    fn interior(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<Interior> {
+        None
+    }
+
+    /// Whether the surface is really there at this hit. Cutouts return
+    /// `false` where they are clear and the ray carries on past them.
+    fn is_opaque_at(&self, _hit_record: &HitRecord) -> bool {
+        true
+    }
 }
 
 pub struct Lambertian {
@@ -27,11 +256,30 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> (Vector3, Ray, bool) {
-        let target = hit_record.p + hit_record.normal + rng::random_in_unit_sphere();
-        let scattered = Ray::new(hit_record.p, target - hit_record.p);
+    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        let direction = Onb::from_w(hit_record.normal).world(rng::random_cosine_direction());
+        let scattered = Ray::new(hit_record.p, direction);
         let attenuation = self.albedo;
-        (attenuation, scattered, true)
+        let pdf = direction.dot(hit_record.normal).max(0.0) / PI;
+        Some(Scatter::new(attenuation, scattered).with_pdf(pdf))
+    }
+
+    fn evaluate(
+        &self,
+        _ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        let cosine = direction.unit_vector().dot(hit_record.normal).max(0.0);
+        Some(Evaluation::from(
+            Lobe::Diffuse,
+            self.albedo * cosine / PI,
+            cosine / PI,
+        ))
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Vector3 {
+        self.albedo
     }
 }
 
@@ -42,26 +290,100 @@ fn reflect(v: Vector3, n: Vector3) -> Vector3 {
 pub struct Metal {
     albedo: Vector3,
     fuzz: f64,
+    film: Option<ThinFilm>,
 }
 
 impl Metal {
     pub fn new(albedo: Vector3, fuzz: f64) -> Metal {
         let f = if fuzz < 1.0 { fuzz } else { 1.0 };
 
-        Metal { albedo, fuzz: f }
+        Metal {
+            albedo,
+            fuzz: f,
+            film: None,
+        }
+    }
+
+    /// Coats the metal with a thin transparent film, like an oxide layer.
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Metal {
+        self.film = Some(film);
+        self
+    }
+
+    fn attenuation(&self, ray_in: &Ray, hit_record: &HitRecord) -> Vector3 {
+        match &self.film {
+            Some(film) => film.reflectance_rgb(
+                hit_record,
+                ray_in.direction().unit_vector().dot(hit_record.normal),
+                ray_in.exterior_ior().unwrap_or(1.0),
+                Substrate::Conductor(self.albedo),
+                ray_in.wavelength(),
+            ),
+            None => self.albedo,
+        }
+    }
+
+    /// Solid angle density of `direction` when the mirror direction
+    /// `reflected` is pushed by a random point in a ball of radius `fuzz`:
+    /// the ball's volume along the direction, weighted by distance squared.
+    fn fuzz_pdf(&self, reflected: Vector3, direction: Vector3) -> f64 {
+        let w = direction.unit_vector();
+        let b = w.dot(reflected);
+        let discriminant = b * b - reflected.squared_length() + self.fuzz * self.fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let near = (b - discriminant.sqrt()).max(0.0);
+        let far = (b + discriminant.sqrt()).max(0.0);
+        (far.powi(3) - near.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> (Vector3, Ray, bool) {
-        let reflected = reflect(ray_in.direction().unit_vector(), hit_record.normal);
+    fn is_dispersive(&self) -> bool {
+        self.film.is_some()
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Vector3 {
+        self.albedo
+    }
+
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        let unit_direction = ray_in.direction().unit_vector();
+        let reflected = reflect(unit_direction, hit_record.normal);
         let scattered = Ray::new(
             hit_record.p,
             reflected + self.fuzz * rng::random_in_unit_sphere(),
         );
-        let attenuation = self.albedo;
-        let scatter = scattered.direction().dot(hit_record.normal) > 0.0;
-        (attenuation, scattered, scatter)
+        if scattered.direction().dot(hit_record.normal) <= 0.0 {
+            return None;
+        }
+        let scatter = Scatter::new(self.attenuation(ray_in, hit_record), scattered);
+        if self.fuzz <= 0.0 {
+            return Some(scatter);
+        }
+        let pdf = self.fuzz_pdf(reflected, scattered.direction());
+        Some(scatter.with_pdf(pdf).glossy())
+    }
+
+    /// Samples below the surface are absorbed, so the reflection is the
+    /// attenuation times the density of sampling the direction.
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        if self.fuzz <= 0.0 {
+            return None;
+        }
+        if direction.dot(hit_record.normal) <= 0.0 {
+            return Some(Evaluation::zero());
+        }
+        let reflected = reflect(ray_in.direction().unit_vector(), hit_record.normal);
+        let pdf = self.fuzz_pdf(reflected, direction);
+        let value = self.attenuation(ray_in, hit_record) * pdf;
+        Some(Evaluation::from(Lobe::Specular, value, pdf))
     }
 }
 
@@ -82,47 +404,182 @@ fn shlick(cosine: f64, reflective_index: f64) -> f64 {
     r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
 
+/// Wavelength dependent index of refraction, with wavelengths in micrometers.
+pub enum Dispersion {
+    /// `n = a + b / λ²`
+    Cauchy { a: f64, b: f64 },
+    /// `n² = 1 + Σ b λ² / (λ² - c)`
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn ior(&self, wavelength_nm: f64) -> f64 {
+        let l2 = (wavelength_nm / 1000.0) * (wavelength_nm / 1000.0);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = b.iter().zip(c).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
 pub struct Dielectric {
     reflective_index: f64,
+    dispersion: Option<Dispersion>,
+    absorption: Vector3,
+    priority: u32,
+    film: Option<ThinFilm>,
 }
 
 impl Dielectric {
     pub fn new(reflective_index: f64) -> Dielectric {
-        Dielectric { reflective_index }
+        Dielectric {
+            reflective_index,
+            dispersion: None,
+            absorption: Vector3::new(0.0, 0.0, 0.0),
+            priority: 0,
+            film: None,
+        }
+    }
+
+    /// Coats the surface with a thin film, whose interference replaces the
+    /// plain Fresnel reflectance. With an index of one this is a soap bubble.
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Dielectric {
+        self.film = Some(film);
+        self
+    }
+
+    /// Tints light by `exp(-absorption * distance)` as it travels inside.
+    pub fn with_absorption(mut self, absorption: Vector3) -> Dielectric {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Where dielectrics overlap the one with the highest priority wins.
+    pub fn with_priority(mut self, priority: u32) -> Dielectric {
+        self.priority = priority;
+        self
+    }
+
+    /// Index of refraction varying with wavelength when rendering spectrally.
+    /// RGB renders use `reflective_index`.
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Dielectric {
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    /// Schott N-BK7 crown glass.
+    pub fn bk7() -> Dielectric {
+        Dielectric::new(1.5168).with_dispersion(Dispersion::Sellmeier {
+            b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+            c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+        })
+    }
+
+    pub fn diamond() -> Dielectric {
+        Dielectric::new(2.417).with_dispersion(Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030_625, 0.011_236, 0.0],
+        })
+    }
+
+    fn ior(&self, ray_in: &Ray) -> f64 {
+        match (&self.dispersion, ray_in.wavelength()) {
+            (Some(dispersion), Some(wavelength)) => dispersion.ior(wavelength),
+            _ => self.reflective_index,
+        }
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> (Vector3, Ray, bool) {
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some() || self.film.is_some()
+    }
+
+    fn interior(&self, ray_in: &Ray, _hit_record: &HitRecord) -> Option<Interior> {
+        Some(Interior {
+            priority: self.priority,
+            ior: self.ior(ray_in),
+            absorption: self.absorption,
+            scattering: Vector3::new(0.0, 0.0, 0.0),
+            anisotropy: 0.0,
+        })
+    }
+
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        // Relative to whatever surrounds this dielectric, air unless nested.
+        let reflective_index = self.ior(ray_in) / ray_in.exterior_ior().unwrap_or(1.0);
         let reflected = reflect(ray_in.direction(), hit_record.normal);
         let attenuation = Vector3::new(1.0, 1.0, 1.0);
+        // Matching indices leave no boundary to reflect off, but Schlick's
+        // approximation would still reflect at grazing angles.
+        if reflective_index == 1.0 && self.film.is_none() {
+            let through = Ray::new(hit_record.p, ray_in.direction());
+            return Some(Scatter::new(attenuation, through));
+        }
 
         let positive_direction = ray_in.direction().dot(hit_record.normal) > 0.0;
 
         let (outward_normal, ni_over_nt, cosine) = if positive_direction {
-            let cosine = self.reflective_index * ray_in.direction().dot(hit_record.normal)
+            let cosine = reflective_index * ray_in.direction().dot(hit_record.normal)
                 / ray_in.direction().length();
-            (-hit_record.normal, self.reflective_index, cosine)
+            (-hit_record.normal, reflective_index, cosine)
         } else {
             let cosine =
                 (-(ray_in.direction().dot(hit_record.normal))) / ray_in.direction().length();
-            (hit_record.normal, 1.0 / self.reflective_index, cosine)
+            (hit_record.normal, 1.0 / reflective_index, cosine)
         };
 
         let refracted_opt = refract(ray_in.direction(), outward_normal, ni_over_nt);
-        let new_ray = match refracted_opt {
-            Some(refracted) => {
-                let reflected_prob = shlick(cosine, self.reflective_index);
+        let (attenuation, new_ray) = match (refracted_opt, &self.film) {
+            (Some(refracted), None) => {
+                let reflected_prob = shlick(cosine, reflective_index);
                 if rng::random_f64() < reflected_prob {
-                    Ray::new(hit_record.p, reflected)
+                    (attenuation, Ray::new(hit_record.p, reflected))
                 } else {
-                    Ray::new(hit_record.p, refracted)
+                    (attenuation, Ray::new(hit_record.p, refracted))
                 }
             }
-            _ => Ray::new(hit_record.p, reflected),
+            (Some(refracted), Some(film)) => {
+                let interior = self.ior(ray_in);
+                let exterior = ray_in.exterior_ior().unwrap_or(1.0);
+                let (n_incident, n_substrate) = if positive_direction {
+                    (interior, exterior)
+                } else {
+                    (exterior, interior)
+                };
+                let cos_i =
+                    ray_in.direction().dot(hit_record.normal).abs() / ray_in.direction().length();
+                let reflectance = film.reflectance_rgb(
+                    hit_record,
+                    cos_i,
+                    n_incident,
+                    Substrate::Dielectric(n_substrate),
+                    ray_in.wavelength(),
+                );
+                // Pick a side by the average reflectance and reweight each
+                // channel by how likely that side was.
+                let reflected_prob = ((reflectance.x() + reflectance.y() + reflectance.z()) / 3.0)
+                    .clamp(1e-4, 0.9999);
+                if rng::random_f64() < reflected_prob {
+                    (
+                        reflectance / reflected_prob,
+                        Ray::new(hit_record.p, reflected),
+                    )
+                } else {
+                    let transmittance = attenuation - reflectance;
+                    (
+                        transmittance / (1.0 - reflected_prob),
+                        Ray::new(hit_record.p, refracted),
+                    )
+                }
+            }
+            (None, _) => (attenuation, Ray::new(hit_record.p, reflected)),
         };
 
-        (attenuation, new_ray, true)
+        Some(Scatter::new(attenuation, new_ray))
     }
 }
 
@@ -145,65 +602,360 @@ impl Sphere {
 unsafe impl Send for Sphere {}
 unsafe impl Sync for Sphere {}
 
-impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+impl Sphere {
+    fn record(&self, ray: &Ray, t: f64) -> HitRecord<'_> {
+        let point = ray.point_at(t);
+        let normal = (point - self.center) / self.radius;
+        let outward = normal * self.radius.signum();
+        let theta = (-outward.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-outward.z()).atan2(outward.x()) + PI;
+        let radius = self.radius.abs();
+        let ring = outward.x().hypot(outward.z()).max(1e-6);
+        HitRecord {
+            t,
+            p: point,
+            normal,
+            geometric_normal: normal,
+            dpdu: 2.0 * PI * radius * Vector3::new(outward.z(), 0.0, -outward.x()),
+            dpdv: PI
+                * radius
+                * Vector3::new(
+                    -outward.x() * outward.y() / ring,
+                    ring,
+                    -outward.z() * outward.y() / ring,
+                ),
+            u: phi / (2.0 * PI),
+            v: theta / PI,
+            material: &*self.material,
+            id: HitId::default(),
+        }
+    }
+
+    /// Both roots of the ray/sphere equation, nearest first.
+    fn roots(&self, ray: &Ray) -> Option<(f64, f64)> {
         let oc = ray.origin() - self.center;
         let a = ray.direction().dot(ray.direction());
         let b = oc.dot(ray.direction());
         let c = oc.dot(oc) - self.radius * self.radius;
         let discriminant = b * b - a * c;
         if discriminant > 0.0 {
-            let temp = (-b - discriminant.sqrt()) / a;
-            let point = ray.point_at(temp);
-            if temp < t_max && temp > t_min {
-                return Some(HitRecord {
-                    t: temp,
-                    p: point,
-                    normal: (point - self.center) / self.radius,
-                    material: &self.material,
-                });
+            let root = discriminant.sqrt();
+            Some(((-b - root) / a, (-b + root) / a))
+        } else {
+            None
+        }
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (near, far) = self.roots(ray)?;
+        if near < t_max && near > t_min {
+            return Some(self.record(ray, near));
+        }
+        if far < t_max && far > t_min {
+            return Some(self.record(ray, far));
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vector3::new(1.0, 1.0, 1.0) * self.radius.abs();
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+impl Solid for Sphere {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        match self.roots(ray) {
+            Some((near, far)) => vec![Interval {
+                enter: self.record(ray, near),
+                exit: self.record(ray, far),
+            }],
+            None => vec![],
+        }
+    }
+}
+
+/// Axis aligned box.
+pub struct Cuboid {
+    bounds: Aabb,
+    material: Box<dyn Material>,
+}
+
+impl Cuboid {
+    pub fn new(min: Vector3, max: Vector3, material: Box<dyn Material>) -> Cuboid {
+        Cuboid {
+            bounds: Aabb::new(min, max),
+            material,
+        }
+    }
+
+    fn record(&self, ray: &Ray, t: f64, axis: usize, sign: f64) -> HitRecord<'_> {
+        let mut normal = [0.0; 3];
+        normal[axis] = sign;
+        let point = ray.point_at(t);
+        let extent = self.bounds.extent();
+        let local = (point - self.bounds.min()) / extent;
+        let edge = |axis: usize| {
+            let mut edge = [0.0; 3];
+            edge[axis] = extent[axis];
+            Vector3::from_array(edge)
+        };
+        HitRecord {
+            t,
+            p: point,
+            normal: Vector3::from_array(normal),
+            geometric_normal: Vector3::from_array(normal),
+            dpdu: edge((axis + 1) % 3),
+            dpdv: edge((axis + 2) % 3),
+            u: local[(axis + 1) % 3],
+            v: local[(axis + 2) % 3],
+            material: &*self.material,
+            id: HitId::default(),
+        }
+    }
+
+    /// Slab test over the whole line, keeping track of which faces bound the
+    /// entry and exit points.
+    fn slabs(&self, ray: &Ray) -> Option<Interval<'_>> {
+        let mut enter = (f64::MIN, 0, 0.0);
+        let mut exit = (f64::MAX, 0, 0.0);
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction()[axis];
+            let t0 = (self.bounds.min()[axis] - ray.origin()[axis]) * inv_d;
+            let t1 = (self.bounds.max()[axis] - ray.origin()[axis]) * inv_d;
+            let (near, far, sign) = if inv_d < 0.0 {
+                (t1, t0, 1.0)
+            } else {
+                (t0, t1, -1.0)
+            };
+            if near > enter.0 {
+                enter = (near, axis, sign);
             }
-            let temp = (-b + discriminant.sqrt()) / a;
-            let point = ray.point_at(temp);
-            if temp < t_max && temp > t_min {
-                return Some(HitRecord {
-                    t: temp,
-                    p: point,
-                    normal: (point - self.center) / self.radius,
-                    material: &self.material,
-                });
+            if far < exit.0 {
+                exit = (far, axis, -sign);
+            }
+            if exit.0 <= enter.0 {
+                return None;
             }
         }
+        Some(Interval {
+            enter: self.record(ray, enter.0, enter.1, enter.2),
+            exit: self.record(ray, exit.0, exit.1, exit.2),
+        })
+    }
+}
+
+unsafe impl Send for Cuboid {}
+unsafe impl Sync for Cuboid {}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let interval = self.slabs(ray)?;
+        if interval.enter.t < t_max && interval.enter.t > t_min {
+            return Some(interval.enter);
+        }
+        if interval.exit.t < t_max && interval.exit.t > t_min {
+            return Some(interval.exit);
+        }
         None
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+impl Solid for Cuboid {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        self.slabs(ray).into_iter().collect()
+    }
 }
 
 pub struct HittableList {
-    list: Vec<Box<dyn Hittable + Send + Sync>>,
+    /// Objects along with the identifiers their hits are offset by.
+    list: Vec<(HitId, Box<dyn Hittable + Send + Sync>)>,
+    ids: HitId,
 }
 
 impl HittableList {
     pub fn new() -> HittableList {
-        HittableList { list: vec![] }
+        HittableList {
+            list: vec![],
+            ids: HitId::default(),
+        }
     }
 
     pub fn add<T: Hittable + Send + Sync + 'static>(&mut self, hittable: T) {
-        self.list.push(Box::new(hittable));
+        let offset = self.ids;
+        self.ids = offset + hittable.ids();
+        self.list.push((offset, Box::new(hittable)));
+    }
+
+    /// Hands the objects over, with the identifiers their hits are offset
+    /// by, to build an acceleration structure from.
+    pub fn into_objects(self) -> Vec<(HitId, Box<dyn Hittable + Send + Sync>)> {
+        self.list
     }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut hit_record: Option<HitRecord> = None;
         let mut closet_so_far = t_max;
-        for hittable in &self.list {
-            let new_hit = hittable.hit(ray, t_min, closet_so_far);
-            if new_hit.is_some() {
-                let hit = new_hit.unwrap();
+        for (offset, hittable) in &self.list {
+            if let Some(mut hit) = masked_hit(hittable.as_ref(), ray, t_min, closet_so_far) {
                 closet_so_far = hit.t;
+                hit.id = hit.id + *offset;
                 hit_record = Some(hit);
             }
         }
         hit_record
     }
+
+    fn ids(&self) -> HitId {
+        self.ids
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut bounds: Option<Aabb> = None;
+        for (_, hittable) in &self.list {
+            let child = hittable.bounding_box()?;
+            bounds = Some(match bounds {
+                Some(b) => Aabb::surrounding(b, child),
+                None => child,
+            });
+        }
+        bounds
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        for (_, hittable) in &self.list {
+            transmittance *= hittable.transmittance(ray, t_min, t_max);
+            if transmittance <= 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::csg::Difference;
+
+    fn sphere(x: f64, z: f64, radius: f64) -> Sphere {
+        let material = Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5)));
+        Sphere::new(Vector3::new(x, 0.0, z), radius, material)
+    }
+
+    fn id_below(world: &HittableList, x: f64) -> HitId {
+        let ray = Ray::new(Vector3::new(x, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+        world.hit(&ray, 0.001, f64::MAX).unwrap().id
+    }
+
+    #[test]
+    fn fuzzy_metal_samples_match_evaluation() {
+        let metal = Metal::new(Vector3::new(0.9, 0.6, 0.3), 0.4);
+        let rec = HitRecord {
+            t: 1.0,
+            p: Vector3::new(0.0, 0.0, 0.0),
+            normal: Vector3::new(0.0, 0.0, 1.0),
+            geometric_normal: Vector3::new(0.0, 0.0, 1.0),
+            dpdu: Vector3::new(1.0, 0.0, 0.0),
+            dpdv: Vector3::new(0.0, 1.0, 0.0),
+            u: 0.5,
+            v: 0.5,
+            material: &metal,
+            id: HitId::default(),
+        };
+        let ray = Ray::new(Vector3::new(-1.0, 0.0, 1.0), Vector3::new(1.0, 0.0, -1.0));
+        for _ in 0..1000 {
+            let scatter = match metal.scatter(&ray, &rec) {
+                Some(scatter) => scatter,
+                None => continue,
+            };
+            let pdf = scatter.pdf.unwrap();
+            let evaluation = metal.evaluate(&ray, &rec, scatter.ray.direction()).unwrap();
+            assert!((evaluation.pdf - pdf).abs() < 1e-9);
+            assert!((evaluation.value() / pdf - scatter.attenuation).length() < 1e-9);
+        }
+
+        // The directions the ball of fuzz reaches cover the sphere once.
+        let n = 400;
+        let mut total = 0.0;
+        let reflected = reflect(ray.direction().unit_vector(), rec.normal);
+        for i in 0..n {
+            let cos_theta = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            for j in 0..n {
+                let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+                let w = Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                total += metal.fuzz_pdf(reflected, w);
+            }
+        }
+        let integral = total * 4.0 * PI / (n * n) as f64;
+        assert!((integral - 1.0).abs() < 0.01, "integrates to {}", integral);
+    }
+
+    #[test]
+    fn matching_indices_pass_straight_through() {
+        let air = Dielectric::new(1.0);
+        let glass = Dielectric::new(1.5);
+        let rec = |material| HitRecord {
+            t: 1.0,
+            p: Vector3::new(0.0, 0.0, 0.0),
+            normal: Vector3::new(0.0, 0.0, 1.0),
+            geometric_normal: Vector3::new(0.0, 0.0, 1.0),
+            dpdu: Vector3::new(1.0, 0.0, 0.0),
+            dpdv: Vector3::new(0.0, 1.0, 0.0),
+            u: 0.5,
+            v: 0.5,
+            material,
+            id: HitId::default(),
+        };
+        // Grazing, where Schlick's approximation reflects the most.
+        let direction = Vector3::new(1.0, 0.0, -0.02);
+        let from_air = Ray::new(Vector3::new(-1.0, 0.0, 0.02), direction);
+        let from_glass = from_air.with_exterior_ior(1.5);
+        for (material, ray) in [(&air, from_air), (&glass, from_glass)] {
+            for _ in 0..1000 {
+                let scatter = material.scatter(&ray, &rec(material)).unwrap();
+                assert_eq!((scatter.ray.direction() - direction).length(), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn ids_count_through_hierarchies_and_solids() {
+        let mut inner = HittableList::new();
+        inner.add(sphere(0.0, 0.0, 1.0));
+        inner.add(Difference::new(
+            sphere(4.0, 0.0, 1.0),
+            sphere(4.0, 1.0, 0.5),
+        ));
+        let mut world = HittableList::new();
+        world.add(sphere(-4.0, 0.0, 1.0));
+        world.add(Bvh::new(inner));
+        world.add(sphere(8.0, 0.0, 1.0));
+        assert_eq!(
+            world.ids(),
+            HitId {
+                object: 4,
+                material: 5
+            }
+        );
+
+        let id = |object, material| HitId { object, material };
+        assert_eq!(id_below(&world, -4.0), id(0, 0));
+        assert_eq!(id_below(&world, 0.0), id(1, 1));
+        // Both surfaces of the difference are one object.
+        assert_eq!(id_below(&world, 4.8), id(2, 2));
+        assert_eq!(id_below(&world, 4.0), id(2, 3));
+        assert_eq!(id_below(&world, 8.0), id(3, 4));
+    }
 }
@@ -0,0 +1,177 @@
+use crate::vector::Vector3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Index of the segment of sorted `angles` holding `angle`, with how far
+/// along it `angle` lies, or `None` outside of them.
+fn locate(angles: &[f64], angle: f64) -> Option<(usize, f64)> {
+    let first = *angles.first()?;
+    let last = *angles.last()?;
+    if angles.len() == 1 {
+        return if (angle - first).abs() < 1e-9 {
+            Some((0, 0.0))
+        } else {
+            None
+        };
+    }
+    if angle < first || angle > last {
+        return None;
+    }
+    let index = (angles.partition_point(|&a| a <= angle).max(1) - 1).min(angles.len() - 2);
+    let span = angles[index + 1] - angles[index];
+    let t = if span > 0.0 {
+        (angle - angles[index]) / span
+    } else {
+        0.0
+    };
+    Some((index, t.clamp(0.0, 1.0)))
+}
+
+/// Candela distribution of a luminaire from an IES LM-63 photometric file,
+/// using type C photometry: vertical angles run from straight down at 0 to
+/// straight up at 180 degrees, and horizontal angles turn around the
+/// vertical axis.
+pub struct IesProfile {
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    /// Candelas for each horizontal angle, then each vertical angle.
+    candela: Vec<f64>,
+    max_candela: f64,
+}
+
+impl IesProfile {
+    pub fn load(path: &Path) -> Result<IesProfile, io::Error> {
+        IesProfile::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<IesProfile, io::Error> {
+        let mut lines = text.lines();
+        // Keywords run up to the line describing the lamp tilt.
+        let tilt = loop {
+            match lines.next() {
+                Some(line) if line.trim_start().starts_with("TILT=") => {
+                    break line.trim_start()["TILT=".len()..].trim().to_string();
+                }
+                Some(_) => continue,
+                None => return Err(invalid_data("ies file has no TILT line")),
+            }
+        };
+        let mut numbers = lines.flat_map(|line| {
+            line.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|token| !token.is_empty())
+        });
+        let mut next = || -> Result<f64, io::Error> {
+            numbers
+                .next()
+                .ok_or_else(|| invalid_data("truncated ies file"))?
+                .parse()
+                .map_err(|_| invalid_data("invalid number in ies file"))
+        };
+
+        if tilt == "INCLUDE" {
+            let _geometry = next()?;
+            let pairs = next()? as usize;
+            for _ in 0..2 * pairs {
+                next()?;
+            }
+        } else if tilt != "NONE" {
+            return Err(invalid_data("ies tilt files are not supported"));
+        }
+
+        // Lamp count, lumens per lamp and a candela multiplier, none of which
+        // matter once intensities are made relative.
+        for _ in 0..3 {
+            next()?;
+        }
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        if photometric_type != 1.0 {
+            return Err(invalid_data("only type C ies photometry is supported"));
+        }
+        // Units and luminous opening size, then ballast factor, a reserved
+        // field and input watts.
+        for _ in 0..7 {
+            next()?;
+        }
+        let vertical = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let horizontal = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let candela = (0..vertical_count * horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        if vertical.is_empty() || horizontal.is_empty() {
+            return Err(invalid_data("ies file has no angles"));
+        }
+        let max_candela = candela.iter().cloned().fold(0.0, f64::max);
+        Ok(IesProfile {
+            vertical,
+            horizontal,
+            candela,
+            max_candela,
+        })
+    }
+
+    /// Folds a horizontal angle in degrees into the range the file covers,
+    /// using the symmetry implied by its last horizontal angle.
+    fn fold_horizontal(&self, angle: f64) -> f64 {
+        let angle = angle.rem_euclid(360.0);
+        let last = *self.horizontal.last().unwrap_or(&0.0);
+        if last <= 0.0 {
+            0.0
+        } else if last <= 90.0 {
+            let half = angle % 180.0;
+            if half > 90.0 {
+                180.0 - half
+            } else {
+                half
+            }
+        } else if last <= 180.0 {
+            if angle > 180.0 {
+                360.0 - angle
+            } else {
+                angle
+            }
+        } else {
+            angle
+        }
+    }
+
+    /// Intensity towards a direction in the luminaire's frame, with +z
+    /// straight down and horizontal angle zero along +x, as a fraction of
+    /// the brightest direction.
+    pub fn relative_intensity(&self, direction: Vector3) -> f64 {
+        if self.max_candela <= 0.0 {
+            return 0.0;
+        }
+        let direction = direction.unit_vector();
+        let vertical = direction.z().clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = self.fold_horizontal(direction.y().atan2(direction.x()).to_degrees());
+        let (v, tv) = match locate(&self.vertical, vertical) {
+            Some(located) => located,
+            None => return 0.0,
+        };
+        // Past the last angle of a full circle the last one is held.
+        let (h, th) = match locate(&self.horizontal, horizontal) {
+            Some(located) => located,
+            None => (self.horizontal.len() - 1, 0.0),
+        };
+        let vertical_count = self.vertical.len();
+        let at = |h: usize, v: usize| {
+            let h = h.min(self.horizontal.len() - 1);
+            let v = v.min(vertical_count - 1);
+            self.candela[h * vertical_count + v]
+        };
+        let along = |h: usize| (1.0 - tv) * at(h, v) + tv * at(h, v + 1);
+        let value = (1.0 - th) * along(h) + th * along(h + 1);
+        value / self.max_candela
+    }
+}
@@ -1,3 +1,5 @@
+use crate::aov::Aov;
+use crate::hdr;
 use crate::vector::Vector3;
 
 pub fn color_float_to_u8(color: f64) -> u8 {
@@ -49,6 +51,8 @@ pub struct Image {
     width: u32,
     height: u32,
     pixelmap: Vec<Pixel>,
+    /// Output variables rendered along with the image.
+    passes: Vec<(Aov, Framebuffer)>,
 }
 
 impl Image {
@@ -57,26 +61,7 @@ impl Image {
             width,
             height,
             pixelmap: init_bitmap(width, height),
-        }
-    }
-
-    pub fn from_tiles(width: u32, height: u32, mut tiles: Vec<Tile>) -> Image {
-        // Sort tiles by starting line
-        tiles.sort_by(|t1, t2| t1.start_x().cmp(&t2.start_x()));
-
-        let capacity = width * height;
-        let mut pixelmap: Vec<Pixel> = Vec::with_capacity(capacity as usize);
-
-        tiles.reverse();
-        while let Some(tile) = tiles.pop() {
-            let mut pixel = tile.extract_image().pixelmap;
-            pixelmap.append(&mut pixel);
-        }
-
-        Image {
-            width,
-            height,
-            pixelmap,
+            passes: vec![],
         }
     }
 
@@ -104,6 +89,15 @@ impl Image {
     pub fn get_pixel_count(&self) -> u32 {
         self.width * self.height
     }
+
+    pub fn with_passes(mut self, passes: Vec<(Aov, Framebuffer)>) -> Image {
+        self.passes = passes;
+        self
+    }
+
+    pub fn passes(&self) -> &[(Aov, Framebuffer)] {
+        &self.passes
+    }
 }
 
 impl IntoIterator for Image {
@@ -115,27 +109,108 @@ impl IntoIterator for Image {
     }
 }
 
+/// Image of linear floating point colours, before gamma correction and
+/// quantization.
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Vector3>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Vector3::new(0.0, 0.0, 0.0); (width * height) as usize],
+        }
+    }
+
+    /// Joins the `layer` of each tile, which must cover whole lines.
+    pub fn from_tiles(width: u32, height: u32, tiles: &[Tile], layer: usize) -> Framebuffer {
+        let mut tiles: Vec<&Tile> = tiles.iter().collect();
+        tiles.sort_by(|t1, t2| t1.start_x().cmp(&t2.start_x()));
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for tile in tiles {
+            pixels.extend_from_slice(&tile.layers[layer].pixels);
+        }
+        Framebuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn xy_to_index(&self, x: u32, y: u32) -> usize {
+        (x * self.width + y) as usize
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Vector3) {
+        let index = self.xy_to_index(x, y);
+        self.pixels[index] = color
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Vector3 {
+        self.pixels[self.xy_to_index(x, y)]
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Gamma corrects the colours and quantizes them into an image.
+    pub fn to_image(&self) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            pixelmap: self
+                .pixels
+                .iter()
+                .map(|color| Pixel::from(color.square_root()))
+                .collect(),
+            passes: vec![],
+        }
+    }
+
+    /// Encodes the colours as they are in a portable float map.
+    pub fn to_pfm(&self) -> Vec<u8> {
+        hdr::to_pfm(self.width, self.height, &self.pixels)
+    }
+}
+
+/// A band of whole lines rendered by one worker, with the beauty pass as
+/// the first layer and any other passes after it.
 pub struct Tile {
     start_x: u32,
     start_y: u32,
-    image: Image,
+    layers: Vec<Framebuffer>,
 }
 
 impl Tile {
-    pub fn new(start_x: u32, start_y: u32, width: u32, height: u32) -> Tile {
+    pub fn new(start_x: u32, start_y: u32, width: u32, height: u32, layers: usize) -> Tile {
         Tile {
             start_x,
             start_y,
-            image: Image::new(width, height),
+            layers: (0..layers)
+                .map(|_| Framebuffer::new(width, height))
+                .collect(),
         }
     }
 
-    pub fn image(&self) -> &Image {
-        &self.image
+    pub fn set(&mut self, layer: usize, x: u32, y: u32, color: Vector3) {
+        self.layers[layer].set(x - self.start_x, y - self.start_y, color);
     }
 
-    pub fn set(&mut self, x: u32, y: u32, pixel: Pixel) {
-        self.image.set(x - self.start_x, y - self.start_y, pixel);
+    pub fn width(&self) -> u32 {
+        self.layers[0].width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.layers[0].height
     }
 
     pub fn start_x(&self) -> u32 {
@@ -145,8 +220,4 @@ impl Tile {
     pub fn start_y(&self) -> u32 {
         self.start_y
     }
-
-    pub fn extract_image(self) -> Image {
-        self.image
-    }
 }
@@ -0,0 +1,344 @@
+use crate::aov::Lobe;
+use crate::hittables::{Evaluation, HitRecord, Material, Scatter};
+use crate::microfacet::{fresnel_dielectric, local_frame, reflect, Ggx};
+use crate::nested::Interior;
+use crate::ray::Ray;
+use crate::rng::{hashed_f64, random_f64};
+use crate::textures::{ConstantTexture, Texture};
+use crate::vector::Vector3;
+
+/// Blends two materials, picking one per scattering event with probability
+/// given by the weight, read from the first channel: zero is all `a`, one is
+/// all `b`. Since the choice is made with the blend weight the weights cancel
+/// and each side reports its own attenuation unchanged. The choice is fixed
+/// by the hit, so the medium entered is the one of the material scattering.
+pub struct MixMaterial {
+    a: Box<dyn Material>,
+    b: Box<dyn Material>,
+    weight: Box<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(a: Box<dyn Material>, b: Box<dyn Material>, weight: f64) -> MixMaterial {
+        MixMaterial {
+            a,
+            b,
+            weight: Box::new(ConstantTexture::scalar(weight)),
+        }
+    }
+
+    pub fn with_weight_texture<T: Texture + 'static>(mut self, texture: T) -> MixMaterial {
+        self.weight = Box::new(texture);
+        self
+    }
+
+    fn weight(&self, hit_record: &HitRecord) -> f64 {
+        self.weight
+            .value(hit_record.u, hit_record.v, hit_record.p)
+            .x()
+            .clamp(0.0, 1.0)
+    }
+
+    fn pick(&self, hit_record: &HitRecord) -> &dyn Material {
+        // Unlike the hit point these survive transforms into object space.
+        let key = [hit_record.t, hit_record.u, hit_record.v];
+        if hashed_f64(&key) < self.weight(hit_record) {
+            self.b.as_ref()
+        } else {
+            self.a.as_ref()
+        }
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        let mut scatter = self.pick(hit_record).scatter(ray_in, hit_record)?;
+        if scatter.pdf.is_some() {
+            let direction = scatter.ray.direction();
+            scatter.pdf = self
+                .evaluate(ray_in, hit_record, direction)
+                .map(|evaluation| evaluation.pdf);
+        }
+        Some(scatter)
+    }
+
+    /// Only mixes of two materials that can both be evaluated can be.
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        let a = self.a.evaluate(ray_in, hit_record, direction)?;
+        let b = self.b.evaluate(ray_in, hit_record, direction)?;
+        let weight = self.weight(hit_record);
+        Some(Evaluation {
+            lobes: [0, 1, 2].map(|i| (1.0 - weight) * a.lobes[i] + weight * b.lobes[i]),
+            pdf: (1.0 - weight) * a.pdf + weight * b.pdf,
+        })
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Vector3 {
+        let weight = self.weight(hit_record);
+        (1.0 - weight) * self.a.emitted(hit_record) + weight * self.b.emitted(hit_record)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vector3 {
+        let weight = self.weight(hit_record);
+        (1.0 - weight) * self.a.albedo(hit_record) + weight * self.b.albedo(hit_record)
+    }
+
+    fn is_light(&self) -> bool {
+        self.a.is_light() || self.b.is_light()
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.a.is_dispersive() || self.b.is_dispersive()
+    }
+
+    fn interior(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Interior> {
+        self.pick(hit_record).interior(ray_in, hit_record)
+    }
+
+    fn is_opaque_at(&self, hit_record: &HitRecord) -> bool {
+        self.pick(hit_record).is_opaque_at(hit_record)
+    }
+}
+
+/// A clear dielectric layer, like varnish or car paint lacquer, over any base
+/// material. Light either reflects off the coat or passes through it to the
+/// base, getting tinted on the way in and out and losing what the coat
+/// reflects back inside on the way out, so the result never gains energy.
+/// The layer is treated as infinitely thin: the base is shaded at the same
+/// point and with the same incoming ray.
+pub struct Coated {
+    base: Box<dyn Material>,
+    ior: f64,
+    distribution: Ggx,
+    /// Optical depth of the coat crossed straight down.
+    absorption: Vector3,
+}
+
+impl Coated {
+    pub fn new(base: Box<dyn Material>, ior: f64) -> Coated {
+        Coated {
+            base,
+            ior,
+            distribution: Ggx::from_roughness(0.0),
+            absorption: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn with_roughness(mut self, roughness: f64) -> Coated {
+        self.distribution = Ggx::from_roughness(roughness);
+        self
+    }
+
+    /// Tints the coat so that light crossing it straight down and back up
+    /// keeps `color` of its energy.
+    pub fn with_tint(mut self, color: Vector3) -> Coated {
+        let depth = |c: f64| -0.5 * c.clamp(1e-4, 1.0).ln();
+        self.absorption = Vector3::new(depth(color.x()), depth(color.y()), depth(color.z()));
+        self
+    }
+
+    /// Fraction of light kept crossing the coat at `cosine` to the normal,
+    /// after refracting into it.
+    fn transmittance(&self, cosine: f64) -> Vector3 {
+        let sin2 = (1.0 - cosine * cosine) / (self.ior * self.ior);
+        let cos_t = (1.0 - sin2).max(1e-4).sqrt();
+        (-self.absorption / cos_t).exp()
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        let (frame, wo, entering) = local_frame(ray_in, hit_record);
+        if !entering {
+            return self.base.scatter(ray_in, hit_record);
+        }
+
+        let h = self.distribution.sample_visible_normal(wo);
+        let fresnel = fresnel_dielectric(wo.dot(h), self.ior);
+        let mut scatter = if random_f64() < fresnel {
+            let wi = reflect(-wo, h);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            let weight = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+            let scattered = Ray::new(hit_record.p, frame.world(wi));
+            let scatter = Scatter::new(Vector3::new(weight, weight, weight), scattered);
+            if self.distribution.is_smooth() {
+                return Some(scatter);
+            }
+            scatter.with_pdf(0.0).glossy()
+        } else {
+            // Picking the base with probability 1 - F cancels the light the
+            // coat lets in, leaving only the losses on the way back out.
+            let mut scatter = self.base.scatter(ray_in, hit_record)?;
+            let wi = frame.local(scatter.ray.direction().unit_vector());
+            scatter.attenuation = scatter.attenuation * self.transmittance(wo.z());
+            if wi.z() > 0.0 {
+                scatter.attenuation = scatter.attenuation
+                    * self.transmittance(wi.z())
+                    * (1.0 - fresnel_dielectric(wi.z(), self.ior));
+            }
+            scatter
+        };
+        if scatter.pdf.is_some() {
+            let direction = scatter.ray.direction();
+            scatter.pdf = self
+                .evaluate(ray_in, hit_record, direction)
+                .map(|evaluation| evaluation.pdf);
+        }
+        Some(scatter)
+    }
+
+    /// The base seen through the coat, plus the coat's own glossy reflection
+    /// when it is rough. Fresnel is taken at the macro surface normal for the
+    /// base, which matches sampling closely enough to weight samples by.
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        let (frame, wo, entering) = local_frame(ray_in, hit_record);
+        if !entering {
+            return self.base.evaluate(ray_in, hit_record, direction);
+        }
+        let base = self.base.evaluate(ray_in, hit_record, direction)?;
+        let wi = frame.local(direction.unit_vector());
+        if wi.z() <= 0.0 {
+            return Some(Evaluation::zero());
+        }
+        let fresnel_in = fresnel_dielectric(wo.z(), self.ior);
+        let fresnel_out = fresnel_dielectric(wi.z(), self.ior);
+        let through = self.transmittance(wo.z())
+            * self.transmittance(wi.z())
+            * ((1.0 - fresnel_in) * (1.0 - fresnel_out));
+        let mut evaluation = Evaluation {
+            lobes: base.lobes.map(|lobe| lobe * through),
+            pdf: (1.0 - fresnel_in) * base.pdf,
+        };
+        if !self.distribution.is_smooth() {
+            let h = (wo + wi).unit_vector();
+            let d = self.distribution.d(h);
+            let fresnel = fresnel_dielectric(wo.dot(h), self.ior);
+            let specular = fresnel * d * self.distribution.g2(wo, wi) / (4.0 * wo.z());
+            evaluation.lobes[Lobe::Specular as usize] += Vector3::new(specular, specular, specular);
+            evaluation.pdf += fresnel * d * self.distribution.g1(wo) / (4.0 * wo.z());
+        }
+        Some(evaluation)
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Vector3 {
+        self.base.emitted(hit_record)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vector3 {
+        self.base.albedo(hit_record)
+    }
+
+    fn is_light(&self) -> bool {
+        self.base.is_light()
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.base.is_dispersive()
+    }
+
+    fn interior(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Interior> {
+        self.base.interior(ray_in, hit_record)
+    }
+
+    fn is_opaque_at(&self, hit_record: &HitRecord) -> bool {
+        self.base.is_opaque_at(hit_record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::{Dielectric, HitId, Lambertian};
+    use crate::lights::Emissive;
+
+    fn hit(material: &dyn Material, t: f64) -> HitRecord<'_> {
+        HitRecord {
+            t,
+            p: Vector3::new(0.0, 0.0, 1.0),
+            normal: Vector3::new(0.0, 0.0, 1.0),
+            geometric_normal: Vector3::new(0.0, 0.0, 1.0),
+            dpdu: Vector3::new(1.0, 0.0, 0.0),
+            dpdv: Vector3::new(0.0, 1.0, 0.0),
+            u: 0.25,
+            v: 0.75,
+            material,
+            id: HitId::default(),
+        }
+    }
+
+    fn ray() -> Ray {
+        Ray::new(Vector3::new(0.0, 0.0, 2.0), Vector3::new(0.0, 0.0, -1.0))
+    }
+
+    fn absorbing_glass() -> Box<dyn Material> {
+        Box::new(Dielectric::new(1.5).with_absorption(Vector3::new(0.5, 1.0, 2.0)))
+    }
+
+    #[test]
+    fn coated_absorbing_dielectric_still_attenuates() {
+        let coated = Coated::new(absorbing_glass(), 1.5);
+        let interior = coated
+            .interior(&ray(), &hit(&coated, 1.0))
+            .expect("the coat hides the glass inside");
+        let kept = (-interior.absorption).exp();
+        assert!(kept.x() < 1.0 && kept.y() < 1.0 && kept.z() < 1.0);
+        assert!((interior.absorption.z() - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn mix_enters_the_medium_of_the_material_it_picks() {
+        let lambertian = || Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5)));
+        let all_glass = MixMaterial::new(lambertian(), absorbing_glass(), 1.0);
+        assert!(all_glass.interior(&ray(), &hit(&all_glass, 1.0)).is_some());
+        let no_glass = MixMaterial::new(lambertian(), absorbing_glass(), 0.0);
+        assert!(no_glass.interior(&ray(), &hit(&no_glass, 1.0)).is_none());
+
+        let half = MixMaterial::new(lambertian(), absorbing_glass(), 0.5);
+        let mut glass = 0;
+        for i in 0..1000 {
+            let rec = hit(&half, 1.0 + i as f64 * 1e-3);
+            let inside = half.interior(&ray(), &rec).is_some();
+            // Asking again at the same hit gives the same answer.
+            assert_eq!(inside, half.interior(&ray(), &rec).is_some());
+            assert_eq!(inside, std::ptr::eq(half.pick(&rec), half.b.as_ref()));
+            glass += inside as usize;
+        }
+        assert!((400..600).contains(&glass), "picked glass {} times", glass);
+    }
+
+    #[test]
+    fn mix_with_a_sampled_glow_is_a_light() {
+        let glow = Box::new(Emissive::new(Vector3::new(4.0, 4.0, 4.0)));
+        let mix = MixMaterial::new(absorbing_glass(), glow, 0.3);
+        assert!(mix.is_light());
+        assert!(!MixMaterial::new(absorbing_glass(), absorbing_glass(), 0.3).is_light());
+    }
+
+    #[test]
+    fn rough_coat_evaluates_into_specular_and_base_into_diffuse() {
+        let base = Box::new(Lambertian::from(Vector3::new(0.8, 0.4, 0.2)));
+        let coated = Coated::new(base, 1.5).with_roughness(0.3);
+        let rec = hit(&coated, 1.0);
+        let direction = Vector3::new(0.3, 0.0, 1.0).unit_vector();
+        let evaluation = coated.evaluate(&ray(), &rec, direction).unwrap();
+        let [diffuse, specular, transmission] = evaluation.lobes;
+        assert!(diffuse.x() > 0.0 && diffuse.x() > diffuse.z());
+        assert!(specular.x() > 0.0);
+        assert_eq!(specular.x(), specular.z());
+        assert_eq!(transmission.squared_length(), 0.0);
+        let sum = diffuse + specular;
+        assert!((evaluation.value() - sum).length() < 1e-12);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::camera::{Camera, CameraFrame};
+use crate::microfacet::refract;
+use crate::ray::Ray;
+use crate::rng::random_f64;
+use crate::vector::Vector3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Radial bands of the film with their own exit pupil bounds.
+const PUPIL_BANDS: usize = 64;
+
+/// Rays traced from each band of the film to find its exit pupil.
+const PUPIL_SAMPLES: usize = 4096;
+
+/// One spherical surface of a lens, or the aperture stop when flat.
+#[derive(Clone, Copy)]
+struct LensElement {
+    curvature_radius: f64,
+    /// Distance along the axis to the next surface, or to the film after
+    /// the last one.
+    thickness: f64,
+    /// Index of refraction of the glass behind the surface, or zero for
+    /// the air gap at the stop.
+    eta: f64,
+    aperture_radius: f64,
+}
+
+impl LensElement {
+    fn is_stop(&self) -> bool {
+        self.curvature_radius == 0.0
+    }
+}
+
+/// Intersection with a spherical surface centred on the axis at `z_center`,
+/// returning the distance and the normal facing back along the ray.
+fn intersect_element(radius: f64, z_center: f64, ray: &Ray) -> Option<(f64, Vector3)> {
+    let oc = ray.origin() - Vector3::new(0.0, 0.0, z_center);
+    let d = ray.direction();
+    let a = d.squared_length();
+    let b = 2.0 * d.dot(oc);
+    let c = oc.squared_length() - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (t0, t1) = ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a));
+    // Only one of the two hits is on the cap of the sphere the lens uses.
+    let t = if (d.z() > 0.0) ^ (radius < 0.0) {
+        t0.min(t1)
+    } else {
+        t0.max(t1)
+    };
+    if t < 0.0 {
+        return None;
+    }
+    let normal = (oc + t * d).unit_vector();
+    let normal = if normal.dot(d) > 0.0 { -normal } else { normal };
+    Some((t, normal))
+}
+
+/// A lens prescription: spherical elements listed from the front, facing
+/// the scene, to the rear, facing the film, which sits at `z = 0` with the
+/// lens towards -z.
+#[derive(Clone)]
+pub struct LensSystem {
+    elements: Vec<LensElement>,
+}
+
+impl LensSystem {
+    pub fn load(path: &Path) -> Result<LensSystem, io::Error> {
+        LensSystem::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Reads one surface per line as curvature radius, thickness, index of
+    /// refraction and aperture diameter, all lengths in millimetres, with
+    /// `#` starting comments. The aperture stop has a radius of zero.
+    pub fn parse(text: &str) -> Result<LensSystem, io::Error> {
+        let mut elements = vec![];
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let values = line
+                .split_whitespace()
+                .map(|token| token.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid_data("invalid number in lens file"))?;
+            let (radius, thickness, eta, aperture) = match values.as_slice() {
+                [radius, thickness, eta, aperture] => (*radius, *thickness, *eta, *aperture),
+                _ => return Err(invalid_data("lens surfaces need four values")),
+            };
+            elements.push(LensElement {
+                curvature_radius: 0.001 * radius,
+                thickness: 0.001 * thickness,
+                eta,
+                aperture_radius: 0.0005 * aperture,
+            });
+        }
+        if elements.is_empty() {
+            return Err(invalid_data("lens file has no surfaces"));
+        }
+        Ok(LensSystem { elements })
+    }
+
+    /// Closes the stop down to f-number `f_number`, going by the focal
+    /// length of the lens. It never opens wider than the prescription.
+    pub fn stopped_down(mut self, f_number: f64) -> Result<LensSystem, io::Error> {
+        let (principal, focal) = self.thick_lens(1e-4)?;
+        let radius = 0.5 * (focal[0] - principal[0]).abs() / f_number.max(0.1);
+        for element in self.elements.iter_mut().filter(|e| e.is_stop()) {
+            element.aperture_radius = element.aperture_radius.min(radius);
+        }
+        Ok(self)
+    }
+
+    fn front_z(&self) -> f64 {
+        self.elements.iter().map(|e| e.thickness).sum()
+    }
+
+    fn rear_z(&self) -> f64 {
+        self.elements.last().map_or(0.0, |e| e.thickness)
+    }
+
+    fn rear_radius(&self) -> f64 {
+        self.elements.last().map_or(0.0, |e| e.aperture_radius)
+    }
+
+    /// Follows a ray leaving the film through the lens, returning it once
+    /// it has left the front element, or `None` if the lens blocks it.
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut z = 0.0;
+        let mut ray = *ray;
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            z -= element.thickness;
+            let (t, normal) = if element.is_stop() {
+                if ray.direction().z() >= 0.0 {
+                    return None;
+                }
+                ((z - ray.origin().z()) / ray.direction().z(), None)
+            } else {
+                let radius = element.curvature_radius;
+                let (t, normal) = intersect_element(radius, z + radius, &ray)?;
+                (t, Some(normal))
+            };
+            let hit = ray.point_at(t);
+            if hit.x() * hit.x() + hit.y() * hit.y() > element.aperture_radius.powi(2) {
+                return None;
+            }
+            ray = match normal {
+                Some(normal) => {
+                    let eta_i = if element.eta != 0.0 { element.eta } else { 1.0 };
+                    let eta_t = match i.checked_sub(1).map(|p| self.elements[p].eta) {
+                        Some(eta) if eta != 0.0 => eta,
+                        _ => 1.0,
+                    };
+                    let direction = refract(-ray.direction().unit_vector(), normal, eta_i / eta_t)?;
+                    Ray::new(hit, direction)
+                }
+                None => Ray::new(hit, ray.direction()),
+            };
+        }
+        Some(ray)
+    }
+
+    /// Follows a ray from the scene through the lens to the film side.
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut z = -self.front_z();
+        let mut ray = *ray;
+        for (i, element) in self.elements.iter().enumerate() {
+            let (t, normal) = if element.is_stop() {
+                if ray.direction().z() <= 0.0 {
+                    return None;
+                }
+                ((z - ray.origin().z()) / ray.direction().z(), None)
+            } else {
+                let radius = element.curvature_radius;
+                let (t, normal) = intersect_element(radius, z + radius, &ray)?;
+                (t, Some(normal))
+            };
+            let hit = ray.point_at(t);
+            if hit.x() * hit.x() + hit.y() * hit.y() > element.aperture_radius.powi(2) {
+                return None;
+            }
+            ray = match normal {
+                Some(normal) => {
+                    let eta_i = match i.checked_sub(1).map(|p| self.elements[p].eta) {
+                        Some(eta) if eta != 0.0 => eta,
+                        _ => 1.0,
+                    };
+                    let eta_t = if element.eta != 0.0 { element.eta } else { 1.0 };
+                    let direction = refract(-ray.direction().unit_vector(), normal, eta_i / eta_t)?;
+                    Ray::new(hit, direction)
+                }
+                None => Ray::new(hit, ray.direction()),
+            };
+            z += element.thickness;
+        }
+        Some(ray)
+    }
+
+    /// Principal planes and focal points along z on the scene side and the
+    /// film side, found by tracing rays parallel to the axis at `height`.
+    fn thick_lens(&self, height: f64) -> Result<([f64; 2], [f64; 2]), io::Error> {
+        let cardinal = |entering: &Ray, leaving: &Ray| {
+            let focal = leaving.point_at(-leaving.origin().x() / leaving.direction().x());
+            let principal = leaving
+                .point_at((entering.origin().x() - leaving.origin().x()) / leaving.direction().x());
+            (principal.z(), focal.z())
+        };
+        let from_scene = Ray::new(
+            Vector3::new(height, 0.0, -self.front_z() - 1.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        let to_film = self
+            .trace_from_scene(&from_scene)
+            .ok_or_else(|| invalid_data("lens does not pass rays near its axis"))?;
+        let from_film = Ray::new(
+            Vector3::new(height, 0.0, -self.rear_z() + 1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        );
+        let to_scene = self
+            .trace_from_film(&from_film)
+            .ok_or_else(|| invalid_data("lens does not pass rays near its axis"))?;
+        let (film_principal, film_focal) = cardinal(&from_scene, &to_film);
+        let (scene_principal, scene_focal) = cardinal(&from_film, &to_scene);
+        Ok(([film_principal, scene_principal], [film_focal, scene_focal]))
+    }
+
+    /// Moves the lens away from the film so that it focuses at `distance`
+    /// in front of the film, using a thick lens approximation.
+    fn focus(&mut self, distance: f64, height: f64) -> Result<(), io::Error> {
+        let (principal, focal) = self.thick_lens(height)?;
+        let f = focal[0] - principal[0];
+        let z = -distance;
+        let c = (principal[1] - z - principal[0]) * (principal[1] - z - 4.0 * f - principal[0]);
+        if c <= 0.0 {
+            return Err(invalid_data("lens cannot focus that close"));
+        }
+        let delta = 0.5 * (principal[1] - z + principal[0] - c.sqrt());
+        if let Some(last) = self.elements.last_mut() {
+            last.thickness += delta;
+        }
+        Ok(())
+    }
+
+    /// Bounds on the rear element, as `(min x, min y, max x, max y)`, of the
+    /// rays from film points between `r0` and `r1` along +x that make it
+    /// through the lens.
+    fn exit_pupil(&self, r0: f64, r1: f64) -> (f64, f64, f64, f64) {
+        let reach = 1.5 * self.rear_radius();
+        let rear_z = -self.rear_z();
+        let mut bounds: Option<(f64, f64, f64, f64)> = None;
+        for _ in 0..PUPIL_SAMPLES {
+            let film = Vector3::new(r0 + random_f64() * (r1 - r0), 0.0, 0.0);
+            let x = reach * (2.0 * random_f64() - 1.0);
+            let y = reach * (2.0 * random_f64() - 1.0);
+            let rear = Vector3::new(x, y, rear_z);
+            if self.trace_from_film(&Ray::new(film, rear - film)).is_some() {
+                bounds = Some(match bounds {
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                    None => (x, y, x, y),
+                });
+            }
+        }
+        match bounds {
+            // Grown by a sample spacing, which a random sample may miss.
+            Some((x0, y0, x1, y1)) => {
+                let margin = 2.0 * reach / (PUPIL_SAMPLES as f64).sqrt();
+                (x0 - margin, y0 - margin, x1 + margin, y1 + margin)
+            }
+            None => (-reach, -reach, reach, reach),
+        }
+    }
+}
+
+/// Camera tracing rays through a real lens prescription onto a film of the
+/// given diagonal, with the distortion, vignetting and focus breathing of
+/// that lens. Rays are aimed at precomputed bounds of the exit pupil so few
+/// are wasted on the lens barrel.
+pub struct RealisticCamera {
+    frame: CameraFrame,
+    lens: LensSystem,
+    film_width: f64,
+    film_height: f64,
+    pupils: Vec<(f64, f64, f64, f64)>,
+    /// Largest pupil area, to which others are relative.
+    max_pupil_area: f64,
+}
+
+impl RealisticCamera {
+    /// `film_diagonal` is in millimetres, `focus_distance` in scene units
+    /// taken as metres.
+    pub fn new(
+        frame: CameraFrame,
+        lens: LensSystem,
+        film_diagonal: f64,
+        aspect: f64,
+        focus_distance: f64,
+    ) -> Result<RealisticCamera, io::Error> {
+        let diagonal = 0.001 * film_diagonal;
+        let film_height = diagonal / (1.0 + aspect * aspect).sqrt();
+        let film_width = aspect * film_height;
+        let mut lens = lens;
+        lens.focus(focus_distance, 0.001 * diagonal)?;
+        let half = 0.5 * diagonal;
+        let pupils: Vec<_> = (0..PUPIL_BANDS)
+            .map(|band| {
+                lens.exit_pupil(
+                    half * band as f64 / PUPIL_BANDS as f64,
+                    half * (band + 1) as f64 / PUPIL_BANDS as f64,
+                )
+            })
+            .collect();
+        let max_pupil_area = pupils
+            .iter()
+            .map(|(x0, y0, x1, y1)| (x1 - x0) * (y1 - y0))
+            .fold(0.0, f64::max);
+        Ok(RealisticCamera {
+            frame,
+            lens,
+            film_width,
+            film_height,
+            pupils,
+            max_pupil_area,
+        })
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn generate_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        // The lens turns the image upside down, so the film is too.
+        let film = Vector3::new(
+            -(s - 0.5) * self.film_width,
+            -(t - 0.5) * self.film_height,
+            0.0,
+        );
+        let radius = (film.x() * film.x() + film.y() * film.y()).sqrt();
+        let half =
+            0.5 * (self.film_width * self.film_width + self.film_height * self.film_height).sqrt();
+        let band = ((radius / half * PUPIL_BANDS as f64) as usize).min(PUPIL_BANDS - 1);
+        let (x0, y0, x1, y1) = self.pupils[band];
+        let x = x0 + random_f64() * (x1 - x0);
+        let y = y0 + random_f64() * (y1 - y0);
+        // Pupils were found along +x, so turn the sample to the film point.
+        let (sin, cos) = if radius > 0.0 {
+            (film.y() / radius, film.x() / radius)
+        } else {
+            (0.0, 1.0)
+        };
+        let rear = Vector3::new(cos * x - sin * y, sin * x + cos * y, -self.lens.rear_z());
+        let direction = (rear - film).unit_vector();
+
+        // Light reaching the film falls off with the pupil area and the
+        // fourth power of the cosine; rays are dropped to match.
+        let area = (x1 - x0) * (y1 - y0);
+        let cos_theta = -direction.z();
+        if random_f64() >= cos_theta.powi(4) * area / self.max_pupil_area {
+            return None;
+        }
+        let ray = self.lens.trace_from_film(&Ray::new(film, direction))?;
+        Some(Ray::new(
+            self.frame.point(ray.origin()),
+            self.frame.world(ray.direction()),
+        ))
+    }
+}
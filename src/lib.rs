@@ -1,7 +1,37 @@
+mod aabb;
+mod alpha;
+mod animation;
+mod aov;
+mod bump;
+mod bvh;
+mod camera;
+mod csg;
+mod denoise;
+mod environment;
+mod hdr;
 mod hittables;
+mod ies;
 mod image;
+mod layered;
+mod lens;
+mod lights;
+mod lightsampler;
+mod microfacet;
+mod nested;
+mod onb;
+pub mod png;
 pub mod ppm;
+mod principled;
+mod quadrics;
 mod ray;
 pub mod renderers;
 mod rng;
+mod sdf;
+mod sky;
+mod spectrum;
+mod subsurface;
+mod textures;
+mod thinfilm;
+mod transform;
 mod vector;
+mod volumes;
@@ -0,0 +1,339 @@
+use crate::aabb::Aabb;
+use crate::environment::LightSample;
+use crate::hittables::{HitRecord, Material, Scatter};
+use crate::ies::IesProfile;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rng::random_f64;
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+
+/// A light that is reached by sampling it with shadow rays.
+pub trait Light: Send + Sync {
+    /// Light arriving at `point` with the solid angle density its direction
+    /// was picked with. Lights shining from a single direction have their
+    /// radiance already divided by the density and a density of one.
+    fn sample(&self, point: Vector3) -> Option<LightSample>;
+
+    /// Where the light is and which way it shines, or `None` for lights
+    /// infinitely far away.
+    fn bounds(&self) -> Option<LightBounds>;
+}
+
+/// Extent, power and directions of emission of one or more lights, for
+/// picking the ones that matter most to a point among many.
+#[derive(Clone, Copy)]
+pub struct LightBounds {
+    pub bounds: Aabb,
+    /// Total emitted power as luminance.
+    pub power: f64,
+    /// Central direction of emission.
+    pub axis: Vector3,
+    /// Cosine of the widest angle between `axis` and the emitters' normals.
+    pub cos_normal: f64,
+    /// Cosine of the widest angle light leaves at from those normals.
+    pub cos_emission: f64,
+}
+
+impl LightBounds {
+    /// Bounds of a light shining evenly in all directions.
+    fn omnidirectional(bounds: Aabb, power: f64) -> LightBounds {
+        LightBounds {
+            bounds,
+            power,
+            axis: Vector3::new(0.0, 0.0, 1.0),
+            cos_normal: -1.0,
+            cos_emission: 0.0,
+        }
+    }
+}
+
+fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// A photometric profile placed on a light, with the axis its vertical
+/// angles are measured from and the direction of horizontal angle zero.
+struct Goniometry {
+    profile: IesProfile,
+    front: Vector3,
+    side: Vector3,
+    down: Vector3,
+}
+
+impl Goniometry {
+    fn new(profile: IesProfile, down: Vector3, front: Vector3) -> Goniometry {
+        let down = down.unit_vector();
+        let front = (front - front.dot(down) * down).unit_vector();
+        Goniometry {
+            profile,
+            front,
+            side: down.cross(front),
+            down,
+        }
+    }
+
+    /// Fraction of the light's intensity emitted along `direction`.
+    fn intensity(&self, direction: Vector3) -> f64 {
+        let local = Vector3::new(
+            direction.dot(self.front),
+            direction.dot(self.side),
+            direction.dot(self.down),
+        );
+        self.profile.relative_intensity(local)
+    }
+}
+
+/// Light spreading out from a point, falling off with the square of the
+/// distance, evenly or following a photometric profile.
+pub struct PointLight {
+    position: Vector3,
+    intensity: Vector3,
+    goniometry: Option<Goniometry>,
+}
+
+impl PointLight {
+    /// `intensity` is the radiance arriving at one unit of distance.
+    pub fn new(position: Vector3, intensity: Vector3) -> PointLight {
+        PointLight {
+            position,
+            intensity,
+            goniometry: None,
+        }
+    }
+
+    /// Shapes the light by an IES profile hanging along `down`, with its
+    /// horizontal angle zero towards `front`. `intensity` is then reached in
+    /// the profile's brightest direction.
+    pub fn with_profile(
+        mut self,
+        profile: IesProfile,
+        down: Vector3,
+        front: Vector3,
+    ) -> PointLight {
+        self.goniometry = Some(Goniometry::new(profile, down, front));
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: Vector3) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let shape = match &self.goniometry {
+            Some(goniometry) => goniometry.intensity(-direction),
+            None => 1.0,
+        };
+        if shape <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            radiance: shape * self.intensity / (distance * distance),
+            pdf: 1.0,
+            distance,
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omnidirectional(
+            Aabb::new(self.position, self.position),
+            4.0 * PI * self.intensity.luminance(),
+        ))
+    }
+}
+
+/// A point light shining into a cone, at full strength inside `inner` degrees
+/// of its axis and fading out smoothly to nothing at `outer`.
+pub struct SpotLight {
+    light: PointLight,
+    axis: Vector3,
+    cos_inner: f64,
+    cos_outer: f64,
+    falloff: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vector3,
+        target: Vector3,
+        intensity: Vector3,
+        inner: f64,
+        outer: f64,
+    ) -> SpotLight {
+        let outer = outer.max(inner);
+        SpotLight {
+            light: PointLight::new(position, intensity),
+            axis: (target - position).unit_vector(),
+            cos_inner: inner.to_radians().cos(),
+            cos_outer: outer.to_radians().cos(),
+            falloff: 1.0,
+        }
+    }
+
+    /// Sharpens the fade between the cones above one and softens it below.
+    pub fn with_falloff(mut self, falloff: f64) -> SpotLight {
+        self.falloff = falloff.max(0.0);
+        self
+    }
+
+    /// Shapes the light inside the cone by an IES profile aimed along the
+    /// spot's axis, with its horizontal angle zero towards `front`.
+    pub fn with_profile(mut self, profile: IesProfile, front: Vector3) -> SpotLight {
+        self.light = self.light.with_profile(profile, self.axis, front);
+        self
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: Vector3) -> Option<LightSample> {
+        let mut sample = self.light.sample(point)?;
+        let cosine = -sample.direction.dot(self.axis);
+        let spread = (self.cos_inner - self.cos_outer).max(1e-6);
+        let cone = smoothstep((cosine - self.cos_outer) / spread).powf(self.falloff);
+        if cone <= 0.0 {
+            return None;
+        }
+        sample.radiance = cone * sample.radiance;
+        Some(sample)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let bounds = self.light.bounds()?;
+        // Solid angle of the cone, counting the fade as half strength.
+        let solid_angle = 2.0 * PI * (1.0 - 0.5 * (self.cos_inner + self.cos_outer));
+        Some(LightBounds {
+            power: solid_angle * self.light.intensity.luminance(),
+            axis: self.axis,
+            cos_normal: 1.0,
+            cos_emission: self.cos_outer,
+            ..bounds
+        })
+    }
+}
+
+/// Parallel light from infinitely far away, like the sun.
+pub struct DirectionalLight {
+    /// Unit direction towards the light.
+    direction: Vector3,
+    irradiance: Vector3,
+}
+
+impl DirectionalLight {
+    /// Light travelling along `direction`, giving `irradiance` on surfaces
+    /// facing it.
+    pub fn new(direction: Vector3, irradiance: Vector3) -> DirectionalLight {
+        DirectionalLight {
+            direction: -direction.unit_vector(),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: Vector3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: self.direction,
+            radiance: self.irradiance,
+            pdf: 1.0,
+            distance: f64::INFINITY,
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+}
+
+/// A glowing sphere sampled by the solid angle it covers. It is not
+/// visible on its own: the geometry goes in the world with an `Emissive`
+/// material of the same radiance.
+pub struct SphereLight {
+    center: Vector3,
+    radius: f64,
+    radiance: Vector3,
+}
+
+impl SphereLight {
+    pub fn new(center: Vector3, radius: f64, radiance: Vector3) -> SphereLight {
+        SphereLight {
+            center,
+            radius,
+            radiance,
+        }
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, point: Vector3) -> Option<LightSample> {
+        let to_center = self.center - point;
+        let distance_squared = to_center.squared_length();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        let cos_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let cos_theta = 1.0 - random_f64() * (1.0 - cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_f64();
+        let direction = Onb::from_w(to_center).world(Vector3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        // Nearer intersection of the sampled direction with the sphere.
+        let along = to_center.dot(direction);
+        let distance = along
+            - (radius_squared - (distance_squared - along * along))
+                .max(0.0)
+                .sqrt();
+        Some(LightSample {
+            direction,
+            radiance: self.radiance,
+            pdf: 1.0 / (2.0 * PI * (1.0 - cos_max)),
+            distance,
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let extent = Vector3::new(self.radius, self.radius, self.radius);
+        let area = 4.0 * PI * self.radius * self.radius;
+        Some(LightBounds::omnidirectional(
+            Aabb::new(self.center - extent, self.center + extent),
+            PI * area * self.radiance.luminance(),
+        ))
+    }
+}
+
+/// Surface that glows and absorbs everything falling on it. Light sampling
+/// is left to a matching `Light`, so rays only pick its glow up when they
+/// could not have sampled that light: straight from the camera or off
+/// mirror-like surfaces.
+pub struct Emissive {
+    radiance: Vector3,
+}
+
+impl Emissive {
+    pub fn new(radiance: Vector3) -> Emissive {
+        Emissive { radiance }
+    }
+}
+
+impl Material for Emissive {
+    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Vector3 {
+        self.radiance
+    }
+
+    fn is_light(&self) -> bool {
+        true
+    }
+}
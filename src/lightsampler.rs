@@ -0,0 +1,389 @@
+use crate::aabb::Aabb;
+use crate::lights::{Light, LightBounds};
+use crate::rng::random_f64;
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+
+/// How next event estimation picks the one light it samples at each hit.
+#[derive(Clone, Copy, Default)]
+pub enum LightSelection {
+    /// In proportion to each light's power, wherever the hit is.
+    Power,
+    /// Down a bounding volume hierarchy over the lights, favouring those
+    /// that are bright, near and facing the hit.
+    #[default]
+    Tree,
+}
+
+/// Walker's alias method, picking among weighted items in constant time.
+struct AliasTable {
+    /// Probability of each item.
+    probability: Vec<f64>,
+    /// Chance of keeping an item once its bucket is picked rather than
+    /// moving on to its alias.
+    threshold: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    fn new(weights: &[f64]) -> AliasTable {
+        let count = weights.len();
+        let total: f64 = weights.iter().map(|w| w.max(0.0)).sum();
+        let probability: Vec<f64> = if total > 0.0 {
+            weights.iter().map(|w| w.max(0.0) / total).collect()
+        } else {
+            vec![1.0 / count as f64; count]
+        };
+        let mut threshold: Vec<f64> = probability.iter().map(|p| p * count as f64).collect();
+        let mut alias: Vec<usize> = (0..count).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..count).partition(|&i| threshold[i] < 1.0);
+        while let (Some(&under), Some(&over)) = (small.last(), large.last()) {
+            small.pop();
+            alias[under] = over;
+            threshold[over] -= 1.0 - threshold[under];
+            if threshold[over] < 1.0 {
+                large.pop();
+                small.push(over);
+            }
+        }
+        // What is left over is only off from one by rounding.
+        for i in small.into_iter().chain(large) {
+            threshold[i] = 1.0;
+        }
+        AliasTable {
+            probability,
+            threshold,
+            alias,
+        }
+    }
+
+    /// Picks an item, returning it with its probability.
+    fn sample(&self) -> Option<(usize, f64)> {
+        if self.alias.is_empty() {
+            return None;
+        }
+        let scaled = random_f64() * self.alias.len() as f64;
+        let bucket = (scaled as usize).min(self.alias.len() - 1);
+        let index = if scaled - (bucket as f64) < self.threshold[bucket] {
+            bucket
+        } else {
+            self.alias[bucket]
+        };
+        Some((index, self.probability[index]))
+    }
+}
+
+/// Smallest cone holding cones `a` and `b`, each given by its axis and the
+/// cosine of its half angle.
+fn cone_union(a: (Vector3, f64), b: (Vector3, f64)) -> (Vector3, f64) {
+    let (theta_a, theta_b) = (a.1.clamp(-1.0, 1.0).acos(), b.1.clamp(-1.0, 1.0).acos());
+    let theta_d = a.0.dot(b.0).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return a;
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return b;
+    }
+    let theta = 0.5 * (theta_a + theta_d + theta_b);
+    let turn_axis = a.0.cross(b.0);
+    if theta >= PI || turn_axis.squared_length() <= 0.0 {
+        return (a.0, -1.0);
+    }
+    // Turn a's axis towards b's until the cone just holds both.
+    let k = turn_axis.unit_vector();
+    let turn = theta - theta_a;
+    let axis = a.0 * turn.cos() + k.cross(a.0) * turn.sin() + k * k.dot(a.0) * (1.0 - turn.cos());
+    (axis, theta.cos())
+}
+
+fn bounds_union(a: &LightBounds, b: &LightBounds) -> LightBounds {
+    let (axis, cos_normal) = cone_union((a.axis, a.cos_normal), (b.axis, b.cos_normal));
+    LightBounds {
+        bounds: Aabb::surrounding(a.bounds, b.bounds),
+        power: a.power + b.power,
+        axis,
+        cos_normal,
+        cos_emission: a.cos_emission.min(b.cos_emission),
+    }
+}
+
+/// How much the lights in `bounds` could contribute at `point`: their power
+/// over the squared distance, times the cosine of the smallest angle any of
+/// them could be shining at towards the point. After Conty Estevez and Kulla,
+/// "Importance Sampling of Many Lights with Adaptive Tree Splitting".
+fn importance(bounds: &LightBounds, point: Vector3) -> f64 {
+    let center = 0.5 * (bounds.bounds.min() + bounds.bounds.max());
+    let radius = 0.5 * bounds.bounds.extent().length();
+    let to_point = point - center;
+    let distance = to_point.length();
+    let (cos_w, theta_b) = if distance > radius {
+        (
+            bounds.axis.dot(to_point / distance),
+            (radius / distance).asin(),
+        )
+    } else {
+        (1.0, PI)
+    };
+    let theta_w = cos_w.clamp(-1.0, 1.0).acos();
+    let theta_o = bounds.cos_normal.clamp(-1.0, 1.0).acos();
+    let theta_e = bounds.cos_emission.clamp(-1.0, 1.0).acos();
+    let theta = (theta_w - theta_o - theta_b).max(0.0);
+    if theta >= theta_e {
+        return 0.0;
+    }
+    let distance_squared = (distance * distance).max(0.25 * radius * radius).max(1e-6);
+    bounds.power * theta.cos() / distance_squared
+}
+
+enum Node {
+    Leaf(usize),
+    /// Index of the second child; the first follows right after its parent.
+    Interior(usize),
+}
+
+/// Light bounding volume hierarchy, stored depth first.
+struct LightTree {
+    nodes: Vec<(LightBounds, Node)>,
+}
+
+impl LightTree {
+    fn new(lights: &mut [(usize, LightBounds)]) -> LightTree {
+        let mut tree = LightTree { nodes: vec![] };
+        if !lights.is_empty() {
+            tree.build(lights);
+        }
+        tree
+    }
+
+    /// Splits `lights` in half along the widest spread of their centres.
+    fn build(&mut self, lights: &mut [(usize, LightBounds)]) -> LightBounds {
+        if let [(index, bounds)] = lights {
+            self.nodes.push((*bounds, Node::Leaf(*index)));
+            return *bounds;
+        }
+        let centre = |bounds: &LightBounds| 0.5 * (bounds.bounds.min() + bounds.bounds.max());
+        let spread = lights[1..].iter().fold(
+            Aabb::new(centre(&lights[0].1), centre(&lights[0].1)),
+            |spread, (_, bounds)| {
+                let c = centre(bounds);
+                Aabb::surrounding(spread, Aabb::new(c, c))
+            },
+        );
+        let extent = spread.extent();
+        let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        };
+        lights.sort_by(|a, b| centre(&a.1)[axis].total_cmp(&centre(&b.1)[axis]));
+
+        let node = self.nodes.len();
+        // Placeholder until both children are built.
+        self.nodes.push((lights[0].1, Node::Interior(0)));
+        let (first, second) = lights.split_at_mut(lights.len() / 2);
+        let first = self.build(first);
+        let second_index = self.nodes.len();
+        let second = self.build(second);
+        let bounds = bounds_union(&first, &second);
+        self.nodes[node] = (bounds, Node::Interior(second_index));
+        bounds
+    }
+
+    /// Walks down to a light, picking children by their importance at
+    /// `point`, returning it with the probability of the walk.
+    fn sample(&self, point: Vector3) -> Option<(usize, f64)> {
+        let (root, _) = self.nodes.first()?;
+        if importance(root, point) <= 0.0 {
+            return None;
+        }
+        let mut node = 0;
+        let mut probability = 1.0;
+        loop {
+            match self.nodes[node].1 {
+                Node::Leaf(index) => return Some((index, probability)),
+                Node::Interior(second) => {
+                    let first_importance = importance(&self.nodes[node + 1].0, point);
+                    let second_importance = importance(&self.nodes[second].0, point);
+                    let total = first_importance + second_importance;
+                    if total <= 0.0 {
+                        return None;
+                    }
+                    let p = first_importance / total;
+                    if random_f64() < p {
+                        node += 1;
+                        probability *= p;
+                    } else {
+                        node = second;
+                        probability *= 1.0 - p;
+                    }
+                }
+            }
+        }
+    }
+}
+
+enum Selector {
+    Power(AliasTable),
+    Tree(LightTree),
+}
+
+/// Picks one of a scene's lights to sample from a point. Lights infinitely
+/// far away have no position to go by, so they are picked uniformly, each
+/// as likely as the whole of the others.
+pub struct LightSampler {
+    infinite: Vec<usize>,
+    bounded: Vec<usize>,
+    selector: Selector,
+}
+
+impl LightSampler {
+    pub fn new(lights: &[Box<dyn Light>], selection: LightSelection) -> LightSampler {
+        let mut infinite = vec![];
+        let mut bounded = vec![];
+        for (index, light) in lights.iter().enumerate() {
+            match light.bounds() {
+                Some(bounds) => bounded.push((index, bounds)),
+                None => infinite.push(index),
+            }
+        }
+        let selector = match selection {
+            LightSelection::Power => Selector::Power(AliasTable::new(
+                &bounded.iter().map(|(_, b)| b.power).collect::<Vec<_>>(),
+            )),
+            LightSelection::Tree => Selector::Tree(LightTree::new(&mut bounded)),
+        };
+        LightSampler {
+            infinite,
+            bounded: bounded.into_iter().map(|(index, _)| index).collect(),
+            selector,
+        }
+    }
+
+    /// Picks a light to sample from `point`, returning its index in the
+    /// scene with the probability it was picked.
+    pub fn sample(&self, point: Vector3) -> Option<(usize, f64)> {
+        let groups = self.infinite.len() + usize::from(!self.bounded.is_empty());
+        if groups == 0 {
+            return None;
+        }
+        let infinite_probability = self.infinite.len() as f64 / groups as f64;
+        let u = random_f64();
+        if u < infinite_probability {
+            let pick = ((u * groups as f64) as usize).min(self.infinite.len() - 1);
+            return Some((self.infinite[pick], 1.0 / groups as f64));
+        }
+        let bounded_probability = 1.0 - infinite_probability;
+        match &self.selector {
+            Selector::Power(table) => table
+                .sample()
+                .map(|(pick, p)| (self.bounded[pick], bounded_probability * p)),
+            Selector::Tree(tree) => tree
+                .sample(point)
+                .map(|(index, p)| (index, bounded_probability * p)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point_light(position: Vector3, power: f64) -> LightBounds {
+        LightBounds {
+            bounds: Aabb::new(position, position),
+            power,
+            axis: Vector3::new(0.0, 0.0, 1.0),
+            cos_normal: -1.0,
+            cos_emission: 0.0,
+        }
+    }
+
+    /// Probability of the walk down `tree` from `point` ending at each light.
+    fn leaf_probabilities(tree: &LightTree, point: Vector3, count: usize) -> Vec<f64> {
+        fn walk(tree: &LightTree, point: Vector3, node: usize, p: f64, out: &mut [f64]) {
+            match tree.nodes[node].1 {
+                Node::Leaf(index) => out[index] += p,
+                Node::Interior(second) => {
+                    let first = importance(&tree.nodes[node + 1].0, point);
+                    let other = importance(&tree.nodes[second].0, point);
+                    walk(tree, point, node + 1, p * first / (first + other), out);
+                    walk(tree, point, second, p * other / (first + other), out);
+                }
+            }
+        }
+        let mut probabilities = vec![0.0; count];
+        walk(tree, point, 0, 1.0, &mut probabilities);
+        probabilities
+    }
+
+    #[test]
+    fn alias_table_picks_in_proportion_to_weight() {
+        let weights = [1.0, 0.0, 3.0, 0.5, 5.5];
+        let table = AliasTable::new(&weights);
+        let total: f64 = weights.iter().sum();
+        let count = weights.len() as f64;
+        // Each bucket keeps its item or passes to its alias.
+        let mut picked = vec![0.0; weights.len()];
+        for (bucket, &alias) in table.alias.iter().enumerate() {
+            picked[bucket] += table.threshold[bucket] / count;
+            picked[alias] += (1.0 - table.threshold[bucket]) / count;
+        }
+        assert!((table.probability.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        for i in 0..weights.len() {
+            assert!((table.probability[i] - weights[i] / total).abs() < 1e-12);
+            assert!((picked[i] - weights[i] / total).abs() < 1e-12);
+        }
+        for _ in 0..1000 {
+            let (index, p) = table.sample().unwrap();
+            assert_eq!(p, weights[index] / total);
+        }
+    }
+
+    #[test]
+    fn alias_table_without_weight_is_uniform() {
+        let table = AliasTable::new(&[0.0, 0.0, 0.0, 0.0]);
+        assert!(table.probability.iter().all(|&p| p == 0.25));
+        assert!(AliasTable::new(&[]).sample().is_none());
+    }
+
+    #[test]
+    fn light_tree_picks_coincident_lights_by_power() {
+        let powers = [2.0, 7.0, 1.0, 4.0, 6.0];
+        let position = Vector3::new(1.0, 2.0, 3.0);
+        let mut lights: Vec<(usize, LightBounds)> = powers
+            .iter()
+            .enumerate()
+            .map(|(i, &power)| (i, point_light(position, power)))
+            .collect();
+        let tree = LightTree::new(&mut lights);
+        let point = Vector3::new(-2.0, 0.5, 0.0);
+        let probabilities = leaf_probabilities(&tree, point, powers.len());
+        let total: f64 = powers.iter().sum();
+        for (p, power) in probabilities.iter().zip(&powers) {
+            assert!((p - power / total).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn light_tree_probabilities_sum_to_one_and_match_sampling() {
+        let mut lights: Vec<(usize, LightBounds)> = (0..13)
+            .map(|i| {
+                let x = i as f64;
+                let position = Vector3::new(x.sin() * 5.0, x * 0.3, (x * 1.7).cos() * 4.0);
+                (i, point_light(position, 1.0 + (i % 4) as f64))
+            })
+            .collect();
+        let tree = LightTree::new(&mut lights);
+        for point in [Vector3::new(0.0, 0.0, 0.0), Vector3::new(3.0, 10.0, -2.0)] {
+            let probabilities = leaf_probabilities(&tree, point, 13);
+            assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            for _ in 0..200 {
+                let (index, p) = tree.sample(point).unwrap();
+                assert!((p - probabilities[index]).abs() < 1e-12);
+            }
+        }
+    }
+}
@@ -1,12 +1,63 @@
-use rayitolib::{ppm, renderers};
+use rayitolib::renderers::RenderSettings;
+use rayitolib::{png, ppm, renderers};
 use std::env;
 use std::fs::OpenOptions;
 use std::io;
 use std::io::prelude::*;
+use std::ops::RangeInclusive;
 
 fn main() {
     println!("Trace all the rays!");
-    let args: Vec<String> = env::args().collect();
+    let mut settings = RenderSettings::default();
+    let mut args: Vec<String> = vec![];
+    let mut eye_separation = None;
+    let mut convergence = None;
+    let mut frames = None;
+    let mut argv = env::args();
+    while let Some(arg) = argv.next() {
+        match arg.as_ref() {
+            "--spectral" => settings.spectral = true,
+            "--aovs" => settings.aovs = true,
+            "--denoise" => settings.denoise = true,
+            _ if arg.starts_with("--samples=") => {
+                settings.samples = Some(
+                    arg["--samples=".len()..]
+                        .parse()
+                        .expect("Invalid sample count"),
+                )
+            }
+            _ if arg.starts_with("--camera=") => {
+                settings.projection = arg["--camera=".len()..]
+                    .parse()
+                    .expect("Unknown camera projection")
+            }
+            _ if arg.starts_with("--stereo=") => {
+                settings.stereo = Some(
+                    arg["--stereo=".len()..]
+                        .parse()
+                        .expect("Unknown stereo layout"),
+                )
+            }
+            _ if arg.starts_with("--eye-separation=") => {
+                eye_separation = arg["--eye-separation=".len()..].parse().ok()
+            }
+            _ if arg.starts_with("--convergence=") => {
+                convergence = arg["--convergence=".len()..].parse().ok()
+            }
+            "--frames" => {
+                let range = argv.next().expect("Missing frame range after --frames");
+                frames = Some(parse_frames(&range).expect("Invalid frame range"))
+            }
+            _ if arg.starts_with("--frames=") => {
+                frames = Some(parse_frames(&arg["--frames=".len()..]).expect("Invalid frame range"))
+            }
+            _ => args.push(arg),
+        }
+    }
+    if let Some(stereo) = settings.stereo.as_mut() {
+        stereo.eye_separation = eye_separation.unwrap_or(stereo.eye_separation);
+        stereo.convergence = convergence.or(stereo.convergence);
+    }
     let renderer = &args[1];
     let width_str = args.get(2);
     let height_str = args.get(3);
@@ -14,17 +65,107 @@ fn main() {
     let width = get_uint_or(width_str, 200);
     let height = get_uint_or(height_str, 100);
 
+    if let Some(frames) = frames {
+        if renderer != "animation" {
+            panic!("Only the animation renderer has frames");
+        }
+        let animation = renderers::animation_scene();
+        let last = *frames.end();
+        for frame in frames {
+            println!("Frame {} of {}", frame, last);
+            let image =
+                renderers::draw_animation_frame(width, height, &settings, &animation, frame);
+            let path = format!("output/render_{:04}.png", frame);
+            write_to_file(&path, &png::to_png(&image)).unwrap();
+            for (aov, pass) in image.passes() {
+                let path = format!("output/render_{:04}_{}.pfm", frame, aov.name());
+                write_to_file(&path, &pass.to_pfm()).unwrap();
+            }
+        }
+        println!("Rays have been traced!");
+        return;
+    }
+
     let image = match renderer.as_ref() {
         "gradient" => renderers::draw_gradient(width, height),
-        "trio" => renderers::draw_trio(width, height),
-        "random" => renderers::draw_random(width, height),
+        "trio" => renderers::draw_trio(width, height, &settings),
+        "random" => renderers::draw_random(width, height, &settings),
+        "manylights" => {
+            renderers::draw_many_lights(width, height, &settings, args.get(4).map(String::as_str))
+        }
+        "bokeh" => renderers::draw_bokeh(
+            width,
+            height,
+            &settings,
+            get_uint_or(args.get(4), 6),
+            args.get(5).map(String::as_str),
+        )
+        .expect("Could not load aperture mask"),
+        "animation" => renderers::draw_animation_frame(
+            width,
+            height,
+            &settings,
+            &renderers::animation_scene(),
+            get_uint_or(args.get(4), 1),
+        ),
+        "lens" => renderers::draw_lens(
+            width,
+            height,
+            &settings,
+            args.get(4).map(String::as_str),
+            get_float_or(args.get(5), 2.8),
+        )
+        .expect("Could not load lens"),
+        "tiltshift" => {
+            renderers::draw_tilt_shift(width, height, &settings, get_float_or(args.get(4), 30.0))
+        }
+        "csg" => renderers::draw_csg(width, height, &settings),
+        "microfacet" => renderers::draw_microfacet(width, height, &settings),
+        "principled" => renderers::draw_principled(width, height, &settings),
+        "quadrics" => renderers::draw_quadrics(width, height, &settings),
+        "cutout" => renderers::draw_cutout(width, height, &settings),
+        "lights" => renderers::draw_lights(width, height, &settings),
+        "layered" => renderers::draw_layered(width, height, &settings),
+        "subsurface" => renderers::draw_subsurface(width, height, &settings),
+        "nested" => renderers::draw_nested(width, height, &settings),
+        "thinfilm" => renderers::draw_thin_film(width, height, &settings),
+        "dispersion" => renderers::draw_dispersion(width, height, &settings),
+        "sdf" => renderers::draw_sdf(width, height, &settings),
+        "bump" => renderers::draw_bump(width, height, &settings, args.get(4).map(String::as_str))
+            .expect("Could not load normal map"),
+        "ies" => renderers::draw_ies(width, height, &settings, args.get(4).map(String::as_str))
+            .expect("Could not load IES profile"),
+        "environment" => renderers::draw_environment(
+            width,
+            height,
+            &settings,
+            args.get(4).map(String::as_str),
+            get_float_or(args.get(5), 0.0),
+            get_float_or(args.get(6), 1.0),
+        )
+        .expect("Could not load environment map"),
+        "sky" => renderers::draw_sky(
+            width,
+            height,
+            &settings,
+            get_float_or(args.get(4), 15.0),
+            get_float_or(args.get(5), 3.0),
+        ),
+        "volume" => {
+            renderers::draw_volume(width, height, &settings, args.get(4).map(String::as_str))
+                .expect("Could not load voxel grid")
+        }
         _ => renderers::draw_blank(width, height),
     };
 
     println!("Generating Output File");
     let ppm_str = ppm::to_ppm_p3_string(&image);
     println!("Write file");
-    write_to_file("output/render.ppm", &ppm_str).unwrap();
+    write_to_file("output/render.ppm", ppm_str.as_bytes()).unwrap();
+    for (aov, pass) in image.passes() {
+        let path = format!("output/render_{}.pfm", aov.name());
+        write_to_file(&path, &pass.to_pfm()).unwrap();
+    }
     println!("Rays have been traced!");
 }
 
@@ -36,14 +177,56 @@ fn get_uint_or(number: Option<&String>, default: u32) -> u32 {
     }
 }
 
-fn write_to_file(filepath: &str, data: &String) -> Result<(), io::Error> {
+fn get_float_or(number: Option<&String>, default: f64) -> f64 {
+    let parsed_num = number.map(|num_str| num_str.parse::<f64>());
+    match parsed_num {
+        Some(Ok(num)) => num,
+        _ => default,
+    }
+}
+
+/// Parses a frame range like `1..120`, or a single frame.
+fn parse_frames(range: &str) -> Result<RangeInclusive<u32>, String> {
+    let frame = |frame: &str| {
+        frame
+            .parse::<u32>()
+            .map_err(|_| format!("{:?} is not a frame number", frame))
+    };
+    let frames = match range.split_once("..") {
+        Some((first, last)) => frame(first)?..=frame(last)?,
+        None => frame(range).map(|frame| frame..=frame)?,
+    };
+    if frames.is_empty() {
+        return Err(format!(
+            "{} is empty, the first frame comes after the last",
+            range
+        ));
+    }
+    Ok(frames)
+}
+
+fn write_to_file(filepath: &str, data: &[u8]) -> Result<(), io::Error> {
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
         .open(filepath)?;
 
-    file.write_all(data.as_bytes())?;
+    file.write_all(data)?;
     file.sync_all()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_ranges() {
+        assert_eq!(parse_frames("1..120"), Ok(1..=120));
+        assert_eq!(parse_frames("7"), Ok(7..=7));
+        assert!(parse_frames("120..1").is_err());
+        assert!(parse_frames("1..x").is_err());
+        assert!(parse_frames("").is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::aov::Lobe;
+use crate::hittables::{Evaluation, HitRecord, Material, Scatter};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rng::random_f64;
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+
+/// Isotropic GGX (Trowbridge-Reitz) distribution of microfacet normals,
+/// evaluated in a local frame where the macro surface normal is +z.
+#[derive(Copy, Clone)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    /// Uses the perceptual mapping `alpha = roughness^2`.
+    pub fn from_roughness(roughness: f64) -> Ggx {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Ggx {
+            alpha: (roughness * roughness).max(1e-4),
+        }
+    }
+
+    /// Below this the surface is treated as a perfect mirror.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < 1e-3
+    }
+
+    /// Density of microfacet normals `h`, projected onto the macro surface.
+    pub fn d(&self, h: Vector3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let cos2 = h.z() * h.z();
+        let denominator = cos2 * (a2 - 1.0) + 1.0;
+        a2 / (PI * denominator * denominator)
+    }
+
+    fn lambda(&self, w: Vector3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 >= 1.0 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2) / cos2;
+        0.5 * ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0)
+    }
+
+    /// Smith masking for a single direction.
+    pub fn g1(&self, w: Vector3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height correlated Smith masking-shadowing.
+    pub fn g2(&self, wo: Vector3, wi: Vector3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Solid angle density of reflecting `wo` off a normal `h` drawn by
+    /// `sample_visible_normal`.
+    pub fn reflection_pdf(&self, wo: Vector3, h: Vector3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * self.d(h) / (4.0 * wo.z())
+    }
+
+    /// Samples a microfacet normal from the distribution of normals visible
+    /// from `wo` (Heitz, "Sampling the GGX Distribution of Visible Normals").
+    pub fn sample_visible_normal(&self, wo: Vector3) -> Vector3 {
+        let vh = Vector3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).unit_vector();
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vector3::new(-vh.y(), vh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        let r = random_f64().sqrt();
+        let phi = 2.0 * PI * random_f64();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        Vector3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(0.0)).unit_vector()
+    }
+}
+
+/// Unpolarized Fresnel reflectance at a conductor interface with complex index
+/// of refraction `eta + i k`, per colour channel.
+pub fn fresnel_conductor(cos_i: f64, eta: Vector3, k: Vector3) -> Vector3 {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_i * cos_i;
+        let sin2 = 1.0 - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    Vector3::new(
+        channel(eta.x(), k.x()),
+        channel(eta.y(), k.y()),
+        channel(eta.z(), k.z()),
+    )
+}
+
+/// Unpolarized Fresnel reflectance at a dielectric interface, where `eta` is
+/// the ratio of the transmitted to the incident index of refraction.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+pub fn reflect(v: Vector3, n: Vector3) -> Vector3 {
+    v - 2.0 * v.dot(n) * n
+}
+
+/// Refracts `v` (pointing away from the surface) through a facet `n` on the
+/// same side, with `eta` the incident over transmitted index ratio.
+pub fn refract(v: Vector3, n: Vector3, eta: f64) -> Option<Vector3> {
+    let cos_i = v.dot(n);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i).max(0.0);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-eta * v + (eta * cos_i - cos_t) * n)
+}
+
+/// Shading frame around the side of the surface the ray arrives from, and the
+/// direction back towards the ray origin in that frame.
+pub fn local_frame(ray_in: &Ray, hit_record: &HitRecord) -> (Onb, Vector3, bool) {
+    let direction = ray_in.direction().unit_vector();
+    let entering = direction.dot(hit_record.normal) < 0.0;
+    let normal = if entering {
+        hit_record.normal
+    } else {
+        -hit_record.normal
+    };
+    let frame = Onb::from_w(normal);
+    (frame, frame.local(-direction), entering)
+}
+
+/// Rough metal with a GGX microfacet distribution and conductor Fresnel.
+pub struct RoughConductor {
+    distribution: Ggx,
+    eta: Vector3,
+    k: Vector3,
+}
+
+impl RoughConductor {
+    pub fn new(eta: Vector3, k: Vector3, roughness: f64) -> RoughConductor {
+        RoughConductor {
+            distribution: Ggx::from_roughness(roughness),
+            eta,
+            k,
+        }
+    }
+
+    pub fn gold(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            Vector3::new(0.143, 0.374, 1.442),
+            Vector3::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            Vector3::new(0.200, 0.924, 1.102),
+            Vector3::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            Vector3::new(1.657, 0.880, 0.521),
+            Vector3::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+}
+
+impl Material for RoughConductor {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        let (frame, wo, _) = local_frame(ray_in, hit_record);
+        let h = self.distribution.sample_visible_normal(wo);
+        let wi = reflect(-wo, h);
+        let scattered = Ray::new(hit_record.p, frame.world(wi));
+        if wi.z() <= 0.0 {
+            return None;
+        }
+        // With visible normal sampling D and most of G cancel against the pdf.
+        let fresnel = fresnel_conductor(wo.dot(h), self.eta, self.k);
+        let weight = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        let scatter = Scatter::new(weight * fresnel, scattered).glossy();
+        if self.distribution.is_smooth() {
+            return Some(scatter);
+        }
+        Some(scatter.with_pdf(self.distribution.reflection_pdf(wo, h)))
+    }
+
+    /// `F D G / (4 cos θo)`, the cosine towards the light cancelling.
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+        let (frame, wo, _) = local_frame(ray_in, hit_record);
+        let wi = frame.local(direction.unit_vector());
+        if wi.z() <= 0.0 || wo.z() <= 0.0 {
+            return Some(Evaluation::zero());
+        }
+        let h = (wo + wi).unit_vector();
+        let fresnel = fresnel_conductor(wo.dot(h), self.eta, self.k);
+        let value = self.distribution.d(h) * self.distribution.g2(wo, wi) / (4.0 * wo.z());
+        let pdf = self.distribution.reflection_pdf(wo, h);
+        Some(Evaluation::from(Lobe::Specular, value * fresnel, pdf))
+    }
+
+    /// Reflectance looking straight at the metal.
+    fn albedo(&self, _hit_record: &HitRecord) -> Vector3 {
+        fresnel_conductor(1.0, self.eta, self.k)
+    }
+}
+
+/// Rough glass with GGX microfacets, reflecting or transmitting according to
+/// the Fresnel term of the sampled facet.
+pub struct RoughDielectric {
+    distribution: Ggx,
+    ior: f64,
+}
+
+impl RoughDielectric {
+    pub fn new(ior: f64, roughness: f64) -> RoughDielectric {
+        RoughDielectric {
+            distribution: Ggx::from_roughness(roughness),
+            ior,
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        let (frame, wo, entering) = local_frame(ray_in, hit_record);
+        let eta = if entering { self.ior } else { 1.0 / self.ior };
+        let h = self.distribution.sample_visible_normal(wo);
+        let fresnel = fresnel_dielectric(wo.dot(h), eta);
+
+        let refracted = refract(wo, h, 1.0 / eta).filter(|_| random_f64() >= fresnel);
+        let wi = refracted.unwrap_or_else(|| reflect(-wo, h));
+        let scattered = Ray::new(hit_record.p, frame.world(wi));
+        // Reflections must stay above the surface and refractions below it.
+        if (wi.dot(h) > 0.0) != (wi.z() > 0.0) {
+            return None;
+        }
+        let weight = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        let scatter = Scatter::new(Vector3::new(weight, weight, weight), scattered);
+        // Light sampling never looks through the surface, so refraction is
+        // left unweighted.
+        if refracted.is_some() || self.distribution.is_smooth() {
+            return Some(scatter);
+        }
+        let pdf = fresnel * self.distribution.reflection_pdf(wo, h);
+        Some(scatter.with_pdf(pdf).glossy())
+    }
+
+    /// The reflection off the facets, `F D G / (4 cos θo)`, which is picked
+    /// with probability `F`. Directions through the surface are only reached
+    /// by sampling.
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+        let (frame, wo, entering) = local_frame(ray_in, hit_record);
+        let wi = frame.local(direction.unit_vector());
+        if wi.z() <= 0.0 || wo.z() <= 0.0 {
+            return Some(Evaluation::zero());
+        }
+        let eta = if entering { self.ior } else { 1.0 / self.ior };
+        let h = (wo + wi).unit_vector();
+        let fresnel = fresnel_dielectric(wo.dot(h), eta);
+        let value =
+            fresnel * self.distribution.d(h) * self.distribution.g2(wo, wi) / (4.0 * wo.z());
+        let pdf = fresnel * self.distribution.reflection_pdf(wo, h);
+        Some(Evaluation::from(
+            Lobe::Specular,
+            Vector3::new(value, value, value),
+            pdf,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::HitId;
+
+    fn hit(material: &dyn Material) -> HitRecord<'_> {
+        HitRecord {
+            t: 1.0,
+            p: Vector3::new(0.0, 0.0, 0.0),
+            normal: Vector3::new(0.0, 0.0, 1.0),
+            geometric_normal: Vector3::new(0.0, 0.0, 1.0),
+            dpdu: Vector3::new(1.0, 0.0, 0.0),
+            dpdv: Vector3::new(0.0, 1.0, 0.0),
+            u: 0.5,
+            v: 0.5,
+            material,
+            id: HitId::default(),
+        }
+    }
+
+    /// Every weighted sample must carry the density and weight that
+    /// evaluating its direction gives, or light sampling would disagree.
+    fn assert_samples_match_evaluation(material: &dyn Material) {
+        let ray = Ray::new(Vector3::new(-1.0, 0.0, 1.0), Vector3::new(1.0, 0.0, -1.0));
+        let rec = hit(material);
+        let mut weighted = 0;
+        for _ in 0..2000 {
+            let scatter = match material.scatter(&ray, &rec) {
+                Some(scatter) => scatter,
+                None => continue,
+            };
+            let pdf = match scatter.pdf {
+                Some(pdf) => pdf,
+                None => continue,
+            };
+            weighted += 1;
+            let evaluation = material
+                .evaluate(&ray, &rec, scatter.ray.direction())
+                .unwrap();
+            assert!((evaluation.pdf - pdf).abs() <= 1e-9 * pdf.max(1.0));
+            let expected = evaluation.value() / pdf;
+            assert!((expected - scatter.attenuation).length() < 1e-6);
+            assert!(evaluation.lobes[Lobe::Specular as usize].length() > 0.0);
+        }
+        assert!(weighted > 0);
+    }
+
+    #[test]
+    fn rough_conductor_samples_match_evaluation() {
+        assert_samples_match_evaluation(&RoughConductor::gold(0.4));
+    }
+
+    #[test]
+    fn rough_dielectric_reflections_match_evaluation() {
+        assert_samples_match_evaluation(&RoughDielectric::new(1.5, 0.4));
+    }
+
+    #[test]
+    fn smooth_surfaces_are_only_sampled() {
+        let rough = RoughConductor::gold(0.0);
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let rec = hit(&rough);
+        assert!(rough
+            .evaluate(&ray, &rec, Vector3::new(0.0, 0.0, 1.0))
+            .is_none());
+        assert!(rough.scatter(&ray, &rec).unwrap().pdf.is_none());
+    }
+}
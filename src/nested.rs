@@ -0,0 +1,76 @@
+use crate::hittables::Material;
+use crate::vector::Vector3;
+
+/// What a path is inside of after crossing into a dielectric. Where
+/// dielectrics overlap, the one with the highest priority owns the overlap,
+/// so a liquid can be modelled slightly larger than the glass holding it.
+#[derive(Clone, Copy)]
+pub struct Interior {
+    pub priority: u32,
+    pub ior: f64,
+    /// Beer-Lambert absorption coefficient per unit of distance.
+    pub absorption: Vector3,
+    /// Scattering coefficient per unit of distance, zero for clear media.
+    pub scattering: Vector3,
+    /// Henyey-Greenstein asymmetry of the scattering.
+    pub anisotropy: f64,
+}
+
+/// The dielectrics a path is currently inside of, following Schmidt and
+/// Budge, "Simple Nested Dielectrics in Ray Traced Images".
+#[derive(Clone, Default)]
+pub struct MediumStack<'a> {
+    entries: Vec<(&'a dyn Material, Interior)>,
+}
+
+fn same_material(a: &dyn Material, b: &dyn Material) -> bool {
+    std::ptr::eq(
+        a as *const dyn Material as *const u8,
+        b as *const dyn Material as *const u8,
+    )
+}
+
+impl<'a> MediumStack<'a> {
+    pub fn new() -> MediumStack<'a> {
+        MediumStack { entries: vec![] }
+    }
+
+    /// The medium owning the current position: highest priority, most
+    /// recently entered first.
+    fn current(&self, skip: Option<&dyn Material>) -> Option<&Interior> {
+        self.entries
+            .iter()
+            .filter(|(material, _)| skip.is_none_or(|skip| !same_material(*material, skip)))
+            .max_by_key(|(_, interior)| interior.priority)
+            .map(|(_, interior)| interior)
+    }
+
+    /// The medium the path is travelling through, if any.
+    pub fn medium(&self) -> Option<Interior> {
+        self.current(None).copied()
+    }
+
+    /// Index of refraction on the far side of a boundary of `material`, or
+    /// `None` when the boundary lies inside a higher priority medium and
+    /// should be passed through untouched.
+    pub fn exterior_ior(&self, material: &dyn Material, interior: &Interior) -> Option<f64> {
+        match self.current(Some(material)) {
+            Some(outside) if outside.priority > interior.priority => None,
+            Some(outside) => Some(outside.ior),
+            None => Some(1.0),
+        }
+    }
+
+    /// Records the path crossing a boundary of `material`.
+    pub fn cross(&mut self, material: &'a dyn Material, interior: Interior, entering: bool) {
+        if entering {
+            self.entries.push((material, interior));
+        } else if let Some(index) = self
+            .entries
+            .iter()
+            .rposition(|(entry, _)| same_material(*entry, material))
+        {
+            self.entries.remove(index);
+        }
+    }
+}
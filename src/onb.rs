@@ -0,0 +1,34 @@
+use crate::vector::Vector3;
+
+/// Orthonormal basis with `w` as its local z axis.
+#[derive(Copy, Clone)]
+pub struct Onb {
+    u: Vector3,
+    v: Vector3,
+    w: Vector3,
+}
+
+impl Onb {
+    /// Builds a basis around a unit vector (Duff et al., "Building an
+    /// Orthonormal Basis, Revisited").
+    pub fn from_w(w: Vector3) -> Onb {
+        let sign = 1.0_f64.copysign(w.z());
+        let a = -1.0 / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        Onb {
+            u: Vector3::new(1.0 + sign * w.x() * w.x() * a, sign * b, -sign * w.x()),
+            v: Vector3::new(b, sign + w.y() * w.y() * a, -w.y()),
+            w,
+        }
+    }
+
+    /// Expresses a world space vector in this basis.
+    pub fn local(&self, a: Vector3) -> Vector3 {
+        Vector3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+
+    /// Maps a vector expressed in this basis back to world space.
+    pub fn world(&self, a: Vector3) -> Vector3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+}
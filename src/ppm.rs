@@ -1,4 +1,8 @@
 use crate::image;
+use crate::vector::Vector3;
+use std::fs;
+use std::io;
+use std::path::Path;
 
 fn header(width: u32, height: u32) -> String {
     format!("P3 {} {} 255", height.to_string(), width.to_string())
@@ -28,3 +32,86 @@ pub fn to_ppm_p3_string(image: &image::Image) -> String {
         pixelmap_to_string(image)
     )
 }
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Reads the next whitespace separated header token, skipping comments.
+/// Leaves `position` on the single whitespace byte that ends the token.
+fn next_token<'a>(bytes: &'a [u8], position: &mut usize) -> Result<&'a str, io::Error> {
+    loop {
+        match bytes.get(*position) {
+            Some(b'#') => {
+                while bytes.get(*position).is_some_and(|&b| b != b'\n') {
+                    *position += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *position += 1,
+            Some(_) => break,
+            None => return Err(invalid_data("truncated ppm header")),
+        }
+    }
+    let start = *position;
+    while bytes
+        .get(*position)
+        .is_some_and(|b| !b.is_ascii_whitespace())
+    {
+        *position += 1;
+    }
+    std::str::from_utf8(&bytes[start..*position])
+        .map_err(|_| invalid_data("ppm header is not text"))
+}
+
+fn next_number(bytes: &[u8], position: &mut usize) -> Result<u32, io::Error> {
+    next_token(bytes, position)?
+        .parse()
+        .map_err(|_| invalid_data("invalid number in ppm file"))
+}
+
+/// Loads a plain (`P3`) or binary (`P6`) ppm file, returning its width,
+/// height and colours in `[0, 1]` row by row from the top.
+pub fn read_ppm(path: &Path) -> Result<(u32, u32, Vec<Vector3>), io::Error> {
+    let bytes = fs::read(path)?;
+    let mut position = 0;
+    let binary = match next_token(&bytes, &mut position)? {
+        "P3" => false,
+        "P6" => true,
+        _ => return Err(invalid_data("only P3 and P6 ppm files are supported")),
+    };
+    let width = next_number(&bytes, &mut position)?;
+    let height = next_number(&bytes, &mut position)?;
+    let max_value = next_number(&bytes, &mut position)?;
+    if max_value == 0 || max_value > 255 {
+        return Err(invalid_data("only 8 bit ppm files are supported"));
+    }
+
+    if width == 0 || height == 0 {
+        return Err(invalid_data("ppm image has no pixels"));
+    }
+    // Every sample takes at least a byte, which also bounds the size.
+    let count = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .filter(|&count| count < bytes.len())
+        .ok_or_else(|| invalid_data("ppm payload does not match its header"))?;
+    let samples: Vec<u32> = if binary {
+        let start = position + 1;
+        let payload = bytes
+            .get(start..)
+            .and_then(|rest| rest.get(..count))
+            .ok_or_else(|| invalid_data("ppm payload does not match its header"))?;
+        payload.iter().map(|&b| b as u32).collect()
+    } else {
+        (0..count)
+            .map(|_| next_number(&bytes, &mut position))
+            .collect::<Result<_, _>>()?
+    };
+
+    let scale = 1.0 / max_value as f64;
+    let colors = samples
+        .chunks_exact(3)
+        .map(|c| Vector3::new(c[0] as f64, c[1] as f64, c[2] as f64) * scale)
+        .collect();
+    Ok((width, height, colors))
+}
@@ -0,0 +1,352 @@
+use crate::aov;
+use crate::hittables::{Evaluation, HitRecord, Material, Scatter};
+use crate::microfacet::{fresnel_dielectric, local_frame, reflect, refract, Ggx};
+use crate::ray::Ray;
+use crate::rng::{random_cosine_direction, random_f64};
+use crate::textures::{ConstantTexture, Texture};
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+
+fn schlick_weight(cosine: f64) -> f64 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+fn schlick(f0: Vector3, cosine: f64) -> Vector3 {
+    f0 + schlick_weight(cosine) * (Vector3::new(1.0, 1.0, 1.0) - f0)
+}
+
+fn mix(a: Vector3, b: Vector3, t: f64) -> Vector3 {
+    (1.0 - t) * a + t * b
+}
+
+enum Lobe {
+    Diffuse,
+    Sheen,
+    Specular,
+    Clearcoat,
+    Transmission,
+}
+
+impl Lobe {
+    /// Pass the light scattered through the lobe is rendered to.
+    fn pass(&self) -> aov::Lobe {
+        match self {
+            Lobe::Diffuse | Lobe::Sheen => aov::Lobe::Diffuse,
+            Lobe::Specular | Lobe::Clearcoat => aov::Lobe::Specular,
+            Lobe::Transmission => aov::Lobe::Transmission,
+        }
+    }
+}
+
+/// Disney style principled BSDF combining diffuse, specular, clearcoat, sheen
+/// and transmission lobes. One lobe is picked per scatter in proportion to its
+/// approximate albedo and the result is divided by that probability.
+pub struct Principled {
+    base_color: Box<dyn Texture>,
+    metallic: Box<dyn Texture>,
+    roughness: Box<dyn Texture>,
+    specular: f64,
+    clearcoat: f64,
+    clearcoat_roughness: f64,
+    sheen: f64,
+    sheen_tint: f64,
+    transmission: f64,
+    ior: f64,
+}
+
+impl Principled {
+    /// Rough dielectric of the given colour; every other lobe starts off.
+    pub fn new(base_color: Vector3) -> Principled {
+        Principled {
+            base_color: Box::new(ConstantTexture::new(base_color)),
+            metallic: Box::new(ConstantTexture::scalar(0.0)),
+            roughness: Box::new(ConstantTexture::scalar(0.5)),
+            specular: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+
+    pub fn with_base_color_texture<T: Texture + 'static>(mut self, texture: T) -> Principled {
+        self.base_color = Box::new(texture);
+        self
+    }
+
+    pub fn with_metallic(self, metallic: f64) -> Principled {
+        self.with_metallic_texture(ConstantTexture::scalar(metallic))
+    }
+
+    pub fn with_metallic_texture<T: Texture + 'static>(mut self, texture: T) -> Principled {
+        self.metallic = Box::new(texture);
+        self
+    }
+
+    pub fn with_roughness(self, roughness: f64) -> Principled {
+        self.with_roughness_texture(ConstantTexture::scalar(roughness))
+    }
+
+    pub fn with_roughness_texture<T: Texture + 'static>(mut self, texture: T) -> Principled {
+        self.roughness = Box::new(texture);
+        self
+    }
+
+    /// Dielectric reflectance at normal incidence is `0.08 * specular`, so
+    /// the default of 0.5 gives the usual 4%.
+    pub fn with_specular(mut self, specular: f64) -> Principled {
+        self.specular = specular;
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: f64, roughness: f64) -> Principled {
+        self.clearcoat = clearcoat;
+        self.clearcoat_roughness = roughness;
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: f64, tint: f64) -> Principled {
+        self.sheen = sheen;
+        self.sheen_tint = tint;
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: f64, ior: f64) -> Principled {
+        self.transmission = transmission;
+        self.ior = ior;
+        self
+    }
+
+    fn pick_lobe(&self, weights: &[(Lobe, f64)]) -> Option<(usize, f64)> {
+        let total: f64 = weights.iter().map(|(_, w)| w).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut target = random_f64() * total;
+        for (i, (_, w)) in weights.iter().enumerate() {
+            if target < *w || i == weights.len() - 1 {
+                return Some((i, w / total));
+            }
+            target -= w;
+        }
+        None
+    }
+}
+
+/// What the lobes share at one hit, seen from the outgoing direction.
+struct Shading {
+    base: Vector3,
+    metallic: f64,
+    roughness: f64,
+    distribution: Ggx,
+    f0: Vector3,
+    under: f64,
+    dielectric: f64,
+    sheen_color: Vector3,
+    /// Each lobe with the approximate albedo it is picked by.
+    weights: [(Lobe, f64); 5],
+}
+
+impl Principled {
+    fn shading(&self, hit_record: &HitRecord, wo: Vector3) -> Shading {
+        let (u, v, p) = (hit_record.u, hit_record.v, hit_record.p);
+        let base = self.base_color.value(u, v, p);
+        let metallic = self.metallic.value(u, v, p).x().clamp(0.0, 1.0);
+        let roughness = self.roughness.value(u, v, p).x().clamp(0.0, 1.0);
+
+        let cos_o = wo.z();
+        let f0_dielectric = 0.08 * self.specular;
+        let f0 = mix(Vector3::new(1.0, 1.0, 1.0) * f0_dielectric, base, metallic);
+        let dielectric_fresnel = f0_dielectric + (1.0 - f0_dielectric) * schlick_weight(cos_o);
+        let coat = self.clearcoat * (0.04 + 0.96 * schlick_weight(cos_o));
+        let under = 1.0 - coat;
+        let dielectric = under * (1.0 - metallic) * (1.0 - dielectric_fresnel);
+        let tint = if base.luminance() > 0.0 {
+            base / base.luminance()
+        } else {
+            Vector3::new(1.0, 1.0, 1.0)
+        };
+        let sheen_color = self.sheen * mix(Vector3::new(1.0, 1.0, 1.0), tint, self.sheen_tint);
+
+        let weights = [
+            (
+                Lobe::Diffuse,
+                dielectric * (1.0 - self.transmission) * base.luminance(),
+            ),
+            (
+                Lobe::Sheen,
+                0.1 * under * (1.0 - metallic) * sheen_color.luminance(),
+            ),
+            (Lobe::Specular, under * schlick(f0, cos_o).luminance()),
+            (Lobe::Clearcoat, coat),
+            (
+                Lobe::Transmission,
+                dielectric * self.transmission * base.luminance(),
+            ),
+        ];
+        Shading {
+            base,
+            metallic,
+            roughness,
+            distribution: Ggx::from_roughness(roughness),
+            f0,
+            under,
+            dielectric,
+            sheen_color,
+            weights,
+        }
+    }
+
+    /// Scattering function times cosine over the density of sampling `wi`
+    /// from a reflection lobe, and that density.
+    fn reflection(
+        &self,
+        shading: &Shading,
+        lobe: &Lobe,
+        wo: Vector3,
+        wi: Vector3,
+    ) -> (Vector3, f64) {
+        let h = (wi + wo).unit_vector();
+        match lobe {
+            Lobe::Diffuse => {
+                let cos_d = wi.dot(h);
+                let fd90 = 0.5 + 2.0 * shading.roughness * cos_d * cos_d;
+                let fd = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z()))
+                    * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z()));
+                let weight = shading.dielectric * (1.0 - self.transmission) * fd * shading.base;
+                (weight, wi.z() / PI)
+            }
+            Lobe::Sheen => {
+                let sheen = PI * schlick_weight(wi.dot(h));
+                let weight = shading.under * (1.0 - shading.metallic) * sheen * shading.sheen_color;
+                (weight, wi.z() / PI)
+            }
+            Lobe::Specular => {
+                let distribution = shading.distribution;
+                let g = distribution.g2(wo, wi) / distribution.g1(wo);
+                let pdf = distribution.g1(wo) * distribution.d(h) / (4.0 * wo.z());
+                (shading.under * g * schlick(shading.f0, wo.dot(h)), pdf)
+            }
+            Lobe::Clearcoat => {
+                let distribution = Ggx::from_roughness(self.clearcoat_roughness);
+                let g = distribution.g2(wo, wi) / distribution.g1(wo);
+                let pdf = distribution.g1(wo) * distribution.d(h) / (4.0 * wo.z());
+                let fresnel = 0.04 + 0.96 * schlick_weight(wo.dot(h));
+                let weight = self.clearcoat * g * fresnel;
+                (Vector3::new(weight, weight, weight), pdf)
+            }
+            Lobe::Transmission => (Vector3::new(0.0, 0.0, 0.0), 0.0),
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        let p = hit_record.p;
+        let (frame, wo, entering) = local_frame(ray_in, hit_record);
+
+        // Leaving a transmissive object only the glass interface matters.
+        if !entering && self.transmission > 0.0 {
+            let roughness = self
+                .roughness
+                .value(hit_record.u, hit_record.v, p)
+                .x()
+                .clamp(0.0, 1.0);
+            let distribution = Ggx::from_roughness(roughness);
+            let eta = 1.0 / self.ior;
+            let h = distribution.sample_visible_normal(wo);
+            let wi = match refract(wo, h, 1.0 / eta) {
+                Some(refracted) if random_f64() >= fresnel_dielectric(wo.dot(h), eta) => refracted,
+                _ => reflect(-wo, h),
+            };
+            if (wi.dot(h) > 0.0) != (wi.z() > 0.0) {
+                return None;
+            }
+            let weight = distribution.g2(wo, wi) / distribution.g1(wo);
+            return Some(Scatter::new(
+                Vector3::new(weight, weight, weight),
+                Ray::new(p, frame.world(wi)),
+            ));
+        }
+
+        let shading = self.shading(hit_record, wo);
+        let (index, probability) = self.pick_lobe(&shading.weights)?;
+        let lobe = &shading.weights[index].0;
+
+        let wi = match lobe {
+            Lobe::Diffuse | Lobe::Sheen => random_cosine_direction(),
+            Lobe::Specular => reflect(-wo, shading.distribution.sample_visible_normal(wo)),
+            Lobe::Clearcoat => {
+                let distribution = Ggx::from_roughness(self.clearcoat_roughness);
+                reflect(-wo, distribution.sample_visible_normal(wo))
+            }
+            Lobe::Transmission => {
+                let distribution = shading.distribution;
+                let h = distribution.sample_visible_normal(wo);
+                let wi = match refract(wo, h, 1.0 / self.ior) {
+                    Some(refracted) if refracted.z() < 0.0 => refracted,
+                    _ => return None,
+                };
+                let g = distribution.g2(wo, wi) / distribution.g1(wo);
+                let fresnel = fresnel_dielectric(wo.dot(h), self.ior);
+                // The specular lobe already accounts for the reflected part.
+                let scale =
+                    shading.under * (1.0 - shading.metallic) * self.transmission * (1.0 - fresnel);
+                // Light sampling never looks through the surface, so this is
+                // left unweighted.
+                return Some(Scatter::new(
+                    scale * g * shading.base / probability,
+                    Ray::new(p, frame.world(wi)),
+                ));
+            }
+        };
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        let (weight, _) = self.reflection(&shading, lobe, wo, wi);
+        let scattered = Ray::new(p, frame.world(wi));
+        let mut scatter = Scatter::new(weight / probability, scattered);
+        if let Lobe::Specular | Lobe::Clearcoat = lobe {
+            scatter = scatter.glossy();
+        }
+        match self.evaluate(ray_in, hit_record, frame.world(wi)) {
+            Some(evaluation) => Some(scatter.with_pdf(evaluation.pdf)),
+            None => Some(scatter),
+        }
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vector3 {
+        self.base_color
+            .value(hit_record.u, hit_record.v, hit_record.p)
+    }
+
+    /// Sums the reflection lobes, each being what `scatter` returns for the
+    /// lobe times the density it samples with.
+    fn evaluate(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: Vector3,
+    ) -> Option<Evaluation> {
+        let (frame, wo, entering) = local_frame(ray_in, hit_record);
+        if !entering && self.transmission > 0.0 {
+            return None;
+        }
+        let shading = self.shading(hit_record, wo);
+        let total: f64 = shading.weights.iter().map(|(_, w)| w).sum();
+        let wi = frame.local(direction.unit_vector());
+        let mut evaluation = Evaluation::zero();
+        if total <= 0.0 || wi.z() <= 0.0 {
+            return Some(evaluation);
+        }
+        for (lobe, weight) in shading.weights.iter() {
+            let (lobe_weight, pdf) = self.reflection(&shading, lobe, wo, wi);
+            evaluation.lobes[lobe.pass() as usize] += pdf * lobe_weight;
+            evaluation.pdf += weight / total * pdf;
+        }
+        Some(evaluation)
+    }
+}
@@ -0,0 +1,741 @@
+use crate::aabb::Aabb;
+use crate::hittables::{HitId, HitRecord, Hittable, Interval, Material, Solid};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+
+/// Position, orientation and material shared by the shapes below. Each shape
+/// is intersected in a local frame with its axis along z.
+struct Placement {
+    origin: Vector3,
+    frame: Onb,
+    material: Box<dyn Material>,
+}
+
+impl Placement {
+    fn new(origin: Vector3, axis: Vector3, material: Box<dyn Material>) -> Placement {
+        Placement {
+            origin,
+            frame: Onb::from_w(axis.unit_vector()),
+            material,
+        }
+    }
+
+    fn local_ray(&self, ray: &Ray) -> (Vector3, Vector3) {
+        (
+            self.frame.local(ray.origin() - self.origin),
+            self.frame.local(ray.direction()),
+        )
+    }
+
+    fn record(&self, ray: &Ray, hit: LocalHit) -> HitRecord<'_> {
+        let normal = self.frame.world(hit.normal).unit_vector();
+        HitRecord {
+            t: hit.t,
+            p: ray.point_at(hit.t),
+            normal,
+            geometric_normal: normal,
+            dpdu: self.frame.world(hit.dpdu),
+            dpdv: self.frame.world(hit.dpdv),
+            u: hit.u,
+            v: hit.v,
+            material: &*self.material,
+            id: HitId::default(),
+        }
+    }
+
+    fn bounding_box(&self, local: Aabb) -> Aabb {
+        local.transformed(|p| self.origin + self.frame.world(p))
+    }
+
+    /// Nearest hit within range out of the shape's sorted local hits.
+    fn hit(&self, ray: &Ray, hits: Vec<LocalHit>, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hits.into_iter()
+            .find(|hit| hit.t > t_min && hit.t < t_max)
+            .map(|hit| self.record(ray, hit))
+    }
+
+    /// Like `hit`, for open surfaces without an inside, whose normal is
+    /// turned to face the ray so that either side shades like the front.
+    fn facing_hit(
+        &self,
+        ray: &Ray,
+        hits: Vec<LocalHit>,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord<'_>> {
+        let (_, d) = self.local_ray(ray);
+        let hits = hits
+            .into_iter()
+            .map(|mut hit| {
+                if hit.normal.dot(d) > 0.0 {
+                    hit.normal = -hit.normal;
+                }
+                hit
+            })
+            .collect();
+        self.hit(ray, hits, t_min, t_max)
+    }
+
+    /// Pairs up the sorted hits of a closed shape into inside intervals, by
+    /// whether the ray enters or leaves through each. Hits that do not change
+    /// sides, like repeated roots or grazing ones, are skipped.
+    fn intervals(&self, ray: &Ray, hits: Vec<LocalHit>) -> Vec<Interval<'_>> {
+        let (_, d) = self.local_ray(ray);
+        let mut intervals = vec![];
+        let mut enter = None;
+        for hit in hits {
+            let entering = hit.normal.dot(d) < 0.0;
+            match enter {
+                None if entering => enter = Some(hit),
+                Some(first) if !entering => {
+                    intervals.push(Interval {
+                        enter: self.record(ray, first),
+                        exit: self.record(ray, hit),
+                    });
+                    enter = None;
+                }
+                _ => {}
+            }
+        }
+        intervals
+    }
+}
+
+#[derive(Copy, Clone)]
+struct LocalHit {
+    t: f64,
+    normal: Vector3,
+    u: f64,
+    v: f64,
+    dpdu: Vector3,
+    dpdv: Vector3,
+}
+
+/// Hits in order along the ray, dropping any a degenerate solve left
+/// without a distance.
+fn sorted(mut hits: Vec<LocalHit>) -> Vec<LocalHit> {
+    hits.retain(|hit| hit.t.is_finite());
+    hits.sort_by(|h1, h2| h1.t.total_cmp(&h2.t));
+    hits
+}
+
+fn azimuth(x: f64, y: f64) -> f64 {
+    let phi = y.atan2(x);
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+/// Derivative of a point with respect to `u = azimuth / 2 pi`.
+fn around_axis(x: f64, y: f64) -> Vector3 {
+    2.0 * PI * Vector3::new(-y, x, 0.0)
+}
+
+/// Unit vector pointing away from the axis, any direction on the axis itself.
+fn radial(x: f64, y: f64) -> Vector3 {
+    let rho = (x * x + y * y).sqrt();
+    if rho > 0.0 {
+        Vector3::new(x / rho, y / rho, 0.0)
+    } else {
+        Vector3::new(1.0, 0.0, 0.0)
+    }
+}
+
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return if b.abs() < 1e-12 {
+            vec![]
+        } else {
+            vec![-c / b]
+        };
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    // Numerically stable form that avoids cancelling b against the root.
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return vec![0.0];
+    }
+    let (r0, r1) = (q / a, c / q);
+    if r0 < r1 {
+        vec![r0, r1]
+    } else {
+        vec![r1, r0]
+    }
+}
+
+/// Largest real root of `x^3 + a x^2 + b x + c`.
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    if r * r < q * q * q {
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        -2.0 * q.sqrt() * ((theta + 2.0 * PI) / 3.0).cos() - a / 3.0
+    } else {
+        let big_a = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let big_b = if big_a != 0.0 { q / big_a } else { 0.0 };
+        big_a + big_b - a / 3.0
+    }
+}
+
+/// Real roots of `c4 t^4 + c3 t^3 + c2 t^2 + c1 t + c0` via Ferrari's method,
+/// polished with a few Newton steps.
+fn solve_quartic(c4: f64, c3: f64, c2: f64, c1: f64, c0: f64) -> Vec<f64> {
+    let (a, b, c, d) = (c3 / c4, c2 / c4, c1 / c4, c0 / c4);
+    // Depressed quartic y^4 + p y^2 + q y + r with t = y - a / 4.
+    let p = b - 3.0 * a * a / 8.0;
+    let q = c - a * b / 2.0 + a * a * a / 8.0;
+    let r = d - a * c / 4.0 + a * a * b / 16.0 - 3.0 * a * a * a * a / 256.0;
+
+    let mut ys = vec![];
+    if q.abs() < 1e-12 {
+        for z in solve_quadratic(1.0, p, r) {
+            if z >= 0.0 {
+                ys.push(z.sqrt());
+                ys.push(-z.sqrt());
+            }
+        }
+    } else {
+        // Resolvent cubic 8m^3 + 8p m^2 + (2p^2 - 8r) m - q^2 = 0.
+        let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m <= 0.0 {
+            return vec![];
+        }
+        let s = (2.0 * m).sqrt();
+        ys.extend(solve_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s)));
+        ys.extend(solve_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s)));
+    }
+
+    let mut roots: Vec<f64> = ys
+        .into_iter()
+        .map(|y| {
+            let mut t = y - a / 4.0;
+            for _ in 0..2 {
+                let f = (((c4 * t + c3) * t + c2) * t + c1) * t + c0;
+                let df = ((4.0 * c4 * t + 3.0 * c3) * t + 2.0 * c2) * t + c1;
+                if df != 0.0 {
+                    t -= f / df;
+                }
+            }
+            t
+        })
+        .filter(|t: &f64| t.is_finite())
+        .collect();
+    roots.sort_by(|r1, r2| r1.total_cmp(r2));
+    roots
+}
+
+/// Hit on the plane `z = height` within `inner..outer` of the axis.
+fn disk_hit(
+    o: Vector3,
+    d: Vector3,
+    height: f64,
+    inner: f64,
+    outer: f64,
+    normal_z: f64,
+) -> Option<LocalHit> {
+    if d.z() == 0.0 {
+        return None;
+    }
+    let t = (height - o.z()) / d.z();
+    let (x, y) = (o.x() + t * d.x(), o.y() + t * d.y());
+    let rho = (x * x + y * y).sqrt();
+    if rho > outer || rho < inner {
+        return None;
+    }
+    Some(LocalHit {
+        t,
+        normal: Vector3::new(0.0, 0.0, normal_z),
+        u: azimuth(x, y) / (2.0 * PI),
+        v: (outer - rho) / (outer - inner),
+        dpdu: around_axis(x, y),
+        dpdv: -(outer - inner) * radial(x, y),
+    })
+}
+
+/// Cylinder of the given radius extending `height` along `axis` from
+/// `base`, closed by disks at both ends.
+pub struct Cylinder {
+    placement: Placement,
+    radius: f64,
+    height: f64,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Vector3,
+        axis: Vector3,
+        radius: f64,
+        height: f64,
+        material: Box<dyn Material>,
+    ) -> Cylinder {
+        Cylinder {
+            placement: Placement::new(base, axis, material),
+            radius,
+            height,
+        }
+    }
+
+    fn local_hits(&self, ray: &Ray) -> Vec<LocalHit> {
+        let (o, d) = self.placement.local_ray(ray);
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+        let mut hits = vec![];
+        for t in solve_quadratic(a, b, c) {
+            let p = o + t * d;
+            if p.z() >= 0.0 && p.z() <= self.height {
+                hits.push(LocalHit {
+                    t,
+                    normal: Vector3::new(p.x(), p.y(), 0.0) / self.radius,
+                    u: azimuth(p.x(), p.y()) / (2.0 * PI),
+                    v: p.z() / self.height,
+                    dpdu: around_axis(p.x(), p.y()),
+                    dpdv: Vector3::new(0.0, 0.0, self.height),
+                });
+            }
+        }
+        hits.extend(disk_hit(o, d, 0.0, 0.0, self.radius, -1.0));
+        hits.extend(disk_hit(o, d, self.height, 0.0, self.radius, 1.0));
+        sorted(hits)
+    }
+}
+
+unsafe impl Send for Cylinder {}
+unsafe impl Sync for Cylinder {}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.placement.hit(ray, self.local_hits(ray), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.placement.bounding_box(Aabb::new(
+            Vector3::new(-r, -r, 0.0),
+            Vector3::new(r, r, self.height),
+        )))
+    }
+}
+
+impl Solid for Cylinder {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        self.placement.intervals(ray, self.local_hits(ray))
+    }
+}
+
+/// Cone with its base disk of the given radius at `base` and its apex
+/// `height` along `axis`.
+pub struct Cone {
+    placement: Placement,
+    radius: f64,
+    height: f64,
+}
+
+impl Cone {
+    pub fn new(
+        base: Vector3,
+        axis: Vector3,
+        radius: f64,
+        height: f64,
+        material: Box<dyn Material>,
+    ) -> Cone {
+        Cone {
+            placement: Placement::new(base, axis, material),
+            radius,
+            height,
+        }
+    }
+
+    fn local_hits(&self, ray: &Ray) -> Vec<LocalHit> {
+        let (o, d) = self.placement.local_ray(ray);
+        let k = (self.radius / self.height) * (self.radius / self.height);
+        let h = self.height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k * d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y() + k * h * d.z());
+        let c = o.x() * o.x() + o.y() * o.y() - k * h * h;
+        let mut hits = vec![];
+        for t in solve_quadratic(a, b, c) {
+            let p = o + t * d;
+            if p.z() >= 0.0 && p.z() <= self.height {
+                hits.push(LocalHit {
+                    t,
+                    normal: Vector3::new(p.x(), p.y(), k * (self.height - p.z())).unit_vector(),
+                    u: azimuth(p.x(), p.y()) / (2.0 * PI),
+                    v: p.z() / self.height,
+                    dpdu: around_axis(p.x(), p.y()),
+                    dpdv: Vector3::new(0.0, 0.0, self.height) - self.radius * radial(p.x(), p.y()),
+                });
+            }
+        }
+        hits.extend(disk_hit(o, d, 0.0, 0.0, self.radius, -1.0));
+        sorted(hits)
+    }
+}
+
+unsafe impl Send for Cone {}
+unsafe impl Sync for Cone {}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.placement.hit(ray, self.local_hits(ray), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.placement.bounding_box(Aabb::new(
+            Vector3::new(-r, -r, 0.0),
+            Vector3::new(r, r, self.height),
+        )))
+    }
+}
+
+impl Solid for Cone {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        self.placement.intervals(ray, self.local_hits(ray))
+    }
+}
+
+/// Flat disk facing `normal`. With a non zero inner radius it is an annulus.
+pub struct Disk {
+    placement: Placement,
+    inner_radius: f64,
+    radius: f64,
+}
+
+impl Disk {
+    pub fn new(center: Vector3, normal: Vector3, radius: f64, material: Box<dyn Material>) -> Disk {
+        Disk::annulus(center, normal, 0.0, radius, material)
+    }
+
+    pub fn annulus(
+        center: Vector3,
+        normal: Vector3,
+        inner_radius: f64,
+        radius: f64,
+        material: Box<dyn Material>,
+    ) -> Disk {
+        Disk {
+            placement: Placement::new(center, normal, material),
+            inner_radius,
+            radius,
+        }
+    }
+}
+
+unsafe impl Send for Disk {}
+unsafe impl Sync for Disk {}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = self.placement.local_ray(ray);
+        let hit = disk_hit(o, d, 0.0, self.inner_radius, self.radius, 1.0);
+        self.placement
+            .facing_hit(ray, hit.into_iter().collect(), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.placement.bounding_box(Aabb::new(
+            Vector3::new(-r, -r, -1e-4),
+            Vector3::new(r, r, 1e-4),
+        )))
+    }
+}
+
+/// Torus around `axis`; `major_radius` is the distance from the center to the
+/// middle of the tube and `minor_radius` the radius of the tube.
+pub struct Torus {
+    placement: Placement,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vector3,
+        axis: Vector3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Box<dyn Material>,
+    ) -> Torus {
+        Torus {
+            placement: Placement::new(center, axis, material),
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    fn local_hits(&self, ray: &Ray) -> Vec<LocalHit> {
+        let (o, d) = self.placement.local_ray(ray);
+        // Solve with a unit direction to keep the quartic well conditioned.
+        let length = d.length();
+        let d = d / length;
+        let big_r2 = self.major_radius * self.major_radius;
+        let small_r2 = self.minor_radius * self.minor_radius;
+        let e = o.dot(o) - big_r2 - small_r2;
+        let f = o.dot(d);
+        let four_r2 = 4.0 * big_r2;
+        let roots = solve_quartic(
+            1.0,
+            4.0 * f,
+            2.0 * e + 4.0 * f * f + four_r2 * d.z() * d.z(),
+            4.0 * f * e + 2.0 * four_r2 * o.z() * d.z(),
+            e * e - four_r2 * (small_r2 - o.z() * o.z()),
+        );
+
+        let hits = roots
+            .into_iter()
+            .map(|t| {
+                let p = o + t * d;
+                let sum = p.dot(p);
+                let normal = Vector3::new(
+                    p.x() * (sum - big_r2 - small_r2),
+                    p.y() * (sum - big_r2 - small_r2),
+                    p.z() * (sum + big_r2 - small_r2),
+                );
+                let ring = (p.x() * p.x() + p.y() * p.y()).sqrt() - self.major_radius;
+                let around_tube = -p.z() * radial(p.x(), p.y()) + Vector3::new(0.0, 0.0, ring);
+                LocalHit {
+                    t: t / length,
+                    normal: normal.unit_vector(),
+                    u: azimuth(p.x(), p.y()) / (2.0 * PI),
+                    v: azimuth(ring, p.z()) / (2.0 * PI),
+                    dpdu: around_axis(p.x(), p.y()),
+                    dpdv: 2.0 * PI * around_tube,
+                }
+            })
+            .collect();
+        sorted(hits)
+    }
+}
+
+unsafe impl Send for Torus {}
+unsafe impl Sync for Torus {}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.placement.hit(ray, self.local_hits(ray), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.major_radius + self.minor_radius;
+        let h = self.minor_radius;
+        Some(
+            self.placement
+                .bounding_box(Aabb::new(Vector3::new(-r, -r, -h), Vector3::new(r, r, h))),
+        )
+    }
+}
+
+impl Solid for Torus {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        self.placement.intervals(ray, self.local_hits(ray))
+    }
+}
+
+/// Open paraboloid with its vertex at `vertex`, opening along `axis` and
+/// reaching `radius` at `height`.
+pub struct Paraboloid {
+    placement: Placement,
+    radius: f64,
+    height: f64,
+}
+
+impl Paraboloid {
+    pub fn new(
+        vertex: Vector3,
+        axis: Vector3,
+        radius: f64,
+        height: f64,
+        material: Box<dyn Material>,
+    ) -> Paraboloid {
+        Paraboloid {
+            placement: Placement::new(vertex, axis, material),
+            radius,
+            height,
+        }
+    }
+}
+
+unsafe impl Send for Paraboloid {}
+unsafe impl Sync for Paraboloid {}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = self.placement.local_ray(ray);
+        let k = self.height / (self.radius * self.radius);
+        let a = k * (d.x() * d.x() + d.y() * d.y());
+        let b = 2.0 * k * (o.x() * d.x() + o.y() * d.y()) - d.z();
+        let c = k * (o.x() * o.x() + o.y() * o.y()) - o.z();
+        let hits = solve_quadratic(a, b, c)
+            .into_iter()
+            .filter_map(|t| {
+                let p = o + t * d;
+                if p.z() < 0.0 || p.z() > self.height {
+                    return None;
+                }
+                Some(LocalHit {
+                    t,
+                    normal: Vector3::new(2.0 * k * p.x(), 2.0 * k * p.y(), -1.0).unit_vector(),
+                    u: azimuth(p.x(), p.y()) / (2.0 * PI),
+                    v: p.z() / self.height,
+                    dpdu: around_axis(p.x(), p.y()),
+                    // Along the surface, a step of dz moves dz / (2 k rho) outwards.
+                    dpdv: self.height
+                        * (radial(p.x(), p.y()) / (2.0 * k * p.x().hypot(p.y()).max(1e-6))
+                            + Vector3::new(0.0, 0.0, 1.0)),
+                })
+            })
+            .collect();
+        self.placement.facing_hit(ray, hits, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.placement.bounding_box(Aabb::new(
+            Vector3::new(-r, -r, 0.0),
+            Vector3::new(r, r, self.height),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::Lambertian;
+
+    fn assert_roots(roots: &[f64], expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "roots {:?}", roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-6, "roots {:?}", roots);
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(&solve_quadratic(1.0, -4.0, 3.0), &[1.0, 3.0]);
+        assert_roots(&solve_quadratic(-2.0, 0.0, 8.0), &[-2.0, 2.0]);
+        assert_roots(&solve_quadratic(0.0, 2.0, -1.0), &[0.5]);
+        assert_roots(&solve_quadratic(1.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn largest_cubic_root_of_known_cubics() {
+        // (x - 1)(x - 2)(x - 3)
+        assert!((largest_cubic_root(-6.0, 11.0, -6.0) - 3.0).abs() < 1e-9);
+        // x^3 - 8, whose other roots are complex.
+        assert!((largest_cubic_root(0.0, 0.0, -8.0) - 2.0).abs() < 1e-9);
+        // (x + 1)^2 (x - 0.5)
+        assert!((largest_cubic_root(1.5, 0.0, -0.5) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        assert_roots(
+            &solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // 2 (t^2 - 1)(t^2 - 4), with no odd terms.
+        assert_roots(
+            &solve_quartic(2.0, 0.0, -10.0, 0.0, 8.0),
+            &[-2.0, -1.0, 1.0, 2.0],
+        );
+        // (t + 0.5)(t - 3)(t^2 + 1)
+        assert_roots(&solve_quartic(1.0, -2.5, -0.5, -2.5, -1.5), &[-0.5, 3.0]);
+        assert_roots(&solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn quartic_roots_are_finite() {
+        for roots in [
+            solve_quartic(0.0, 0.0, 1.0, 0.0, -1.0),
+            solve_quartic(1.0, f64::NAN, 0.0, 0.0, 0.0),
+        ] {
+            assert!(roots.iter().all(|t| t.is_finite()), "roots {:?}", roots);
+        }
+    }
+
+    fn torus() -> Torus {
+        Torus::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            2.0,
+            0.5,
+            Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn torus_intervals_through_both_sides_of_the_tube() {
+        let ray = Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let intervals: Vec<(f64, f64)> = torus()
+            .intervals(&ray)
+            .iter()
+            .map(|interval| (interval.enter.t, interval.exit.t))
+            .collect();
+        assert_eq!(intervals.len(), 2);
+        for ((enter, exit), expected) in intervals.iter().zip(&[(2.5, 3.5), (6.5, 7.5)]) {
+            assert!((enter - expected.0).abs() < 1e-6 && (exit - expected.1).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn torus_intervals_for_a_grazing_ray_enter_before_they_exit() {
+        // Touches the top of the tube on both sides of the hole.
+        let ray = Ray::new(Vector3::new(-5.0, 0.0, 0.5), Vector3::new(1.0, 0.0, 0.0));
+        for interval in torus().intervals(&ray) {
+            assert!(interval.enter.t <= interval.exit.t + 1e-6);
+            assert!(ray.direction().dot(interval.enter.geometric_normal) <= 0.0);
+        }
+    }
+
+    fn lambertian() -> Box<dyn Material> {
+        Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5)))
+    }
+
+    /// Asserts that an open surface hit from `ray` faces it, and so reflects
+    /// light arriving from the side the ray came from.
+    fn assert_faces(hittable: &dyn Hittable, ray: &Ray) {
+        let rec = hittable.hit(ray, 0.001, f64::MAX).unwrap();
+        assert!(ray.direction().dot(rec.normal) < 0.0);
+        assert!(ray.direction().dot(rec.geometric_normal) < 0.0);
+        let back = -ray.direction();
+        let evaluation = rec.material.evaluate(ray, &rec, back).unwrap();
+        assert!(evaluation.value().x() > 0.0);
+        let scatter = rec.material.scatter(ray, &rec).unwrap();
+        assert!(scatter.ray.direction().dot(rec.geometric_normal) > 0.0);
+    }
+
+    #[test]
+    fn inside_of_a_paraboloid_faces_the_ray() {
+        let bowl = Paraboloid::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            lambertian(),
+        );
+        let down = Vector3::new(0.2, -1.0, 0.1);
+        assert_faces(&bowl, &Ray::new(Vector3::new(0.0, 0.8, 0.0), down));
+        // From outside, underneath the bowl.
+        let up = Vector3::new(0.1, 1.0, 0.0);
+        assert_faces(&bowl, &Ray::new(Vector3::new(0.0, -1.0, 0.0), up));
+    }
+
+    #[test]
+    fn back_of_a_disk_faces_the_ray() {
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let disk = Disk::new(Vector3::new(0.0, 0.0, 0.0), normal, 1.0, lambertian());
+        let ray = |z: f64| Ray::new(Vector3::new(0.0, 0.0, z), Vector3::new(0.1, 0.0, -z));
+        assert_faces(&disk, &ray(2.0));
+        assert_faces(&disk, &ray(-2.0));
+    }
+}
@@ -1,12 +1,52 @@
 use crate::vector::Vector3;
+#[derive(Clone, Copy)]
 pub struct Ray {
     origin: Vector3,
     direction: Vector3,
+    wavelength: Option<f64>,
+    exterior_ior: Option<f64>,
 }
 
 impl Ray {
     pub fn new(origin: Vector3, direction: Vector3) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            wavelength: None,
+            exterior_ior: None,
+        }
+    }
+
+    /// Tags the ray with the wavelength in nanometers it carries when
+    /// rendering spectrally.
+    pub fn with_wavelength(mut self, wavelength: f64) -> Ray {
+        self.wavelength = Some(wavelength);
+        self
+    }
+
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    /// Tags the ray with the index of refraction outside the dielectric it
+    /// is about to hit, when that is not air.
+    pub fn with_exterior_ior(mut self, ior: f64) -> Ray {
+        self.exterior_ior = Some(ior);
+        self
+    }
+
+    pub fn exterior_ior(&self) -> Option<f64> {
+        self.exterior_ior
+    }
+
+    /// The same ray, wavelength and all, from another origin and along
+    /// another direction.
+    pub fn redirected(&self, origin: Vector3, direction: Vector3) -> Ray {
+        Ray {
+            origin,
+            direction,
+            ..*self
+        }
     }
 
     pub fn origin(&self) -> Vector3 {
@@ -1,12 +1,112 @@
-use crate::hittables::{Dielectric, Hittable, HittableList, Lambertian, Metal, Sphere};
-use crate::image::{color_float_to_u8, Image, Pixel, Tile};
+use crate::aabb::Aabb;
+use crate::alpha::AlphaMask;
+use crate::animation::{AnimatedCamera, AnimatedTransform, Interpolation, Track};
+use crate::aov::{id_color, Aov, FirstBounce, Lobe, Surface};
+use crate::bump::{BumpMap, NormalMap};
+use crate::bvh::Bvh;
+use crate::camera::{
+    Aperture, ApertureMask, Camera, CameraFrame, PerspectiveCamera, Projection, Stereo,
+};
+use crate::csg::{Difference, Intersection, Union};
+use crate::denoise;
+use crate::environment::{
+    ConstantEnvironment, Environment, ImageEnvironment, LightSample, SkyGradient,
+};
+use crate::hittables::{
+    Cuboid, Dielectric, Dispersion, HitRecord, Hittable, HittableList, Lambertian, Metal, Sphere,
+};
+use crate::ies::IesProfile;
+use crate::image::{color_float_to_u8, Framebuffer, Image, Pixel, Tile};
+use crate::layered::{Coated, MixMaterial};
+use crate::lens::{LensSystem, RealisticCamera};
+use crate::lights::{DirectionalLight, Emissive, Light, PointLight, SphereLight, SpotLight};
+use crate::lightsampler::{LightSampler, LightSelection};
+use crate::microfacet::{RoughConductor, RoughDielectric};
+use crate::nested::MediumStack;
+use crate::principled::Principled;
+use crate::quadrics::{Cone, Cylinder, Disk, Paraboloid, Torus};
 use crate::ray::Ray;
-use crate::rng::{random_f64, random_in_unit_sphere};
+use crate::rng::random_f64;
+use crate::sdf::{
+    BoxSdf, CapsuleSdf, Repeat, SdfHittable, SmoothSubtraction, SmoothUnion, SphereSdf, TorusSdf,
+    Twist,
+};
+use crate::sky::PhysicalSky;
+use crate::spectrum::{self, HERO_WAVELENGTHS};
+use crate::subsurface::{self, Subsurface};
+use crate::textures::{CheckerTexture, ConstantTexture, GradientTexture, ImageTexture};
+use crate::thinfilm::ThinFilm;
+use crate::transform::Transformed;
 use crate::vector::Vector3;
+use crate::volumes::{self, GridMedium, VoxelGrid};
 use num_cpus;
+use std::io;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
+/// Options shared by all the ray traced renderers.
+#[derive(Clone, Copy, Default)]
+pub struct RenderSettings {
+    /// Trace hero wavelengths instead of RGB, so dispersive materials split
+    /// light into its colours.
+    pub spectral: bool,
+    /// Projection replacing each renderer's perspective camera.
+    pub projection: Projection,
+    /// Render a stereo pair packed into the image rather than one view.
+    pub stereo: Option<Stereo>,
+    /// Render every output variable in `Aov::ALL` as a pass of the image.
+    pub aovs: bool,
+    /// Samples per pixel, or `None` for a hundred.
+    pub samples: Option<u32>,
+    /// Filter the noise out of the image, guided by its albedo and normals.
+    pub denoise: bool,
+}
+
+/// Everything a renderer traces rays against: the objects and the light
+/// arriving from around them.
+pub struct Scene {
+    pub world: HittableList,
+    pub environment: Box<dyn Environment>,
+    pub lights: Vec<Box<dyn Light>>,
+    pub light_selection: LightSelection,
+    /// Built from `lights` when rendering starts.
+    light_sampler: LightSampler,
+}
+
+impl Scene {
+    pub fn new(world: HittableList) -> Scene {
+        Scene {
+            world,
+            environment: Box::new(SkyGradient),
+            lights: vec![],
+            light_selection: LightSelection::default(),
+            light_sampler: LightSampler::new(&[], LightSelection::default()),
+        }
+    }
+
+    pub fn with_environment<E: Environment + 'static>(mut self, environment: E) -> Scene {
+        self.environment = Box::new(environment);
+        self
+    }
+
+    pub fn with_light<L: Light + 'static>(mut self, light: L) -> Scene {
+        self.lights.push(Box::new(light));
+        self
+    }
+
+    pub fn with_light_selection(mut self, selection: LightSelection) -> Scene {
+        self.light_selection = selection;
+        self
+    }
+}
+
+impl From<HittableList> for Scene {
+    fn from(world: HittableList) -> Scene {
+        Scene::new(world)
+    }
+}
+
 pub fn draw_blank(width: u32, height: u32) -> Image {
     Image::new(width, height)
 }
@@ -32,74 +132,341 @@ pub fn draw_gradient(width: u32, height: u32) -> Image {
     image
 }
 
-struct Camera {
-    origin: Vector3,
-    lower_left_corner: Vector3,
-    horizontal: Vector3,
-    vertical: Vector3,
-    u: Vector3,
-    v: Vector3,
-    lens_radius: f64,
-}
-
-impl Camera {
-    fn new(
-        lookfrom: Vector3,
-        lookat: Vector3,
-        vup: Vector3,
-        vfov: f64,
-        aspect: f64,
-        aperture: f64,
-        focus_dist: f64,
-    ) -> Camera {
-        let lens_radius = aperture / 2.0;
-        let theta = vfov * std::f64::consts::PI / 180.0;
-        let half_height = (theta / 2.0).tan();
-        let half_width = aspect * half_height;
-        let w = (lookfrom - lookat).unit_vector();
-        let u = vup.cross(w).unit_vector();
-        let v = w.cross(u);
-        let lower_left_corner =
-            lookfrom - half_width * focus_dist * u - half_height * focus_dist * v - focus_dist * w;
-
-        Camera {
-            lower_left_corner,
-            horizontal: 2.0 * half_width * focus_dist * u,
-            vertical: 2.0 * half_height * focus_dist * v,
-            origin: lookfrom,
-            u,
-            v,
-            lens_radius,
+/// Balances light sampling against material sampling of the same direction,
+/// from Veach, "Optimally Combining Sampling Techniques".
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (pdf, other) = (pdf * pdf, other * other);
+    if pdf + other > 0.0 {
+        pdf / (pdf + other)
+    } else {
+        0.0
+    }
+}
+
+/// Fraction of the light from `light` that a shadow ray lets through to the
+/// hit and the material scatters along `ray_in`, per lobe.
+fn light_weight(
+    ray_in: &Ray,
+    rec: &HitRecord,
+    scene: &Scene,
+    light: &LightSample,
+) -> Option<[Vector3; 3]> {
+    let evaluation = rec.material.evaluate(ray_in, rec, light.direction)?;
+    if evaluation.value().squared_length() <= 0.0 {
+        return None;
+    }
+    let shadow = Ray::new(rec.p, light.direction);
+    let visibility = scene
+        .world
+        .transmittance(&shadow, 0.001, light.distance * (1.0 - 1e-6));
+    if visibility <= 0.0 {
+        return None;
+    }
+    Some(evaluation.lobes.map(|lobe| visibility * lobe))
+}
+
+/// Samples the environment and one of the lights from a hit, handing `gather` the
+/// weight the material and shadow ray give each light, per lobe, and the
+/// radiance arriving, kept apart so the spectral renderer can upsample them
+/// differently.
+fn direct_light<F: FnMut([Vector3; 3], Vector3)>(
+    ray_in: &Ray,
+    rec: &HitRecord,
+    scene: &Scene,
+    mut gather: F,
+) {
+    if let Some(light) = scene.environment.sample() {
+        if let Some(weight) = light_weight(ray_in, rec, scene, &light) {
+            let pdf = rec
+                .material
+                .evaluate(ray_in, rec, light.direction)
+                .map_or(0.0, |evaluation| evaluation.pdf);
+            let mis = power_heuristic(light.pdf, pdf) / light.pdf;
+            gather(weight.map(|lobe| mis * lobe), light.radiance);
+        }
+    }
+    if let Some((index, picked)) = scene.light_sampler.sample(rec.p) {
+        if let Some(light) = scene.lights[index].sample(rec.p) {
+            if let Some(weight) = light_weight(ray_in, rec, scene, &light) {
+                let scale = 1.0 / (picked * light.pdf);
+                gather(weight.map(|lobe| scale * lobe), light.radiance);
+            }
         }
     }
+}
 
-    fn ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * random_in_unit_sphere();
-        let offset = self.u * rd.x() + self.v * rd.y();
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-        )
+/// Weight of the environment seen by a ray the last material sampled with
+/// density `scatter_pdf`, `None` when light sampling could not have found it.
+fn environment_weight(scene: &Scene, direction: Vector3, scatter_pdf: Option<f64>) -> f64 {
+    match scatter_pdf {
+        Some(pdf) => power_heuristic(pdf, scene.environment.pdf(direction)),
+        None => 1.0,
     }
 }
 
-fn color(ray: &Ray, world: &dyn Hittable, depth: u8) -> Vector3 {
-    let hit_record = world.hit(ray, 0.001, std::f64::MAX);
+/// Radiance along `ray`. Given `first_bounce`, it is told how the light
+/// splits at the first surface.
+fn color<'w>(
+    ray: &Ray,
+    scene: &'w Scene,
+    depth: u8,
+    media: &MediumStack<'w>,
+    scatter_pdf: Option<f64>,
+    mut first_bounce: Option<&mut FirstBounce>,
+) -> Vector3 {
+    let world = &scene.world;
+    let medium = media.medium();
+    let scattering = medium.map_or([0.0; 3], |m| m.scattering.to_array());
+    let extinction = medium.map_or([0.0; 3], |m| (m.scattering + m.absorption).to_array());
+    let anisotropy = medium.map_or(0.0, |m| m.anisotropy);
+    // Scattering inside a medium turns the ray away from what was sampled.
+    let scatter_pdf = scatter_pdf.filter(|_| scattering.iter().all(|s| *s == 0.0));
+    let mut weight = [1.0; 3];
+    let walked = subsurface::walk_to_surface(
+        *ray,
+        world,
+        &scattering,
+        &extinction,
+        anisotropy,
+        &mut weight,
+    );
+    let (ray, hit_record) = match walked {
+        Some(walked) => walked,
+        None => return Vector3::new(0.0, 0.0, 0.0),
+    };
+    let ray = &ray;
+    let transmission = Vector3::from_array(weight);
     match hit_record {
         Some(rec) => {
-            let (attenuation, scattered, scatter) = rec.material.scatter(ray, &rec);
-            if scatter && depth < 50 {
-                return attenuation * color(&scattered, world, depth + 1);
+            let mut media = media.clone();
+            let entering = ray.direction().dot(rec.geometric_normal) < 0.0;
+            let interior = rec.material.interior(ray, &rec);
+            let ray_in = match &interior {
+                Some(inside) => match media.exterior_ior(rec.material, inside) {
+                    Some(ior) => ray.with_exterior_ior(ior),
+                    None => {
+                        // Boundary hidden inside a higher priority medium.
+                        media.cross(rec.material, *inside, entering);
+                        if depth >= 50 {
+                            return Vector3::new(0.0, 0.0, 0.0);
+                        }
+                        let through = Ray::new(rec.p, ray.direction());
+                        let radiance = color(
+                            &through,
+                            scene,
+                            depth + 1,
+                            &media,
+                            scatter_pdf,
+                            first_bounce.as_deref_mut(),
+                        );
+                        if let Some(first_bounce) = first_bounce {
+                            first_bounce.attenuate(transmission);
+                        }
+                        return transmission * radiance;
+                    }
+                },
+                None => *ray,
+            };
+
+            let emitted = if rec.material.is_light() && scatter_pdf.is_some() {
+                Vector3::new(0.0, 0.0, 0.0)
             } else {
-                return Vector3::new(0.0, 0.0, 0.0);
+                rec.material.emitted(&rec)
+            };
+            if let Some(first_bounce) = first_bounce.as_deref_mut() {
+                first_bounce.surface = Some(Surface::of(&rec));
+                first_bounce.emission = transmission * emitted;
+            }
+            match rec.material.scatter(&ray_in, &rec) {
+                Some(scatter) if depth < 50 => {
+                    let mut direct = [Vector3::new(0.0, 0.0, 0.0); 3];
+                    direct_light(&ray_in, &rec, scene, |weights, radiance| {
+                        for (lobe, weight) in direct.iter_mut().zip(weights) {
+                            *lobe += weight * radiance;
+                        }
+                    });
+                    if let Some(inside) = interior {
+                        if (scatter.ray.direction().dot(rec.geometric_normal) < 0.0) == entering {
+                            media.cross(rec.material, inside, entering);
+                        }
+                    }
+                    let incoming = color(&scatter.ray, scene, depth + 1, &media, scatter.pdf, None);
+                    let scattered = scatter.attenuation * incoming;
+                    if let Some(first_bounce) = first_bounce {
+                        first_bounce.lobes = direct.map(|lobe| transmission * lobe);
+                        first_bounce.lobes[Lobe::of(&ray_in, &rec, &scatter) as usize] +=
+                            transmission * scattered;
+                    }
+                    transmission * (emitted + direct[0] + direct[1] + direct[2] + scattered)
+                }
+                _ => transmission * emitted,
             }
         }
         None => {
-            let unit_direction = ray.direction().unit_vector();
-            let t = 0.5 * (unit_direction.y() + 1.0);
-            (1.0 - t) * Vector3::new(1.0, 1.0, 1.0) + t * Vector3::new(0.5, 0.7, 1.0)
+            let weight = environment_weight(scene, ray.direction(), scatter_pdf);
+            let radiance = transmission * weight * scene.environment.radiance(ray.direction());
+            if let Some(first_bounce) = first_bounce {
+                first_bounce.emission = radiance;
+            }
+            radiance
+        }
+    }
+}
+
+/// Radiance along `ray` at each of `wavelengths`. RGB albedos and emission
+/// are upsampled to spectra at every bounce. Once a dispersive material is
+/// hit only the first wavelength can follow the refracted path, so the others
+/// are dropped and its contribution is scaled up to compensate. Given
+/// `first_bounce`, it is told how the light splits at the first surface.
+fn spectral_color(
+    ray: &Ray,
+    scene: &Scene,
+    wavelengths: &[f64; HERO_WAVELENGTHS],
+    first_bounce: Option<&mut FirstBounce>,
+) -> [f64; HERO_WAVELENGTHS] {
+    let world = &scene.world;
+    let mut radiance = [0.0; HERO_WAVELENGTHS];
+    let mut throughput = [1.0; HERO_WAVELENGTHS];
+    let mut ray = Ray::new(ray.origin(), ray.direction()).with_wavelength(wavelengths[0]);
+    let mut dispersed = false;
+    let mut media = MediumStack::new();
+    let mut scatter_pdf = None;
+    // The first surface, the radiance emitted there, what each lobe passed
+    // on directly from the lights and the lobe scattered off it.
+    let mut first_surface = None;
+    let mut first_emission = None;
+    let mut first_direct = [[0.0; HERO_WAVELENGTHS]; 3];
+    let mut first_lobe = None;
+
+    for _depth in 0..=50 {
+        let medium = media.medium();
+        let mut scattering = [0.0; HERO_WAVELENGTHS];
+        let mut extinction = [0.0; HERO_WAVELENGTHS];
+        let mut anisotropy = 0.0;
+        if let Some(medium) = medium {
+            for j in 0..HERO_WAVELENGTHS {
+                scattering[j] = spectrum::rgb_to_spectrum(medium.scattering, wavelengths[j]);
+                extinction[j] =
+                    scattering[j] + spectrum::rgb_to_spectrum(medium.absorption, wavelengths[j]);
+            }
+            anisotropy = medium.anisotropy;
+            if medium.scattering.squared_length() > 0.0 {
+                scatter_pdf = None;
+            }
+        }
+        let walked = subsurface::walk_to_surface(
+            ray,
+            world,
+            &scattering,
+            &extinction,
+            anisotropy,
+            &mut throughput,
+        );
+        let hit_record = match walked {
+            Some((walked_ray, hit_record)) => {
+                ray = walked_ray;
+                hit_record
+            }
+            None => break,
+        };
+        let rec = match hit_record {
+            Some(rec) => rec,
+            None => {
+                let weight = environment_weight(scene, ray.direction(), scatter_pdf);
+                let light = weight * scene.environment.radiance(ray.direction());
+                for j in 0..HERO_WAVELENGTHS {
+                    radiance[j] +=
+                        throughput[j] * spectrum::rgb_to_illuminant(light, wavelengths[j]);
+                }
+                first_emission = first_emission.or(Some(radiance));
+                break;
+            }
+        };
+        let entering = ray.direction().dot(rec.geometric_normal) < 0.0;
+        let interior = rec.material.interior(&ray, &rec);
+        if let Some(inside) = &interior {
+            match media.exterior_ior(rec.material, inside) {
+                Some(ior) => ray = ray.with_exterior_ior(ior),
+                None => {
+                    // Boundary hidden inside a higher priority medium.
+                    media.cross(rec.material, *inside, entering);
+                    ray = Ray::new(rec.p, ray.direction()).with_wavelength(wavelengths[0]);
+                    continue;
+                }
+            }
+        }
+
+        // Lights were already sampled at the last hit unless it was mirror-like.
+        if !(rec.material.is_light() && scatter_pdf.is_some()) {
+            let emitted = rec.material.emitted(&rec);
+            for j in 0..HERO_WAVELENGTHS {
+                radiance[j] += throughput[j] * spectrum::rgb_to_illuminant(emitted, wavelengths[j]);
+            }
+        }
+        let first = first_emission.is_none();
+        if first {
+            first_surface = Some(Surface::of(&rec));
+            first_emission = Some(radiance);
+        }
+        if rec.material.is_dispersive() && !dispersed {
+            dispersed = true;
+            throughput[0] *= HERO_WAVELENGTHS as f64;
+            for value in throughput.iter_mut().skip(1) {
+                *value = 0.0;
+            }
+        }
+        let scatter = match rec.material.scatter(&ray, &rec) {
+            Some(scatter) => scatter,
+            None => break,
+        };
+        if first {
+            first_lobe = Some(Lobe::of(&ray, &rec, &scatter));
+        }
+        // Each lobe is upsampled on its own, as its colour may differ.
+        direct_light(&ray, &rec, scene, |weights, light| {
+            for (lobe, weight) in weights.iter().enumerate() {
+                if weight.squared_length() <= 0.0 {
+                    continue;
+                }
+                for j in 0..HERO_WAVELENGTHS {
+                    let value = throughput[j]
+                        * spectrum::rgb_to_spectrum(*weight, wavelengths[j])
+                        * spectrum::rgb_to_illuminant(light, wavelengths[j]);
+                    radiance[j] += value;
+                    if first {
+                        first_direct[lobe][j] += value;
+                    }
+                }
+            }
+        });
+        if let Some(inside) = interior {
+            if (scatter.ray.direction().dot(rec.geometric_normal) < 0.0) == entering {
+                media.cross(rec.material, inside, entering);
+            }
+        }
+        for j in 0..HERO_WAVELENGTHS {
+            if throughput[j] != 0.0 {
+                throughput[j] *= spectrum::rgb_to_spectrum(scatter.attenuation, wavelengths[j]);
+            }
+        }
+        ray = scatter.ray.with_wavelength(wavelengths[0]);
+        scatter_pdf = scatter.pdf;
+    }
+    if let Some(first_bounce) = first_bounce {
+        let emission = first_emission.unwrap_or([0.0; HERO_WAVELENGTHS]);
+        first_bounce.surface = first_surface;
+        first_bounce.emission = spectrum::radiance_to_rgb(wavelengths, &emission);
+        first_bounce.lobes =
+            first_direct.map(|direct| spectrum::radiance_to_rgb(wavelengths, &direct));
+        if let Some(lobe) = first_lobe {
+            // Everything found further along the path came through it.
+            let direct = first_bounce.lobes[0] + first_bounce.lobes[1] + first_bounce.lobes[2];
+            let rest =
+                spectrum::radiance_to_rgb(wavelengths, &radiance) - first_bounce.emission - direct;
+            first_bounce.lobes[lobe as usize] += rest;
         }
     }
+    radiance
 }
 
 pub fn random_scene() -> HittableList {
@@ -192,143 +559,1511 @@ pub fn trio_sphere_scene() -> HittableList {
         0.5,
         Box::new(Dielectric::new(1.5)),
     ));
+    // Air bubble making the glass sphere hollow.
     world.add(Sphere::new(
         Vector3::new(-1.0, 0.0, -1.0),
-        -0.45,
-        Box::new(Dielectric::new(1.5)),
+        0.45,
+        Box::new(Dielectric::new(1.0).with_priority(1)),
     ));
     world
 }
 
-fn render_lines(width: u32, height: u32, camera: &Camera, world: &HittableList, tile: &mut Tile) {
-    let aa_samples = 100;
-    let aa_samples_f = aa_samples as f64;
-    let end_x = tile.start_x() + tile.image().get_height(); //offsetted width
-    let end_y = tile.start_y() + tile.image().get_width(); //offsetted height
+pub fn csg_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Sphere::new(
+        Vector3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5))),
+    ));
 
-    for x in tile.start_x()..end_x {
-        for y in tile.start_y()..end_y {
-            let mut color_vector = Vector3::new(0.0, 0.0, 0.0);
-            let l = (height - x - 1) as f64;
-            let c = y as f64;
-            for _s in 0..aa_samples {
-                let u = (c + random_f64()) / width as f64;
-                let v = (l + random_f64()) / height as f64;
-                let r = camera.ray(u, v);
-                color_vector += color(&r, world, 0);
-            }
+    // Rounded block with a spherical pocket carved out of its top.
+    let block = Intersection::new(
+        Cuboid::new(
+            Vector3::new(-1.0, 0.0, -1.0),
+            Vector3::new(1.0, 2.0, 1.0),
+            Box::new(Metal::new(Vector3::new(0.7, 0.7, 0.75), 0.1)),
+        ),
+        Sphere::new(
+            Vector3::new(0.0, 1.0, 0.0),
+            1.35,
+            Box::new(Metal::new(Vector3::new(0.7, 0.7, 0.75), 0.1)),
+        ),
+    );
+    world.add(Difference::new(
+        block,
+        Sphere::new(
+            Vector3::new(0.0, 2.0, 0.0),
+            0.7,
+            Box::new(Lambertian::from(Vector3::new(0.8, 0.2, 0.1))),
+        ),
+    ));
 
-            let color_vector_aa = color_vector / aa_samples_f;
-            let color_vector_gamma = color_vector_aa.square_root();
-            tile.set(x, y, Pixel::from(color_vector_gamma));
-        }
-    }
+    // Flanged pin.
+    world.add(Union::new(
+        Cuboid::new(
+            Vector3::new(1.8, 0.0, -0.3),
+            Vector3::new(2.4, 1.6, 0.3),
+            Box::new(Lambertian::from(Vector3::new(0.1, 0.3, 0.6))),
+        ),
+        Sphere::new(
+            Vector3::new(2.1, 1.6, 0.0),
+            0.5,
+            Box::new(Lambertian::from(Vector3::new(0.1, 0.3, 0.6))),
+        ),
+    ));
+
+    // Glass lens: the overlap of two spheres.
+    world.add(Intersection::new(
+        Sphere::new(
+            Vector3::new(-2.0, 0.8, 1.2),
+            0.8,
+            Box::new(Dielectric::new(1.5)),
+        ),
+        Sphere::new(
+            Vector3::new(-2.0, 0.8, -0.2),
+            0.8,
+            Box::new(Dielectric::new(1.5)),
+        ),
+    ));
+    world
 }
 
-fn multithread_render(
-    width: u32,
-    height: u32,
-    camera: Camera,
-    world: HittableList,
-    tiles: Vec<Tile>,
-) -> Vec<Tile> {
-    let num_cpus = num_cpus::get();
-    let tiles_iter = Arc::new(Mutex::new(tiles.into_iter()));
-    let rendered_tiles = Arc::new(Mutex::new(vec![]));
-    let camera = Arc::new(camera);
-    let world = Arc::new(world);
+pub fn sdf_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Sphere::new(
+        Vector3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5))),
+    ));
 
-    let mut handles = vec![];
-    for i in 0..num_cpus {
-        let tiles_iter = Arc::clone(&tiles_iter);
-        let rendered_tiles = Arc::clone(&rendered_tiles);
-        let camera = Arc::clone(&camera);
-        let world = Arc::clone(&world);
-        let handle = thread::Builder::new()
-            .name(format!("rayito-wrk-{}", i))
-            .spawn(move || loop {
-                let tile = {
-                    let mut iter = tiles_iter.lock().unwrap();
-                    iter.next()
-                };
-                match tile {
-                    Some(mut tile) => {
-                        render_lines(width, height, &camera, &world, &mut tile);
-                        rendered_tiles.lock().unwrap().push(tile);
-                    }
-                    _ => break,
-                }
-            })
-            .unwrap();
-        handles.push(handle);
-    }
+    world.add(
+        SdfHittable::new(
+            Twist::new(
+                BoxSdf::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.35, 1.0, 0.35)),
+                1.2,
+            ),
+            Box::new(Metal::new(Vector3::new(0.8, 0.6, 0.2), 0.05)),
+        )
+        .with_bounds(Aabb::new(
+            Vector3::new(-0.6, 0.0, -0.6),
+            Vector3::new(0.6, 2.0, 0.6),
+        ))
+        .with_step_scale(0.5),
+    );
 
-    for handle in handles {
-        handle.join().unwrap();
-    }
+    world.add(SdfHittable::new(
+        SmoothUnion::new(
+            CapsuleSdf::new(
+                Vector3::new(-2.2, 0.3, 0.0),
+                Vector3::new(-1.4, 1.4, 0.0),
+                0.25,
+            ),
+            SphereSdf::new(Vector3::new(-1.9, 0.45, 0.0), 0.45),
+            0.3,
+        ),
+        Box::new(Lambertian::from(Vector3::new(0.1, 0.4, 0.2))),
+    ));
 
-    let tiles = Arc::try_unwrap(rendered_tiles);
-    if let Ok(tiles) = tiles {
-        tiles.into_inner().unwrap()
-    } else {
-        panic!("Tiles still held by worker threads");
-    }
+    world.add(SdfHittable::new(
+        SmoothSubtraction::new(
+            TorusSdf::new(Vector3::new(2.0, 0.25, 0.0), 0.6, 0.25),
+            SphereSdf::new(Vector3::new(2.0, 0.6, 0.6), 0.35),
+            0.1,
+        ),
+        Box::new(Dielectric::new(1.5)),
+    ));
+
+    world.add(
+        SdfHittable::new(
+            Repeat::new(
+                SphereSdf::new(Vector3::new(0.0, 0.12, 0.0), 0.12),
+                Vector3::new(0.5, 0.0, 0.5),
+            )
+            .with_limit(Vector3::new(6.0, 0.0, 2.0)),
+            Box::new(Lambertian::from(Vector3::new(0.6, 0.1, 0.1))),
+        )
+        .with_bounds(Aabb::new(
+            Vector3::new(-3.2, 0.0, 1.0),
+            Vector3::new(3.2, 0.25, 2.2),
+        )),
+    );
+    world
 }
 
-fn render(width: u32, height: u32, camera: Camera, world: HittableList) -> Image {
-    let lines_per_tile = 10;
-    let tile_count = height / lines_per_tile;
+pub fn quadrics_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Sphere::new(
+        Vector3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5))),
+    ));
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    world.add(Cylinder::new(
+        Vector3::new(-3.0, 0.0, 0.0),
+        up,
+        0.5,
+        1.5,
+        Box::new(Lambertian::from(Vector3::new(0.1, 0.3, 0.6))),
+    ));
+    world.add(Cone::new(
+        Vector3::new(-1.5, 0.0, 0.0),
+        up,
+        0.6,
+        1.4,
+        Box::new(Metal::new(Vector3::new(0.8, 0.6, 0.2), 0.1)),
+    ));
+    world.add(Torus::new(
+        Vector3::new(0.0, 0.6, 0.0),
+        Vector3::new(0.0, 1.0, 1.0),
+        0.6,
+        0.2,
+        Box::new(Dielectric::new(1.5)),
+    ));
+    world.add(Disk::annulus(
+        Vector3::new(1.5, 0.8, 0.0),
+        Vector3::new(0.0, 0.3, 1.0),
+        0.25,
+        0.6,
+        Box::new(Lambertian::from(Vector3::new(0.7, 0.2, 0.2))),
+    ));
+    world.add(Disk::new(
+        Vector3::new(1.5, 0.001, 1.0),
+        up,
+        0.5,
+        Box::new(Metal::new(Vector3::new(0.9, 0.9, 0.9), 0.0)),
+    ));
+    world.add(Paraboloid::new(
+        Vector3::new(3.0, 0.0, 0.0),
+        up,
+        0.6,
+        1.2,
+        Box::new(Lambertian::from(Vector3::new(0.2, 0.6, 0.2))),
+    ));
+    world
+}
 
-    let tile_count = if height % tile_count == 0 { tile_count } else { tile_count + 1 };
+pub fn microfacet_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Sphere::new(
+        Vector3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5))),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-3.3, 0.6, 0.0),
+        0.6,
+        Box::new(RoughConductor::gold(0.15)),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-2.0, 0.6, 0.0),
+        0.6,
+        Box::new(RoughConductor::copper(0.35)),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-0.66, 0.6, 0.0),
+        0.6,
+        Box::new(RoughConductor::aluminium(0.6)),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(0.66, 0.6, 0.0),
+        0.6,
+        Box::new(RoughDielectric::new(1.5, 0.05)),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(2.0, 0.6, 0.0),
+        0.6,
+        Box::new(RoughDielectric::new(1.5, 0.3)),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(3.3, 0.6, 0.0),
+        0.6,
+        Box::new(RoughDielectric::new(1.5, 0.6)),
+    ));
+    world
+}
 
-    let mut tiles: Vec<Tile> = Vec::with_capacity(tile_count as usize);
-    for i in 0..tile_count {
-        let start_x = i * lines_per_tile;
-        let tile_height = if i < tile_count - 1 {
-            lines_per_tile
-        } else {
-            height - lines_per_tile * i
-        };
-        tiles.push(Tile::new(start_x, 0, width, tile_height));
+pub fn principled_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Sphere::new(
+        Vector3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(Principled::new(Vector3::new(0.5, 0.5, 0.5)).with_roughness(0.9)),
+    ));
+    let materials = vec![
+        // Plastic.
+        Principled::new(Vector3::new(0.8, 0.1, 0.1)).with_roughness(0.3),
+        // Brushed gold.
+        Principled::new(Vector3::new(1.0, 0.78, 0.34))
+            .with_metallic(1.0)
+            .with_roughness(0.25),
+        // Car paint.
+        Principled::new(Vector3::new(0.05, 0.1, 0.5))
+            .with_roughness(0.5)
+            .with_clearcoat(1.0, 0.03),
+        // Velvet.
+        Principled::new(Vector3::new(0.4, 0.05, 0.3))
+            .with_roughness(1.0)
+            .with_specular(0.2)
+            .with_sheen(1.0, 0.8),
+        // Tinted glass.
+        Principled::new(Vector3::new(0.7, 0.95, 0.8))
+            .with_roughness(0.05)
+            .with_transmission(1.0, 1.5),
+        // Checkered metal and ceramic.
+        Principled::new(Vector3::new(1.0, 1.0, 1.0))
+            .with_base_color_texture(CheckerTexture::new(
+                Box::new(ConstantTexture::new(Vector3::new(0.9, 0.9, 0.9))),
+                Box::new(ConstantTexture::new(Vector3::new(0.95, 0.64, 0.54))),
+                8.0,
+            ))
+            .with_metallic_texture(CheckerTexture::new(
+                Box::new(ConstantTexture::scalar(0.0)),
+                Box::new(ConstantTexture::scalar(1.0)),
+                8.0,
+            ))
+            .with_roughness_texture(CheckerTexture::new(
+                Box::new(ConstantTexture::scalar(0.1)),
+                Box::new(ConstantTexture::scalar(0.4)),
+                8.0,
+            )),
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        world.add(Sphere::new(
+            Vector3::new(-3.3 + 1.32 * i as f64, 0.6, 0.0),
+            0.6,
+            Box::new(material),
+        ));
     }
+    world
+}
 
-    // tiles
-    //     .iter_mut()
-    //     .for_each(|tile| render_lines(width, height, &camera, &world, tile));
+/// A glass of water, modelled as overlapping solids resolved by priority,
+/// next to blocks of coloured glass. The glass is a solid cylinder that the
+/// water and the air above it carve out by outranking it.
+pub fn nested_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.9, 0.9, 0.9))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.2, 0.1, 0.05))),
+                    40.0,
+                )),
+        ),
+    ));
 
-    let tiles = multithread_render(width, height, camera, world, tiles);
-    Image::from_tiles(width, height, tiles)
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    world.add(Cylinder::new(
+        Vector3::new(0.0, 0.0, 0.0),
+        up,
+        0.8,
+        2.0,
+        Box::new(
+            Dielectric::new(1.5)
+                .with_absorption(Vector3::new(0.3, 0.05, 0.2))
+                .with_priority(1),
+        ),
+    ));
+    // The water is narrower than the glass and wins the overlap, leaving the
+    // walls and bottom around it.
+    world.add(Cylinder::new(
+        Vector3::new(0.0, 0.1, 0.0),
+        up,
+        0.75,
+        1.25,
+        Box::new(
+            Dielectric::new(1.33)
+                .with_absorption(Vector3::new(0.4, 0.1, 0.02))
+                .with_priority(2),
+        ),
+    ));
+    // Air hollowing out the glass above the water.
+    world.add(Cylinder::new(
+        Vector3::new(0.0, 1.3, 0.0),
+        up,
+        0.7,
+        1.0,
+        Box::new(Dielectric::new(1.0).with_priority(3)),
+    ));
+
+    world.add(Sphere::new(
+        Vector3::new(-1.9, 0.6, 0.3),
+        0.6,
+        Box::new(Dielectric::new(1.5).with_absorption(Vector3::new(0.1, 1.5, 2.0))),
+    ));
+    world.add(Cuboid::new(
+        Vector3::new(1.3, 0.0, -0.2),
+        Vector3::new(2.3, 1.0, 0.8),
+        Box::new(Dielectric::new(1.5).with_absorption(Vector3::new(1.5, 0.3, 0.1))),
+    ));
+    world
 }
 
-pub fn draw_trio(width: u32, height: u32) -> Image {
-    let height_float = height as f64;
-    let width_float = width as f64;
+/// Soap bubbles and anodized metal, coloured by thin film interference. The
+/// films thin out towards the top of each sphere.
+pub fn thin_film_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.3, 0.3, 0.3))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.05, 0.05, 0.05))),
+                    40.0,
+                )),
+        ),
+    ));
+    let film_thickness = |bottom: f64, top: f64| {
+        GradientTexture::new(
+            Vector3::new(bottom, bottom, bottom),
+            Vector3::new(top, top, top),
+        )
+    };
+    for (center, radius) in [
+        (Vector3::new(-1.6, 1.0, 0.0), 0.8),
+        (Vector3::new(-0.3, 0.5, 1.3), 0.5),
+    ] {
+        world.add(Sphere::new(
+            center,
+            radius,
+            Box::new(Dielectric::new(1.0).with_thin_film(
+                ThinFilm::new(1.33, 0.0).with_thickness_texture(film_thickness(900.0, 150.0)),
+            )),
+        ));
+    }
+    world.add(Sphere::new(
+        Vector3::new(1.5, 0.8, 0.0),
+        0.8,
+        Box::new(
+            Metal::new(Vector3::new(0.6, 0.6, 0.62), 0.02).with_thin_film(
+                ThinFilm::new(2.4, 0.0).with_thickness_texture(film_thickness(350.0, 100.0)),
+            ),
+        ),
+    ));
+    world
+}
 
-    let lookfrom = Vector3::new(3.0, 3.0, 2.0);
-    let lookat = Vector3::new(0.0, 0.0, -1.0);
-    let distance_to_focus = (lookfrom - lookat).length();
-    let aperture = 2.0;
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        Vector3::new(0.0, 1.0, 0.0),
-        20.0,
-        width_float / height_float,
-        aperture,
-        distance_to_focus,
-    );
-    render(width, height, camera, trio_sphere_scene())
+/// Clear glass and gems over a checkerboard, for use with spectral rendering.
+pub fn dispersion_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.9, 0.9, 0.9))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.05, 0.05, 0.05))),
+                    40.0,
+                )),
+        ),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-1.5, 0.7, 0.0),
+        0.7,
+        Box::new(Dielectric::bk7()),
+    ));
+    world.add(Cone::new(
+        Vector3::new(0.0, 1.4, 0.0),
+        Vector3::new(0.0, -1.0, 0.0),
+        0.7,
+        1.4,
+        Box::new(Dielectric::diamond()),
+    ));
+    // Dense flint glass.
+    world.add(Sphere::new(
+        Vector3::new(1.5, 0.7, 0.0),
+        0.7,
+        Box::new(Dielectric::new(1.78).with_dispersion(Dispersion::Cauchy {
+            a: 1.728,
+            b: 0.013_42,
+        })),
+    ));
+    world
 }
 
-pub fn draw_random(width: u32, height: u32) -> Image {
-    let height_float = height as f64;
-    let width_float = width as f64;
+/// Heights of a golf ball like pattern of round dimples, twice as wide as
+/// it is tall to suit the uv mapping of spheres.
+fn dimple_heights() -> (usize, usize, Vec<f64>) {
+    let (width, height, cell) = (512, 256, 32);
+    let radius = 0.4 * cell as f64;
+    let mut heights = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let dx = (x % cell) as f64 + 0.5 - 0.5 * cell as f64;
+            let dy = (y % cell) as f64 + 0.5 - 0.5 * cell as f64;
+            let d2 = dx * dx + dy * dy;
+            let depth = if d2 < radius * radius {
+                (radius * radius - d2).sqrt() / radius
+            } else {
+                0.0
+            };
+            heights.push(1.0 - depth);
+        }
+    }
+    (width, height, heights)
+}
 
-    let lookfrom = Vector3::new(12.0, 1.5, 3.0);
-    let lookat = Vector3::new(0.0, 0.3, 0.0);
-    let distance_to_focus = (lookfrom - lookat).length();
+fn height_texture(width: usize, height: usize, heights: &[f64]) -> ImageTexture {
+    let texels = heights.iter().map(|&h| Vector3::new(h, h, h)).collect();
+    ImageTexture::new(width, height, texels)
+}
+
+/// Tangent space normal map of a height field, from central differences.
+fn normal_texture(width: usize, height: usize, heights: &[f64], strength: f64) -> ImageTexture {
+    let at = |x: usize, y: usize| heights[(y % height) * width + x % width];
+    let mut texels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let dx = 0.5 * (at(x + 1, y) - at(x + width - 1, y));
+            // Rows go down the image while v goes up.
+            let dy = 0.5 * (at(x, y + height - 1) - at(x, y + 1));
+            let normal = Vector3::new(-strength * dx, -strength * dy, 1.0).unit_vector();
+            texels.push(0.5 * normal + Vector3::new(0.5, 0.5, 0.5));
+        }
+    }
+    ImageTexture::new(width, height, texels)
+}
+
+/// The same dimpled pattern applied as a bump map and as a normal map, unless
+/// another normal map is given.
+pub fn bump_scene(normal_map: Option<ImageTexture>) -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.8, 0.8, 0.8))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.3, 0.3, 0.3))),
+                    40.0,
+                )),
+        ),
+    ));
+
+    let (width, height, heights) = dimple_heights();
+    world.add(Sphere::new(
+        Vector3::new(-1.5, 0.8, 0.0),
+        0.8,
+        Box::new(BumpMap::new(
+            Box::new(Principled::new(Vector3::new(0.8, 0.1, 0.1)).with_roughness(0.3)),
+            height_texture(width, height, &heights),
+            0.03,
+        )),
+    ));
+    let normal_map = normal_map.unwrap_or_else(|| normal_texture(width, height, &heights, 8.0));
+    world.add(Sphere::new(
+        Vector3::new(0.5, 0.8, 0.0),
+        0.8,
+        Box::new(NormalMap::new(
+            Box::new(RoughConductor::gold(0.2)),
+            normal_map,
+        )),
+    ));
+    world.add(Torus::new(
+        Vector3::new(2.3, 0.25, 0.8),
+        Vector3::new(0.0, 1.0, 0.0),
+        0.5,
+        0.25,
+        Box::new(BumpMap::new(
+            Box::new(Principled::new(Vector3::new(0.1, 0.3, 0.8)).with_roughness(0.4)),
+            height_texture(width, height, &heights),
+            0.01,
+        )),
+    ));
+    world
+}
+
+/// Opacity of a picket fence: vertical slats held by two rails.
+fn fence_opacity() -> ImageTexture {
+    let size = 128;
+    let mut texels = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let slat = x % 16 < 11 && y >= 8;
+            let rail = (28..36).contains(&y) || (92..100).contains(&y);
+            let opacity = if slat || rail { 1.0 } else { 0.0 };
+            texels.push(Vector3::new(opacity, opacity, opacity));
+        }
+    }
+    ImageTexture::new(size, size, texels)
+}
+
+/// Opacity of a leaf: a pointed ellipse fading out towards its edge.
+fn leaf_opacity() -> ImageTexture {
+    let size = 64;
+    let mut texels = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let u = 2.0 * (x as f64 + 0.5) / size as f64 - 1.0;
+            let v = 2.0 * (y as f64 + 0.5) / size as f64 - 1.0;
+            let half_width = 0.55 * (1.0 - v * v);
+            let r = if half_width > 0.0 {
+                u.abs() / half_width
+            } else {
+                2.0
+            };
+            let opacity = ((1.0 - r) / 0.2).clamp(0.0, 1.0);
+            texels.push(Vector3::new(opacity, opacity, opacity));
+        }
+    }
+    ImageTexture::new(size, size, texels)
+}
+
+/// Cards cut out by opacity masks: a fence, soft edged leaves and a sphere
+/// showing its inside through checkered holes.
+pub fn cutout_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.8, 0.8, 0.8))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.3, 0.3, 0.3))),
+                    40.0,
+                )),
+        ),
+    ));
+    world.add(Cuboid::new(
+        Vector3::new(-3.0, 0.0, -1.0),
+        Vector3::new(3.0, 1.5, -0.98),
+        Box::new(AlphaMask::new(
+            Box::new(Lambertian::from(Vector3::new(0.55, 0.35, 0.2))),
+            fence_opacity(),
+        )),
+    ));
+    let leaves = [
+        (Vector3::new(-1.8, 0.3, 0.2), 0.8),
+        (Vector3::new(-1.2, 0.5, 0.6), 0.6),
+        (Vector3::new(-1.6, 0.9, 0.9), 0.5),
+    ];
+    for (corner, size) in leaves.iter() {
+        world.add(Cuboid::new(
+            *corner,
+            *corner + Vector3::new(0.5 * size, *size, 0.01),
+            Box::new(
+                AlphaMask::new(
+                    Box::new(Lambertian::from(Vector3::new(0.2, 0.5, 0.1))),
+                    leaf_opacity(),
+                )
+                .stochastic(),
+            ),
+        ));
+    }
+    world.add(Sphere::new(
+        Vector3::new(1.2, 0.7, 0.4),
+        0.7,
+        Box::new(
+            AlphaMask::new(
+                Box::new(Principled::new(Vector3::new(0.9, 0.6, 0.1)).with_roughness(0.4)),
+                CheckerTexture::new(
+                    Box::new(ConstantTexture::scalar(1.0)),
+                    Box::new(ConstantTexture::scalar(0.2)),
+                    8.0,
+                ),
+            )
+            .with_threshold(0.5),
+        ),
+    ));
+    world
+}
+
+/// A clear afternoon sky over brown ground as an equirectangular image, with
+/// a small and very bright sun that only converges when sampled directly.
+fn afternoon_environment() -> ImageEnvironment {
+    let (width, height) = (512, 256);
+    let sun = Vector3::new(-0.6, 0.5, -0.4).unit_vector();
+    let mut texels = Vec::with_capacity(width * height);
+    for row in 0..height {
+        for column in 0..width {
+            let theta = std::f64::consts::PI * (row as f64 + 0.5) / height as f64;
+            let phi = 2.0 * std::f64::consts::PI * (column as f64 + 0.5) / width as f64;
+            let direction = Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            let texel = if direction.dot(sun) > 2f64.to_radians().cos() {
+                Vector3::new(600.0, 560.0, 500.0)
+            } else if direction.y() > 0.0 {
+                let t = direction.y().sqrt();
+                (1.0 - t) * Vector3::new(1.0, 0.95, 0.9) + t * Vector3::new(0.2, 0.4, 0.8)
+            } else {
+                Vector3::new(0.12, 0.1, 0.08)
+            };
+            texels.push(texel);
+        }
+    }
+    ImageEnvironment::new(width as u32, height as u32, texels)
+}
+
+/// A few spheres lit only by an environment map.
+pub fn environment_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.8, 0.8, 0.8))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.3, 0.3, 0.3))),
+                    40.0,
+                )),
+        ),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-1.8, 0.6, 0.0),
+        0.6,
+        Box::new(Lambertian::from(Vector3::new(0.8, 0.8, 0.8))),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-0.6, 0.6, 0.0),
+        0.6,
+        Box::new(
+            Coated::new(Box::new(Lambertian::from(Vector3::new(0.1, 0.3, 0.7))), 1.5)
+                .with_roughness(0.2),
+        ),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(0.6, 0.6, 0.0),
+        0.6,
+        Box::new(Metal::new(Vector3::new(0.9, 0.9, 0.9), 0.0)),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(1.8, 0.6, 0.0),
+        0.6,
+        Box::new(Dielectric::new(1.5)),
+    ));
+    world
+}
+
+/// Plain blocks and a sphere on a square, to be lit by daylight.
+pub fn sky_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-20.0, -0.5, -20.0),
+        Vector3::new(20.0, 0.0, 20.0),
+        Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.48))),
+    ));
+    let blocks = [
+        (Vector3::new(-3.0, 0.0, -2.5), Vector3::new(-1.5, 2.5, -1.0)),
+        (Vector3::new(-1.0, 0.0, -3.0), Vector3::new(1.0, 1.2, -1.5)),
+        (Vector3::new(1.5, 0.0, -2.0), Vector3::new(2.5, 3.5, -1.0)),
+    ];
+    for (min, max) in blocks.iter() {
+        world.add(Cuboid::new(
+            *min,
+            *max,
+            Box::new(Principled::new(Vector3::new(0.75, 0.72, 0.68)).with_roughness(0.9)),
+        ));
+    }
+    world.add(Sphere::new(
+        Vector3::new(0.0, 0.6, 0.5),
+        0.6,
+        Box::new(
+            Coated::new(
+                Box::new(Lambertian::from(Vector3::new(0.7, 0.25, 0.1))),
+                1.5,
+            )
+            .with_roughness(0.1),
+        ),
+    ));
+    world
+}
+
+/// Spheres in front of a wall at night, for the point, spot and directional
+/// lights added in `draw_lights`.
+pub fn lights_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.8, 0.8, 0.8))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.3, 0.3, 0.3))),
+                    40.0,
+                )),
+        ),
+    ));
+    world.add(Cuboid::new(
+        Vector3::new(-4.0, 0.0, -1.6),
+        Vector3::new(4.0, 3.0, -1.5),
+        Box::new(Lambertian::from(Vector3::new(0.7, 0.7, 0.7))),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-1.3, 0.5, 0.0),
+        0.5,
+        Box::new(Lambertian::from(Vector3::new(0.8, 0.8, 0.8))),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(0.0, 0.5, 0.0),
+        0.5,
+        Box::new(
+            Principled::new(Vector3::new(0.9, 0.6, 0.2))
+                .with_metallic(1.0)
+                .with_roughness(0.3),
+        ),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(1.3, 0.5, 0.0),
+        0.5,
+        Box::new(Coated::new(
+            Box::new(Lambertian::from(Vector3::new(0.1, 0.5, 0.2))),
+            1.5,
+        )),
+    ));
+    world
+}
+
+/// Double Gauss 50mm f/2 lens from Smith, "Modern Lens Design", as used by
+/// Kolb, Mitchell and Hanrahan, "A Realistic Camera Model for Computer
+/// Graphics".
+const DOUBLE_GAUSS_LENS: &str = "# radius thickness ior aperture
+29.475 3.76 1.67 25.2
+84.83 0.12 1 25.2
+19.275 4.025 1.67 23
+40.77 3.275 1.699 23
+12.75 5.705 1 18
+0 4.5 0 17.1
+-14.495 1.18 1.603 17
+40.77 6.065 1.658 20
+-20.385 0.19 1 20
+437.065 3.22 1.717 20
+-39.73 0 1 20
+";
+
+/// A made-up asymmetric wall washer in IES LM-63 format, throwing most of
+/// its light forwards at horizontal angle zero and little behind it.
+const WALL_WASHER_IES: &str = "IESNA:LM-63-2002
+[TEST] rayitos built-in
+[LUMINAIRE] Wall washer downlight
+TILT=NONE
+1 1000 1 7 5 1 2 0.1 0.1 0
+1 1 20
+0 15 30 45 60 75 90
+0 45 90 135 180
+1000 1200 1500 1800 1600 900 0
+1000 1100 1250 1300 1000 500 0
+1000 950 850 700 450 200 0
+1000 800 550 350 200 80 0
+1000 700 400 200 100 40 0
+";
+
+/// A dark room with a bare wall for photometric downlights to wash.
+pub fn ies_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.8, 0.8, 0.8))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.3, 0.3, 0.3))),
+                    40.0,
+                )),
+        ),
+    ));
+    world.add(Cuboid::new(
+        Vector3::new(-5.0, 0.0, -1.6),
+        Vector3::new(5.0, 3.2, -1.5),
+        Box::new(Lambertian::from(Vector3::new(0.75, 0.75, 0.72))),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-0.8, 0.4, -0.6),
+        0.4,
+        Box::new(Lambertian::from(Vector3::new(0.7, 0.3, 0.2))),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(0.9, 0.4, -0.4),
+        0.4,
+        Box::new(
+            Principled::new(Vector3::new(0.9, 0.9, 0.9))
+                .with_metallic(1.0)
+                .with_roughness(0.25),
+        ),
+    ));
+    world
+}
+
+/// `random_scene` at night with a good share of the small spheres glowing,
+/// so light selection has a few hundred lights to choose from.
+pub fn many_lights_scene() -> Scene {
+    let mut world = HittableList::new();
+    let mut lights = vec![];
+    world.add(Sphere::new(
+        Vector3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5))),
+    ));
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_f64();
+            let radius = 0.1 + 0.1 * random_f64();
+            let center = Vector3::new(
+                a as f64 + 0.9 * random_f64(),
+                radius,
+                b as f64 + 0.9 * random_f64(),
+            );
+            if (center - Vector3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_mat < 0.4 {
+                    let radiance = 10.0
+                        * Vector3::new(
+                            0.3 + 0.7 * random_f64(),
+                            0.3 + 0.7 * random_f64(),
+                            0.3 + 0.7 * random_f64(),
+                        );
+                    world.add(Sphere::new(
+                        center,
+                        radius,
+                        Box::new(Emissive::new(radiance)),
+                    ));
+                    lights.push(SphereLight::new(center, radius, radiance));
+                } else if choose_mat < 0.85 {
+                    world.add(Sphere::new(
+                        center,
+                        radius,
+                        Box::new(Lambertian::from(Vector3::new(
+                            random_f64() * random_f64(),
+                            random_f64() * random_f64(),
+                            random_f64() * random_f64(),
+                        ))),
+                    ));
+                } else {
+                    world.add(Sphere::new(
+                        center,
+                        radius,
+                        Box::new(Metal::new(Vector3::new(0.8, 0.8, 0.8), 0.3 * random_f64())),
+                    ));
+                }
+            }
+        }
+    }
+    world.add(Sphere::new(
+        Vector3::new(0.0, 1.0, 0.0),
+        1.0,
+        Box::new(Dielectric::new(1.5)),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-4.0, 1.0, 0.0),
+        1.0,
+        Box::new(Lambertian::from(Vector3::new(0.4, 0.2, 0.1))),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(4.0, 1.0, 0.0),
+        1.0,
+        Box::new(Metal::new(Vector3::new(0.7, 0.6, 0.5), 0.0)),
+    ));
+
+    let mut scene = Scene::new(world)
+        .with_environment(ConstantEnvironment::new(Vector3::new(0.002, 0.002, 0.004)));
+    for light in lights {
+        scene = scene.with_light(light);
+    }
+    scene
+}
+
+/// A small statue in front of a string of glowing beads far behind it, for
+/// out of focus highlights.
+pub fn bokeh_scene() -> Scene {
+    let mut world = HittableList::new();
+    let mut lights = vec![];
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -30.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.8, 0.8, 0.8))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.3, 0.3, 0.3))),
+                    40.0,
+                )),
+        ),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(0.0, 0.5, 0.0),
+        0.5,
+        Box::new(
+            Principled::new(Vector3::new(0.9, 0.6, 0.2))
+                .with_metallic(1.0)
+                .with_roughness(0.3),
+        ),
+    ));
+    for i in 0..16 {
+        let x = -9.0 + 18.0 * (i as f64 + 0.5) / 16.0;
+        let center = Vector3::new(x, 1.2 + 0.6 * (0.4 * x).sin() + 0.3 * random_f64(), -12.0);
+        let radiance = 20.0 * Vector3::new(1.0, 0.5 + 0.4 * random_f64(), 0.2 + 0.5 * random_f64());
+        world.add(Sphere::new(center, 0.15, Box::new(Emissive::new(radiance))));
+        lights.push(SphereLight::new(center, 0.15, radiance));
+    }
+
+    let mut scene = Scene::new(world)
+        .with_environment(ConstantEnvironment::new(Vector3::new(0.01, 0.012, 0.02)))
+        .with_light(
+            SpotLight::new(
+                Vector3::new(2.0, 3.0, 3.0),
+                Vector3::new(0.0, 0.5, 0.0),
+                Vector3::new(8.0, 8.0, 8.0),
+                15.0,
+                25.0,
+            )
+            .with_falloff(2.0),
+        );
+    for light in lights {
+        scene = scene.with_light(light);
+    }
+    scene
+}
+
+/// A scene whose camera and objects move over time. Geometry that stays
+/// put goes in a hierarchy built once and shared by every frame, and moving
+/// objects are built once too and only placed anew for each frame.
+pub struct Animation {
+    world: Arc<Bvh>,
+    objects: Vec<(Arc<dyn Hittable + Send + Sync>, AnimatedTransform)>,
+    camera: AnimatedCamera,
+    pub frames_per_second: f64,
+}
+
+impl Animation {
+    pub fn new(world: HittableList, camera: AnimatedCamera) -> Animation {
+        Animation {
+            world: Arc::new(Bvh::new(world)),
+            objects: vec![],
+            camera,
+            frames_per_second: 24.0,
+        }
+    }
+
+    pub fn with_object<H: Hittable + Send + Sync + 'static>(
+        mut self,
+        object: H,
+        transform: AnimatedTransform,
+    ) -> Animation {
+        self.objects.push((Arc::new(object), transform));
+        self
+    }
+
+    /// Time in seconds at which frame `frame` is shown, counting from one.
+    pub fn time(&self, frame: u32) -> f64 {
+        frame.saturating_sub(1) as f64 / self.frames_per_second
+    }
+
+    fn scene(&self, time: f64) -> Scene {
+        let mut world = HittableList::new();
+        world.add(Arc::clone(&self.world));
+        for (object, transform) in &self.objects {
+            world.add(Transformed::new(Arc::clone(object), transform.at(time)));
+        }
+        Scene::new(world)
+    }
+}
+
+/// Five seconds over `random_scene`: the camera swings around on a Bezier
+/// path while zooming and stopping down, a mirror cube tumbles across above
+/// the big spheres and a ball bounces in the front, squashing as it lands.
+pub fn animation_scene() -> Animation {
+    let camera = AnimatedCamera::new(
+        Track::new(0.0, Vector3::new(13.0, 2.0, 3.0))
+            .with_key(2.5, Vector3::new(9.0, 3.5, -8.0))
+            .with_key(5.0, Vector3::new(-3.0, 2.5, -12.0))
+            .with_interpolation(Interpolation::Bezier),
+        Track::new(0.0, Vector3::new(0.0, 0.5, 0.0)).with_key(5.0, Vector3::new(0.0, 1.5, 0.0)),
+    )
+    .with_vfov(Track::new(0.0, 20.0).with_key(5.0, 28.0))
+    .with_aperture(Track::new(0.0, 0.1).with_key(5.0, 0.02));
+
+    let cube = AnimatedTransform::new()
+        .with_translation(
+            Track::new(0.0, Vector3::new(-4.0, 2.6, 0.0))
+                .with_key(2.5, Vector3::new(0.0, 3.2, -1.5))
+                .with_key(5.0, Vector3::new(4.0, 2.6, 0.0))
+                .with_interpolation(Interpolation::Bezier),
+        )
+        .with_rotation(
+            Track::new(0.0, Vector3::new(0.0, 0.0, 0.0))
+                .with_key(5.0, Vector3::new(180.0, 360.0, 90.0)),
+        );
+
+    let mut ball_position = Track::new(0.0, Vector3::new(6.0, 1.8, 2.0));
+    let mut ball_scale = Track::new(0.0, Vector3::new(0.4, 0.4, 0.4));
+    for bounce in 0..5 {
+        let landing = bounce as f64 + 0.5;
+        let x = 6.0 - 0.9 * landing;
+        ball_position = ball_position
+            .with_key(landing, Vector3::new(x, 0.32, 2.0))
+            .with_key(landing + 0.5, Vector3::new(x - 0.45, 1.8, 2.0));
+        ball_scale = ball_scale
+            .with_key(landing - 0.1, Vector3::new(0.4, 0.4, 0.4))
+            .with_key(landing, Vector3::new(0.48, 0.32, 0.48))
+            .with_key(landing + 0.1, Vector3::new(0.4, 0.4, 0.4));
+    }
+    let ball = AnimatedTransform::new()
+        .with_translation(ball_position.with_interpolation(Interpolation::Bezier))
+        .with_scale(ball_scale);
+
+    Animation::new(random_scene(), camera)
+        .with_object(
+            Cuboid::new(
+                Vector3::new(-0.5, -0.5, -0.5),
+                Vector3::new(0.5, 0.5, 0.5),
+                Box::new(Metal::new(Vector3::new(0.9, 0.9, 0.9), 0.05)),
+            ),
+            cube,
+        )
+        .with_object(
+            Sphere::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                1.0,
+                Box::new(Lambertian::from(Vector3::new(0.8, 0.2, 0.1))),
+            ),
+            ball,
+        )
+}
+
+/// Materials built from others: blends by a constant and by a texture, and
+/// clear and tinted coats over diffuse and metal bases.
+pub fn layered_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(
+            Principled::new(Vector3::new(1.0, 1.0, 1.0))
+                .with_roughness(0.8)
+                .with_base_color_texture(CheckerTexture::new(
+                    Box::new(ConstantTexture::new(Vector3::new(0.8, 0.8, 0.8))),
+                    Box::new(ConstantTexture::new(Vector3::new(0.3, 0.3, 0.3))),
+                    40.0,
+                )),
+        ),
+    ));
+    // Half diffuse red, half polished gold.
+    world.add(Sphere::new(
+        Vector3::new(-2.4, 0.5, 0.0),
+        0.5,
+        Box::new(MixMaterial::new(
+            Box::new(Lambertian::from(Vector3::new(0.7, 0.1, 0.1))),
+            Box::new(RoughConductor::gold(0.2)),
+            0.5,
+        )),
+    ));
+    // Rusty patches on steel, blended by a texture.
+    world.add(Sphere::new(
+        Vector3::new(-1.2, 0.5, 0.0),
+        0.5,
+        Box::new(
+            MixMaterial::new(
+                Box::new(Metal::new(Vector3::new(0.8, 0.8, 0.8), 0.05)),
+                Box::new(Lambertian::from(Vector3::new(0.45, 0.2, 0.08))),
+                0.0,
+            )
+            .with_weight_texture(CheckerTexture::new(
+                Box::new(ConstantTexture::scalar(0.0)),
+                Box::new(ConstantTexture::scalar(0.85)),
+                10.0,
+            )),
+        ),
+    ));
+    // Glossy plastic: a clear coat over red diffuse.
+    world.add(Sphere::new(
+        Vector3::new(0.0, 0.5, 0.0),
+        0.5,
+        Box::new(Coated::new(
+            Box::new(Lambertian::from(Vector3::new(0.7, 0.1, 0.1))),
+            1.5,
+        )),
+    ));
+    // Satin varnish, tinted amber, over white.
+    world.add(Sphere::new(
+        Vector3::new(1.2, 0.5, 0.0),
+        0.5,
+        Box::new(
+            Coated::new(Box::new(Lambertian::from(Vector3::new(0.8, 0.8, 0.8))), 1.5)
+                .with_roughness(0.3)
+                .with_tint(Vector3::new(0.9, 0.55, 0.2)),
+        ),
+    ));
+    // Car paint: a clear coat over brushed aluminium tinted blue.
+    world.add(Sphere::new(
+        Vector3::new(2.4, 0.5, 0.0),
+        0.5,
+        Box::new(
+            Coated::new(Box::new(RoughConductor::aluminium(0.4)), 1.5)
+                .with_tint(Vector3::new(0.2, 0.35, 0.8)),
+        ),
+    ));
+    world
+}
+
+/// Translucent wax, marble and skin next to an opaque diffuse reference.
+pub fn subsurface_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Cuboid::new(
+        Vector3::new(-10.0, -0.5, -10.0),
+        Vector3::new(10.0, 0.0, 10.0),
+        Box::new(Lambertian::from(Vector3::new(0.4, 0.4, 0.4))),
+    ));
+    world.add(Sphere::new(
+        Vector3::new(-2.25, 0.6, 0.0),
+        0.6,
+        Box::new(Lambertian::from(Vector3::new(0.9, 0.85, 0.7))),
+    ));
+    // Candle wax, forward scattering.
+    world.add(Cylinder::new(
+        Vector3::new(-0.75, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        0.45,
+        1.6,
+        Box::new(
+            Subsurface::from_albedo(
+                1.45,
+                Vector3::new(0.99, 0.95, 0.8),
+                Vector3::new(0.12, 0.1, 0.08),
+            )
+            .with_anisotropy(0.6),
+        ),
+    ));
+    // Marble.
+    world.add(Sphere::new(
+        Vector3::new(0.75, 0.6, 0.0),
+        0.6,
+        Box::new(Subsurface::new(
+            1.5,
+            Vector3::new(21.9, 26.2, 30.0),
+            Vector3::new(0.002, 0.003, 0.005),
+        )),
+    ));
+    // Skin, where red light travels furthest.
+    world.add(Sphere::new(
+        Vector3::new(2.25, 0.6, 0.0),
+        0.6,
+        Box::new(Subsurface::from_albedo(
+            1.4,
+            Vector3::new(0.95, 0.8, 0.7),
+            Vector3::new(0.1, 0.06, 0.045),
+        )),
+    ));
+    world
+}
+
+/// Procedural smoke plume with a hot, glowing core near its base.
+fn plume_grid() -> VoxelGrid {
+    let resolution = [48, 96, 48];
+    let bounds = Aabb::new(Vector3::new(-1.0, 0.0, -1.0), Vector3::new(1.0, 4.0, 1.0));
+    let voxels = resolution[0] * resolution[1] * resolution[2];
+    let mut density = Vec::with_capacity(voxels);
+    let mut temperature = Vec::with_capacity(voxels);
+    for k in 0..resolution[2] {
+        for j in 0..resolution[1] {
+            for i in 0..resolution[0] {
+                let x = 2.0 * (i as f64 + 0.5) / resolution[0] as f64 - 1.0;
+                let y = 4.0 * (j as f64 + 0.5) / resolution[1] as f64;
+                let z = 2.0 * (k as f64 + 0.5) / resolution[2] as f64 - 1.0;
+                let sway = 0.25 * (1.7 * y).sin() * (y / 4.0);
+                let radius = 0.25 + 0.15 * y;
+                let r = ((x - sway) * (x - sway) + z * z).sqrt();
+                let wisps = 0.6 + 0.4 * (9.0 * x + 5.0 * y).sin() * (7.0 * z - 3.0 * y).cos();
+                let fade = 1.0 - y / 4.0;
+                let d = ((1.0 - r / radius) * wisps * fade).max(0.0);
+                density.push(d as f32);
+                let heat = if y < 1.2 && d > 0.0 {
+                    1200.0 + 1500.0 * (1.0 - y / 1.2)
+                } else {
+                    0.0
+                };
+                temperature.push(heat as f32);
+            }
+        }
+    }
+    VoxelGrid::new(resolution, bounds, density).with_temperature(temperature)
+}
+
+pub fn volume_scene(grid: VoxelGrid) -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Sphere::new(
+        Vector3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5))),
+    ));
+    world.add(
+        GridMedium::new(grid, 8.0, Vector3::new(0.8, 0.8, 0.8))
+            .with_emission(Vector3::new(1.0, 0.5, 0.1))
+            .with_temperature(2000.0),
+    );
+    world
+}
+
+/// Adds the output variables of one camera ray to `passes`, kept in the
+/// order of `Aov::ALL`. Identifiers are only added `with_ids`, as they do
+/// not average.
+fn gather_aovs(ray: &Ray, first_bounce: &FirstBounce, passes: &mut [Vector3], with_ids: bool) {
+    if let Some(surface) = &first_bounce.surface {
+        for (pass, aov) in passes.iter_mut().zip(Aov::ALL) {
+            *pass += match aov {
+                Aov::Depth => {
+                    let depth = (surface.position - ray.origin()).length();
+                    Vector3::new(depth, depth, depth)
+                }
+                Aov::Normal => surface.normal,
+                Aov::Albedo => surface.albedo,
+                Aov::Position => surface.position,
+                Aov::ObjectId if with_ids => id_color(surface.id.object as u64),
+                Aov::MaterialId if with_ids => id_color(surface.id.material as u64),
+                _ => continue,
+            };
+        }
+    }
+    passes[Aov::Emission as usize] += first_bounce.emission;
+    for (lobe, radiance) in Lobe::ALL.iter().zip(first_bounce.lobes) {
+        passes[lobe.aov() as usize] += radiance;
+    }
+}
+
+/// Layers of each tile: the image, the variance of its pixels when
+/// denoising, then the output variables in the order of `Aov::ALL`.
+const FIRST_AOV_LAYER: usize = 2;
+
+fn render_lines(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    camera: &dyn Camera,
+    scene: &Scene,
+    tile: &mut Tile,
+) {
+    let aa_samples = settings.samples.unwrap_or(100).max(1);
+    let aa_samples_f = aa_samples as f64;
+    let aovs = settings.aovs || settings.denoise;
+    let end_x = tile.start_x() + tile.height(); //offsetted width
+    let end_y = tile.start_y() + tile.width(); //offsetted height
+
+    for x in tile.start_x()..end_x {
+        for y in tile.start_y()..end_y {
+            let mut color_vector = Vector3::new(0.0, 0.0, 0.0);
+            let mut second_moment = Vector3::new(0.0, 0.0, 0.0);
+            let mut passes = [Vector3::new(0.0, 0.0, 0.0); Aov::ALL.len()];
+            let l = (height - x - 1) as f64;
+            let c = y as f64;
+            // Identifiers come from the first sample the camera could trace.
+            let mut with_ids = true;
+            for _ in 0..aa_samples {
+                let u = (c + random_f64()) / width as f64;
+                let v = (l + random_f64()) / height as f64;
+                let r = match camera.generate_ray(u, v) {
+                    Some(r) => r,
+                    None => continue,
+                };
+                let mut first_bounce = FirstBounce::new();
+                let bounce = Some(&mut first_bounce).filter(|_| aovs);
+                let radiance = if settings.spectral {
+                    let wavelengths = spectrum::sample_wavelengths(random_f64());
+                    let radiance = spectral_color(&r, scene, &wavelengths, bounce);
+                    spectrum::radiance_to_rgb(&wavelengths, &radiance)
+                } else {
+                    color(&r, scene, 0, &MediumStack::new(), None, bounce)
+                };
+                color_vector += radiance;
+                second_moment += radiance * radiance;
+                if aovs {
+                    gather_aovs(&r, &first_bounce, &mut passes, with_ids);
+                    with_ids = false;
+                }
+            }
+
+            let mean = color_vector / aa_samples_f;
+            tile.set(0, x, y, mean);
+            if settings.denoise {
+                // Variance of the mean, from that of the samples.
+                let spread = second_moment / aa_samples_f - mean * mean;
+                let variance = spread / (aa_samples_f - 1.0).max(1.0);
+                let variance = Vector3::new(
+                    variance.x().max(0.0),
+                    variance.y().max(0.0),
+                    variance.z().max(0.0),
+                );
+                tile.set(1, x, y, variance);
+            }
+            if aovs {
+                for (i, (pass, aov)) in passes.iter().zip(Aov::ALL).enumerate() {
+                    let value = if aov.is_id() {
+                        *pass
+                    } else {
+                        *pass / aa_samples_f
+                    };
+                    tile.set(FIRST_AOV_LAYER + i, x, y, value);
+                }
+            }
+        }
+    }
+}
+
+fn multithread_render(
+    width: u32,
+    height: u32,
+    settings: RenderSettings,
+    camera: Box<dyn Camera>,
+    scene: Scene,
+    tiles: Vec<Tile>,
+) -> Vec<Tile> {
+    let num_cpus = num_cpus::get();
+    let tiles_iter = Arc::new(Mutex::new(tiles.into_iter()));
+    let rendered_tiles = Arc::new(Mutex::new(vec![]));
+    let camera = Arc::new(camera);
+    let scene = Arc::new(scene);
+
+    let mut handles = vec![];
+    for i in 0..num_cpus {
+        let tiles_iter = Arc::clone(&tiles_iter);
+        let rendered_tiles = Arc::clone(&rendered_tiles);
+        let camera = Arc::clone(&camera);
+        let scene = Arc::clone(&scene);
+        let handle = thread::Builder::new()
+            .name(format!("rayito-wrk-{}", i))
+            .spawn(move || loop {
+                let tile = {
+                    let mut iter = tiles_iter.lock().unwrap();
+                    iter.next()
+                };
+                match tile {
+                    Some(mut tile) => {
+                        render_lines(width, height, &settings, &**camera, &scene, &mut tile);
+                        rendered_tiles.lock().unwrap().push(tile);
+                    }
+                    _ => break,
+                }
+            })
+            .unwrap();
+        handles.push(handle);
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let tiles = Arc::try_unwrap(rendered_tiles);
+    if let Ok(tiles) = tiles {
+        tiles.into_inner().unwrap()
+    } else {
+        panic!("Tiles still held by worker threads");
+    }
+}
+
+fn render<S: Into<Scene>>(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    camera: PerspectiveCamera,
+    scene: S,
+) -> Image {
+    let camera = settings.projection.camera(camera, settings.stereo);
+    render_through(width, height, settings, camera, scene)
+}
+
+/// Renders through any camera, leaving out the projection and stereo
+/// settings, which only apply to perspective cameras.
+fn render_through<S: Into<Scene>>(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    camera: Box<dyn Camera>,
+    scene: S,
+) -> Image {
+    let mut scene = scene.into();
+    scene.light_sampler = LightSampler::new(&scene.lights, scene.light_selection);
+    let lines_per_tile = 10;
+    let tile_count = height / lines_per_tile;
+
+    let tile_count = if height % tile_count == 0 {
+        tile_count
+    } else {
+        tile_count + 1
+    };
+
+    let layers = if settings.aovs || settings.denoise {
+        FIRST_AOV_LAYER + Aov::ALL.len()
+    } else {
+        1
+    };
+    let mut tiles: Vec<Tile> = Vec::with_capacity(tile_count as usize);
+    for i in 0..tile_count {
+        let start_x = i * lines_per_tile;
+        let tile_height = if i < tile_count - 1 {
+            lines_per_tile
+        } else {
+            height - lines_per_tile * i
+        };
+        tiles.push(Tile::new(start_x, 0, width, tile_height, layers));
+    }
+
+    // tiles
+    //     .iter_mut()
+    //     .for_each(|tile| render_lines(width, height, settings, &camera, &scene, tile));
+
+    let tiles = multithread_render(width, height, *settings, camera, scene, tiles);
+    let pass =
+        |aov: Aov| Framebuffer::from_tiles(width, height, &tiles, FIRST_AOV_LAYER + aov as usize);
+    let mut beauty = Framebuffer::from_tiles(width, height, &tiles, 0);
+    if settings.denoise {
+        let variance = Framebuffer::from_tiles(width, height, &tiles, 1);
+        beauty = denoise::denoise(&beauty, &variance, &pass(Aov::Albedo), &pass(Aov::Normal));
+    }
+    let image = beauty.to_image();
+    if !settings.aovs {
+        return image;
+    }
+    image.with_passes(Aov::ALL.iter().map(|aov| (*aov, pass(*aov))).collect())
+}
+
+pub fn draw_trio(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let height_float = height as f64;
+    let width_float = width as f64;
+
+    let lookfrom = Vector3::new(3.0, 3.0, 2.0);
+    let lookat = Vector3::new(0.0, 0.0, -1.0);
+    let distance_to_focus = (lookfrom - lookat).length();
+    let aperture = 2.0;
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        20.0,
+        width_float / height_float,
+        aperture,
+        distance_to_focus,
+    );
+    render(width, height, settings, camera, trio_sphere_scene())
+}
+
+pub fn draw_random(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let height_float = height as f64;
+    let width_float = width as f64;
+
+    let lookfrom = Vector3::new(12.0, 1.5, 3.0);
+    let lookat = Vector3::new(0.0, 0.3, 0.0);
+    let distance_to_focus = (lookfrom - lookat).length();
     let aperture = 0.05;
-    let camera = Camera::new(
+    let camera = PerspectiveCamera::new(
         lookfrom,
         lookat,
         Vector3::new(0.0, 1.0, 0.0),
@@ -337,5 +2072,504 @@ pub fn draw_random(width: u32, height: u32) -> Image {
         aperture,
         distance_to_focus,
     );
-    render(width, height, camera, random_scene())
+    render(width, height, settings, camera, random_scene())
+}
+
+/// Renders `many_lights_scene`, picking lights by power with `"power"` or
+/// through the light tree otherwise.
+pub fn draw_many_lights(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    selection: Option<&str>,
+) -> Image {
+    let selection = match selection {
+        Some("power") => LightSelection::Power,
+        _ => LightSelection::Tree,
+    };
+    let lookfrom = Vector3::new(12.0, 1.5, 3.0);
+    let lookat = Vector3::new(0.0, 0.3, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        20.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    let scene = many_lights_scene().with_light_selection(selection);
+    render(width, height, settings, camera, scene)
+}
+
+/// Renders `bokeh_scene` through a wide open lens with `blades` straight
+/// diaphragm blades, round with fewer than three, or through the opening
+/// in a `.ppm` mask, with cat's eyes towards the edges.
+pub fn draw_bokeh(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    blades: u32,
+    mask_path: Option<&str>,
+) -> Result<Image, io::Error> {
+    let aperture = match mask_path {
+        Some(path) => Aperture::Mask(Arc::new(ApertureMask::load(Path::new(path))?)),
+        None => Aperture::polygon(blades, 90.0),
+    };
+    let lookfrom = Vector3::new(0.0, 0.8, 4.0);
+    let lookat = Vector3::new(0.0, 0.5, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        30.0,
+        width as f64 / height as f64,
+        0.6,
+        (lookfrom - lookat).length(),
+    )
+    .with_aperture(aperture)
+    .with_cats_eye(0.6);
+    Ok(render(width, height, settings, camera, bokeh_scene()))
+}
+
+/// Looks down on `random_scene` with the plane in focus tilted `tilt`
+/// degrees against the ground, so only a band across the middle is sharp
+/// and the scene looks like a miniature.
+pub fn draw_tilt_shift(width: u32, height: u32, settings: &RenderSettings, tilt: f64) -> Image {
+    let lookfrom = Vector3::new(9.0, 5.0, 6.0);
+    let lookat = Vector3::new(0.0, 0.3, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        30.0,
+        width as f64 / height as f64,
+        0.6,
+        (lookfrom - lookat).length(),
+    )
+    .with_tilt(tilt, 0.0);
+    render(width, height, settings, camera, random_scene())
+}
+
+/// Renders `random_scene` through a lens prescription from `lens_path`, or
+/// a built-in 50mm double Gauss lens, on full frame film, stopped down to
+/// `f_number` and focused on the big glass sphere.
+pub fn draw_lens(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    lens_path: Option<&str>,
+    f_number: f64,
+) -> Result<Image, io::Error> {
+    let lens = match lens_path {
+        Some(path) => LensSystem::load(Path::new(path))?,
+        None => LensSystem::parse(DOUBLE_GAUSS_LENS)?,
+    };
+    let lookfrom = Vector3::new(12.0, 1.5, 3.0);
+    let lookat = Vector3::new(0.0, 0.5, 0.0);
+    let camera = RealisticCamera::new(
+        CameraFrame::look_at(lookfrom, lookat, Vector3::new(0.0, 1.0, 0.0)),
+        lens.stopped_down(f_number)?,
+        43.27,
+        width as f64 / height as f64,
+        (lookfrom - lookat).length(),
+    )?;
+    Ok(render_through(
+        width,
+        height,
+        settings,
+        Box::new(camera),
+        random_scene(),
+    ))
+}
+
+/// Renders frame `frame` of `animation`, counting from one.
+pub fn draw_animation_frame(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    animation: &Animation,
+    frame: u32,
+) -> Image {
+    let time = animation.time(frame);
+    let camera = animation.camera.at(time, width as f64 / height as f64);
+    render(width, height, settings, camera, animation.scene(time))
+}
+
+pub fn draw_csg(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(4.0, 5.0, 9.0);
+    let lookat = Vector3::new(0.0, 0.8, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        30.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, csg_scene())
+}
+
+pub fn draw_sdf(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 3.0, 8.0);
+    let lookat = Vector3::new(0.0, 0.7, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        35.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, sdf_scene())
+}
+
+pub fn draw_quadrics(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 3.0, 8.0);
+    let lookat = Vector3::new(0.0, 0.6, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, quadrics_scene())
+}
+
+pub fn draw_microfacet(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 2.0, 7.0);
+    let lookat = Vector3::new(0.0, 0.6, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, microfacet_scene())
+}
+
+pub fn draw_principled(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 2.0, 7.0);
+    let lookat = Vector3::new(0.0, 0.6, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, principled_scene())
+}
+
+pub fn draw_nested(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 3.0, 6.5);
+    let lookat = Vector3::new(0.0, 0.8, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, nested_scene())
+}
+
+pub fn draw_thin_film(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 2.5, 6.5);
+    let lookat = Vector3::new(0.0, 0.8, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, thin_film_scene())
+}
+
+pub fn draw_cutout(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.5, 2.0, 6.0);
+    let lookat = Vector3::new(0.0, 0.6, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, cutout_scene())
+}
+
+/// Lights `environment_scene` with an `.hdr` or `.pfm` equirectangular
+/// image, or a built-in sky without one, turned by `rotation` degrees and
+/// scaled by `intensity`.
+pub fn draw_environment(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    environment_path: Option<&str>,
+    rotation: f64,
+    intensity: f64,
+) -> Result<Image, io::Error> {
+    let environment = match environment_path {
+        Some(path) => ImageEnvironment::load(Path::new(path))?,
+        None => afternoon_environment(),
+    };
+    let environment = environment
+        .with_rotation(rotation)
+        .with_intensity(intensity);
+    let lookfrom = Vector3::new(0.0, 1.5, 6.0);
+    let lookat = Vector3::new(0.0, 0.6, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    let scene = Scene::new(environment_scene()).with_environment(environment);
+    Ok(render(width, height, settings, camera, scene))
+}
+
+/// Renders `sky_scene` under a physical sky at local solar time `hour` on
+/// midsummer's day at 40 degrees north.
+pub fn draw_sky(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    hour: f64,
+    turbidity: f64,
+) -> Image {
+    let sky = PhysicalSky::at_time(40.0, 172.0, hour)
+        .with_turbidity(turbidity)
+        .with_ground_albedo(Vector3::new(0.5, 0.5, 0.48));
+    let lookfrom = Vector3::new(1.0, 1.8, 7.0);
+    let lookat = Vector3::new(0.0, 1.0, -1.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        45.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(
+        width,
+        height,
+        settings,
+        camera,
+        Scene::new(sky_scene()).with_environment(sky),
+    )
+}
+
+/// `lights_scene` lit by a warm point light, a blue spot light and dim
+/// moonlight under a dark sky.
+pub fn draw_lights(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 1.5, 6.0);
+    let lookat = Vector3::new(0.0, 0.7, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    let scene = Scene::new(lights_scene())
+        .with_environment(ConstantEnvironment::new(Vector3::new(0.01, 0.012, 0.02)))
+        .with_light(PointLight::new(
+            Vector3::new(-2.0, 1.5, 1.0),
+            Vector3::new(2.0, 1.5, 0.9),
+        ))
+        .with_light(
+            SpotLight::new(
+                Vector3::new(2.5, 3.0, 2.0),
+                Vector3::new(1.3, 0.5, 0.0),
+                Vector3::new(6.0, 7.0, 10.0),
+                10.0,
+                20.0,
+            )
+            .with_falloff(2.0),
+        )
+        .with_light(DirectionalLight::new(
+            Vector3::new(0.3, -1.0, -0.5),
+            Vector3::new(0.05, 0.06, 0.1),
+        ));
+    render(width, height, settings, camera, scene)
+}
+
+/// `ies_scene` lit by three downlights along the wall, shaped by an IES
+/// profile from `profile_path` or a built-in wall washer without one.
+pub fn draw_ies(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    profile_path: Option<&str>,
+) -> Result<Image, io::Error> {
+    let load = || match profile_path {
+        Some(path) => IesProfile::load(Path::new(path)),
+        None => IesProfile::parse(WALL_WASHER_IES),
+    };
+    let lookfrom = Vector3::new(0.0, 1.6, 5.5);
+    let lookat = Vector3::new(0.0, 1.2, -1.5);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        45.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    let mut scene = Scene::new(ies_scene())
+        .with_environment(ConstantEnvironment::new(Vector3::new(0.005, 0.005, 0.006)));
+    let down = Vector3::new(0.0, -1.0, 0.0);
+    let front = Vector3::new(0.0, 0.0, -1.0);
+    for x in &[-2.4, 2.4] {
+        let position = Vector3::new(*x, 3.1, -0.9);
+        scene = scene.with_light(
+            PointLight::new(position, Vector3::new(4.0, 3.6, 3.0)).with_profile(
+                load()?,
+                down,
+                front,
+            ),
+        );
+    }
+    // The middle fixture is also cut off by a cone.
+    let position = Vector3::new(0.0, 3.1, -0.9);
+    scene = scene.with_light(
+        SpotLight::new(
+            position,
+            position + down,
+            Vector3::new(4.0, 3.6, 3.0),
+            50.0,
+            70.0,
+        )
+        .with_profile(load()?, front),
+    );
+    Ok(render(width, height, settings, camera, scene))
+}
+
+pub fn draw_layered(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 2.0, 6.0);
+    let lookat = Vector3::new(0.0, 0.5, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, layered_scene())
+}
+
+pub fn draw_subsurface(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 2.0, 6.0);
+    let lookat = Vector3::new(0.0, 0.6, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, subsurface_scene())
+}
+
+pub fn draw_dispersion(width: u32, height: u32, settings: &RenderSettings) -> Image {
+    let lookfrom = Vector3::new(0.0, 3.0, 6.0);
+    let lookat = Vector3::new(0.0, 0.6, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    render(width, height, settings, camera, dispersion_scene())
+}
+
+/// Renders bump and normal mapped objects, optionally with a tangent space
+/// normal map loaded from a ppm file.
+pub fn draw_bump(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    normal_map_path: Option<&str>,
+) -> Result<Image, io::Error> {
+    let normal_map = match normal_map_path {
+        Some(path) => Some(ImageTexture::load(Path::new(path))?),
+        None => None,
+    };
+    let lookfrom = Vector3::new(0.0, 2.5, 6.5);
+    let lookat = Vector3::new(0.3, 0.6, 0.0);
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    Ok(render(
+        width,
+        height,
+        settings,
+        camera,
+        bump_scene(normal_map),
+    ))
+}
+
+/// Renders a voxel grid medium, either loaded from `grid_path` or the built in
+/// procedural plume.
+pub fn draw_volume(
+    width: u32,
+    height: u32,
+    settings: &RenderSettings,
+    grid_path: Option<&str>,
+) -> Result<Image, io::Error> {
+    let grid = match grid_path {
+        Some(path) => volumes::load_grid(path)?,
+        None => plume_grid(),
+    };
+    let bounds = grid.bounds();
+    let lookat = bounds.min() + 0.5 * bounds.extent();
+    let lookfrom = lookat + Vector3::new(0.0, 0.5, 1.5 * bounds.extent().length());
+    let camera = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        Vector3::new(0.0, 1.0, 0.0),
+        30.0,
+        width as f64 / height as f64,
+        0.0,
+        (lookfrom - lookat).length(),
+    );
+    Ok(render(width, height, settings, camera, volume_scene(grid)))
 }
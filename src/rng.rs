@@ -5,6 +5,20 @@ pub fn random_f64() -> f64 {
     thread_rng().gen_range(0.0, 1.0)
 }
 
+/// Number in `[0, 1)` that looks random but is fixed by `values`, for
+/// random choices that separate calls have to agree on.
+pub fn hashed_f64(values: &[f64]) -> f64 {
+    let mut h = 0u64;
+    for value in values {
+        // SplitMix64 finalizer.
+        h = (h ^ value.to_bits()).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+    }
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
 fn random_f64x3() -> [f64; 3] {
     [random_f64(), random_f64(), random_f64()]
 }
@@ -17,3 +31,29 @@ pub fn random_in_unit_sphere() -> Vector3 {
         }
     }
 }
+
+/// Cosine weighted direction on the hemisphere around +z.
+pub fn random_cosine_direction() -> Vector3 {
+    let r1 = random_f64();
+    let r2 = random_f64();
+    let phi = 2.0 * std::f64::consts::PI * r1;
+    let r = r2.sqrt();
+    Vector3::new(r * phi.cos(), r * phi.sin(), (1.0 - r2).sqrt())
+}
+
+/// Uniformly distributed point on the unit disk in the xy plane, using the
+/// concentric mapping of Shirley and Chiu so that strata stay compact.
+pub fn random_in_unit_disk() -> Vector3 {
+    let x = 2.0 * random_f64() - 1.0;
+    let y = 2.0 * random_f64() - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+    let quarter = 0.25 * std::f64::consts::PI;
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, quarter * (y / x))
+    } else {
+        (y, 2.0 * quarter - quarter * (x / y))
+    };
+    Vector3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
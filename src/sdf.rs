@@ -0,0 +1,400 @@
+use crate::aabb::Aabb;
+use crate::hittables::{HitId, HitRecord, Hittable, Material};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// Signed distance function: negative inside the shape, positive outside.
+pub trait Sdf {
+    fn distance(&self, p: Vector3) -> f64;
+}
+
+type BoxedSdf = Box<dyn Sdf + Send + Sync>;
+
+fn max3(v: Vector3) -> Vector3 {
+    Vector3::new(v.x().max(0.0), v.y().max(0.0), v.z().max(0.0))
+}
+
+fn abs3(v: Vector3) -> Vector3 {
+    Vector3::new(v.x().abs(), v.y().abs(), v.z().abs())
+}
+
+fn mix(a: f64, b: f64, h: f64) -> f64 {
+    a * (1.0 - h) + b * h
+}
+
+pub struct SphereSdf {
+    center: Vector3,
+    radius: f64,
+}
+
+impl SphereSdf {
+    pub fn new(center: Vector3, radius: f64) -> SphereSdf {
+        SphereSdf { center, radius }
+    }
+}
+
+impl Sdf for SphereSdf {
+    fn distance(&self, p: Vector3) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+}
+
+/// Axis aligned box given by its center and half extents.
+pub struct BoxSdf {
+    center: Vector3,
+    half_extents: Vector3,
+}
+
+impl BoxSdf {
+    pub fn new(center: Vector3, half_extents: Vector3) -> BoxSdf {
+        BoxSdf {
+            center,
+            half_extents,
+        }
+    }
+}
+
+impl Sdf for BoxSdf {
+    fn distance(&self, p: Vector3) -> f64 {
+        let q = abs3(p - self.center) - self.half_extents;
+        let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+        max3(q).length() + inside
+    }
+}
+
+/// Torus lying in the xz plane.
+pub struct TorusSdf {
+    center: Vector3,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl TorusSdf {
+    pub fn new(center: Vector3, major_radius: f64, minor_radius: f64) -> TorusSdf {
+        TorusSdf {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Sdf for TorusSdf {
+    fn distance(&self, p: Vector3) -> f64 {
+        let p = p - self.center;
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.major_radius;
+        (ring * ring + p.y() * p.y()).sqrt() - self.minor_radius
+    }
+}
+
+/// Segment from `a` to `b` swept by a sphere.
+pub struct CapsuleSdf {
+    a: Vector3,
+    b: Vector3,
+    radius: f64,
+}
+
+impl CapsuleSdf {
+    pub fn new(a: Vector3, b: Vector3, radius: f64) -> CapsuleSdf {
+        CapsuleSdf { a, b, radius }
+    }
+}
+
+impl Sdf for CapsuleSdf {
+    fn distance(&self, p: Vector3) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+        (pa - h * ba).length() - self.radius
+    }
+}
+
+/// Union of two shapes blended over a distance of roughly `k`.
+pub struct SmoothUnion {
+    a: BoxedSdf,
+    b: BoxedSdf,
+    k: f64,
+}
+
+impl SmoothUnion {
+    pub fn new<A, B>(a: A, b: B, k: f64) -> SmoothUnion
+    where
+        A: Sdf + Send + Sync + 'static,
+        B: Sdf + Send + Sync + 'static,
+    {
+        SmoothUnion {
+            a: Box::new(a),
+            b: Box::new(b),
+            k,
+        }
+    }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Vector3) -> f64 {
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        if self.k <= 0.0 {
+            return d1.min(d2);
+        }
+        let h = (0.5 + 0.5 * (d2 - d1) / self.k).clamp(0.0, 1.0);
+        mix(d2, d1, h) - self.k * h * (1.0 - h)
+    }
+}
+
+/// `a` with `b` carved out of it, blended over a distance of roughly `k`.
+pub struct SmoothSubtraction {
+    a: BoxedSdf,
+    b: BoxedSdf,
+    k: f64,
+}
+
+impl SmoothSubtraction {
+    pub fn new<A, B>(a: A, b: B, k: f64) -> SmoothSubtraction
+    where
+        A: Sdf + Send + Sync + 'static,
+        B: Sdf + Send + Sync + 'static,
+    {
+        SmoothSubtraction {
+            a: Box::new(a),
+            b: Box::new(b),
+            k,
+        }
+    }
+}
+
+impl Sdf for SmoothSubtraction {
+    fn distance(&self, p: Vector3) -> f64 {
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        if self.k <= 0.0 {
+            return d1.max(-d2);
+        }
+        let h = (0.5 - 0.5 * (d1 + d2) / self.k).clamp(0.0, 1.0);
+        mix(d1, -d2, h) + self.k * h * (1.0 - h)
+    }
+}
+
+/// Tiles space with copies of a shape centred on the origin cell. With a
+/// limit only `2 * limit + 1` copies are made along each axis.
+pub struct Repeat {
+    sdf: BoxedSdf,
+    period: Vector3,
+    limit: Option<Vector3>,
+}
+
+impl Repeat {
+    pub fn new<S: Sdf + Send + Sync + 'static>(sdf: S, period: Vector3) -> Repeat {
+        Repeat {
+            sdf: Box::new(sdf),
+            period,
+            limit: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: Vector3) -> Repeat {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: Vector3) -> f64 {
+        let mut q = [0.0; 3];
+        for axis in 0..3 {
+            let period = self.period[axis];
+            if period <= 0.0 {
+                q[axis] = p[axis];
+                continue;
+            }
+            let mut cell = (p[axis] / period).round();
+            if let Some(limit) = self.limit {
+                cell = cell.clamp(-limit[axis], limit[axis]);
+            }
+            q[axis] = p[axis] - period * cell;
+        }
+        self.sdf.distance(Vector3::from_array(q))
+    }
+}
+
+/// Twists a shape around the y axis by `rate` radians per unit of height.
+/// The result is no longer a true distance, so pair it with a reduced step
+/// scale on the `SdfHittable`.
+pub struct Twist {
+    sdf: BoxedSdf,
+    rate: f64,
+}
+
+impl Twist {
+    pub fn new<S: Sdf + Send + Sync + 'static>(sdf: S, rate: f64) -> Twist {
+        Twist {
+            sdf: Box::new(sdf),
+            rate,
+        }
+    }
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: Vector3) -> f64 {
+        let angle = self.rate * p.y();
+        let (sin, cos) = angle.sin_cos();
+        let q = Vector3::new(cos * p.x() - sin * p.z(), p.y(), sin * p.x() + cos * p.z());
+        self.sdf.distance(q)
+    }
+}
+
+/// Sphere traces a distance function. Normals are estimated with central
+/// differences.
+pub struct SdfHittable {
+    sdf: BoxedSdf,
+    material: Box<dyn Material>,
+    bounds: Option<Aabb>,
+    step_scale: f64,
+    max_steps: u32,
+    max_distance: f64,
+    epsilon: f64,
+}
+
+impl SdfHittable {
+    pub fn new<S: Sdf + Send + Sync + 'static>(sdf: S, material: Box<dyn Material>) -> SdfHittable {
+        SdfHittable {
+            sdf: Box::new(sdf),
+            material,
+            bounds: None,
+            step_scale: 1.0,
+            max_steps: 512,
+            max_distance: 100.0,
+            epsilon: 1e-4,
+        }
+    }
+
+    /// Restricts marching to a box known to contain the whole shape. Without
+    /// one, rays are only marched `max_distance` (100) units, so shapes
+    /// further away need bounds to be seen.
+    pub fn with_bounds(mut self, bounds: Aabb) -> SdfHittable {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// Fraction of the distance bound taken on each step. Values below one are
+    /// needed for deformations like `Twist` that stretch space.
+    pub fn with_step_scale(mut self, step_scale: f64) -> SdfHittable {
+        self.step_scale = step_scale;
+        self
+    }
+
+    fn normal(&self, p: Vector3) -> Vector3 {
+        let h = self.epsilon;
+        let dx = Vector3::new(h, 0.0, 0.0);
+        let dy = Vector3::new(0.0, h, 0.0);
+        let dz = Vector3::new(0.0, 0.0, h);
+        Vector3::new(
+            self.sdf.distance(p + dx) - self.sdf.distance(p - dx),
+            self.sdf.distance(p + dy) - self.sdf.distance(p - dy),
+            self.sdf.distance(p + dz) - self.sdf.distance(p - dz),
+        )
+        .unit_vector()
+    }
+}
+
+unsafe impl Send for SdfHittable {}
+unsafe impl Sync for SdfHittable {}
+
+impl Hittable for SdfHittable {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let length = ray.direction().length();
+        let (t_start, t_end) = match self.bounds {
+            Some(bounds) => bounds.hit(ray, t_min, t_max)?,
+            None => (t_min, t_max.min(t_min + self.max_distance / length)),
+        };
+
+        // Rays leaving a surface start right on it, so march on whichever side
+        // the ray heads into and only accept a hit once it has moved clear.
+        let start = self.sdf.distance(ray.point_at(t_min));
+        let (side, mut clear) = if start.abs() > 2.0 * self.epsilon {
+            (start.signum(), true)
+        } else {
+            let probe = self
+                .sdf
+                .distance(ray.point_at(t_min + 4.0 * self.epsilon / length));
+            (probe.signum(), false)
+        };
+
+        let mut t = t_start;
+        for _ in 0..self.max_steps {
+            if t >= t_end {
+                return None;
+            }
+            let d = side * self.sdf.distance(ray.point_at(t));
+            if d < self.epsilon {
+                if clear {
+                    let point = ray.point_at(t);
+                    let normal = self.normal(point);
+                    let frame = Onb::from_w(normal);
+                    return Some(HitRecord {
+                        t,
+                        p: point,
+                        normal,
+                        geometric_normal: normal,
+                        dpdu: frame.world(Vector3::new(1.0, 0.0, 0.0)),
+                        dpdv: frame.world(Vector3::new(0.0, 1.0, 0.0)),
+                        u: 0.0,
+                        v: 0.0,
+                        material: &*self.material,
+                        id: HitId::default(),
+                    });
+                }
+                t += self.epsilon / length;
+                continue;
+            }
+            clear = true;
+            t += self.step_scale * d / length;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::Lambertian;
+
+    fn hittable<S: Sdf + Send + Sync + 'static>(sdf: S) -> SdfHittable {
+        SdfHittable::new(sdf, Box::new(Lambertian::from(Vector3::new(0.5, 0.5, 0.5))))
+    }
+
+    fn towards_x() -> Ray {
+        Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn far_shapes_are_hit_within_their_bounds() {
+        let far = || SphereSdf::new(Vector3::new(300.0, 0.0, 0.0), 1.0);
+        assert!(hittable(far()).hit(&towards_x(), 0.001, f64::MAX).is_none());
+
+        let bounds = Aabb::new(
+            Vector3::new(298.0, -2.0, -2.0),
+            Vector3::new(302.0, 2.0, 2.0),
+        );
+        let bounded = hittable(far()).with_bounds(bounds);
+        let hit = bounded.hit(&towards_x(), 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 299.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn smooth_blends_without_a_radius_are_hard() {
+        let a = || SphereSdf::new(Vector3::new(-1.0, 0.0, 0.0), 1.0);
+        let b = || SphereSdf::new(Vector3::new(1.0, 0.0, 0.0), 1.0);
+        // Equally far from both spheres.
+        let p = Vector3::new(0.0, 3.0, 0.0);
+        let union = SmoothUnion::new(a(), b(), 0.0).distance(p);
+        assert!((union - a().distance(p)).abs() < 1e-12);
+        let subtraction = SmoothSubtraction::new(a(), b(), 0.0).distance(p);
+        assert!((subtraction - a().distance(p)).abs() < 1e-12);
+    }
+}
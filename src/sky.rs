@@ -0,0 +1,279 @@
+use crate::environment::{Environment, LightSample};
+use crate::onb::Onb;
+use crate::rng::{random_cosine_direction, random_f64};
+use crate::spectrum;
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+
+/// Luminance in cd/m² of one unit of radiance, chosen so that surfaces lit by
+/// a high sun come out around one.
+const LUMINANCE_SCALE: f64 = 2e-5;
+
+/// Luminance of the sun above the atmosphere in cd/m².
+const SUN_LUMINANCE: f64 = 2e9;
+
+/// Angular radius of the sun seen from the earth.
+const SUN_RADIUS: f64 = 0.004_65;
+
+/// Wavelengths in micrometers standing in for the red, green and blue
+/// channels when attenuating sunlight.
+const RGB_WAVELENGTHS: [f64; 3] = [0.65, 0.55, 0.45];
+
+/// Coefficients of the Perez sky model for one of luminance and the two
+/// chromaticities.
+#[derive(Clone, Copy)]
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    /// Relative brightness at zenith angle `theta` and angle `gamma` from the
+    /// sun.
+    fn f(&self, cos_theta: f64, gamma: f64) -> f64 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta.max(1e-3)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+/// Analytic clear daylight sky after Preetham, Shirley and Smits, "A
+/// Practical Analytic Model for Daylight", with the sun as a small disk that
+/// is sampled directly. Below the horizon is ground of a given albedo lit by
+/// the sun and sky.
+pub struct PhysicalSky {
+    sun: Vector3,
+    turbidity: f64,
+    ground_albedo: Vector3,
+    perez: [Perez; 3],
+    /// Zenith luminance and chromaticity `(Y, x, y)`.
+    zenith: [f64; 3],
+    sun_radiance: Vector3,
+    ground_radiance: Vector3,
+}
+
+impl PhysicalSky {
+    /// A sky with the sun towards `sun_direction`, with +y up.
+    pub fn new(sun_direction: Vector3) -> PhysicalSky {
+        PhysicalSky {
+            sun: sun_direction.unit_vector(),
+            turbidity: 3.0,
+            ground_albedo: Vector3::new(0.3, 0.3, 0.3),
+            perez: [Perez {
+                a: 0.0,
+                b: 0.0,
+                c: 0.0,
+                d: 0.0,
+                e: 0.0,
+            }; 3],
+            zenith: [0.0; 3],
+            sun_radiance: Vector3::new(0.0, 0.0, 0.0),
+            ground_radiance: Vector3::new(0.0, 0.0, 0.0),
+        }
+        .derived()
+    }
+
+    /// The sky at local solar time `hour` on `day_of_year` at `latitude`
+    /// degrees north, with north towards -z and east towards +x.
+    pub fn at_time(latitude: f64, day_of_year: f64, hour: f64) -> PhysicalSky {
+        let latitude = latitude.to_radians();
+        let declination = 0.4093 * (2.0 * PI * (day_of_year - 81.0) / 368.0).sin();
+        let hour_angle = PI * (hour - 12.0) / 12.0;
+        let east = -declination.cos() * hour_angle.sin();
+        let north = latitude.cos() * declination.sin()
+            - latitude.sin() * declination.cos() * hour_angle.cos();
+        let up = latitude.sin() * declination.sin()
+            + latitude.cos() * declination.cos() * hour_angle.cos();
+        PhysicalSky::new(Vector3::new(east, up, -north))
+    }
+
+    /// Haziness of the atmosphere, from 2 for a very clear sky to around 10
+    /// for a hazy one.
+    pub fn with_turbidity(mut self, turbidity: f64) -> PhysicalSky {
+        self.turbidity = turbidity.clamp(1.7, 10.0);
+        self.derived()
+    }
+
+    pub fn with_ground_albedo(mut self, albedo: Vector3) -> PhysicalSky {
+        self.ground_albedo = albedo;
+        self.derived()
+    }
+
+    /// Zenith angle of the sun, kept above the horizon where the model holds.
+    fn sun_theta(&self) -> f64 {
+        self.sun.y().clamp(0.0, 1.0).acos().min(0.5 * PI - 1e-3)
+    }
+
+    /// Recomputes everything that depends on the sun and turbidity.
+    fn derived(mut self) -> PhysicalSky {
+        let t = self.turbidity;
+        self.perez = [
+            Perez {
+                a: 0.1787 * t - 1.4630,
+                b: -0.3554 * t + 0.4275,
+                c: -0.0227 * t + 5.3251,
+                d: 0.1206 * t - 2.5771,
+                e: -0.0670 * t + 0.3703,
+            },
+            Perez {
+                a: -0.0193 * t - 0.2592,
+                b: -0.0665 * t + 0.0008,
+                c: -0.0004 * t + 0.2125,
+                d: -0.0641 * t - 0.8989,
+                e: -0.0033 * t + 0.0452,
+            },
+            Perez {
+                a: -0.0167 * t - 0.2608,
+                b: -0.0950 * t + 0.0092,
+                c: -0.0079 * t + 0.2102,
+                d: -0.0441 * t - 1.6537,
+                e: -0.0109 * t + 0.0529,
+            },
+        ];
+
+        let theta = self.sun_theta();
+        let (t2, th2, th3) = (t * t, theta * theta, theta * theta * theta);
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        // In kcd/m².
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * theta)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * theta + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * theta + 0.25886);
+        let y = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * theta)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * theta + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * theta + 0.26688);
+        self.zenith = [luminance * 1000.0, x, y];
+
+        self.sun_radiance = if self.sun.y() > 0.0 {
+            self.sun_transmittance() * SUN_LUMINANCE * LUMINANCE_SCALE
+        } else {
+            Vector3::new(0.0, 0.0, 0.0)
+        };
+        self.ground_radiance = self.ground_albedo * self.horizontal_irradiance() / PI;
+        self
+    }
+
+    /// Fraction of sunlight per channel that makes it through the air to
+    /// the ground, from Rayleigh scattering and aerosols.
+    fn sun_transmittance(&self) -> Vector3 {
+        let theta = self.sun_theta();
+        let air_mass = 1.0 / (theta.cos() + 0.15 * (93.885 - theta.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let channel = |lambda: f64| {
+            let rayleigh = 0.008735 * lambda.powf(-4.08);
+            let aerosol = beta * lambda.powf(-1.3);
+            (-air_mass * (rayleigh + aerosol)).exp()
+        };
+        Vector3::new(
+            channel(RGB_WAVELENGTHS[0]),
+            channel(RGB_WAVELENGTHS[1]),
+            channel(RGB_WAVELENGTHS[2]),
+        )
+    }
+
+    fn sky_radiance(&self, direction: Vector3) -> Vector3 {
+        let cos_theta = direction.y().max(0.0);
+        let gamma = direction.dot(self.sun).clamp(-1.0, 1.0).acos();
+        let theta_sun = self.sun_theta();
+        let mut value = [0.0; 3];
+        for (i, v) in value.iter_mut().enumerate() {
+            let perez = &self.perez[i];
+            *v = self.zenith[i] * perez.f(cos_theta, gamma) / perez.f(1.0, theta_sun);
+        }
+        let [luminance, x, y] = value;
+        if y <= 0.0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        let xyz = Vector3::new(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        let rgb = spectrum::xyz_to_linear_srgb(xyz * LUMINANCE_SCALE);
+        Vector3::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+    }
+
+    /// Light falling on the ground from the sun and sky, integrating the sky
+    /// numerically over the upper hemisphere.
+    fn horizontal_irradiance(&self) -> Vector3 {
+        let solid_angle = 2.0 * PI * (1.0 - SUN_RADIUS.cos());
+        let mut irradiance = self.sun_radiance * solid_angle * self.sun.y().max(0.0);
+        let (rings, segments) = (16, 32);
+        for ring in 0..rings {
+            let theta = 0.5 * PI * (ring as f64 + 0.5) / rings as f64;
+            for segment in 0..segments {
+                let phi = 2.0 * PI * (segment as f64 + 0.5) / segments as f64;
+                let direction = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let area = theta.sin() * (0.5 * PI / rings as f64) * (2.0 * PI / segments as f64);
+                irradiance += self.sky_radiance(direction) * theta.cos() * area;
+            }
+        }
+        irradiance
+    }
+
+    /// Probability of sampling the sun rather than the sky.
+    fn sun_probability(&self) -> f64 {
+        if self.sun.y() > 0.0 {
+            0.5
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Environment for PhysicalSky {
+    fn radiance(&self, direction: Vector3) -> Vector3 {
+        let direction = direction.unit_vector();
+        if direction.y() < 0.0 {
+            return self.ground_radiance;
+        }
+        let sky = self.sky_radiance(direction);
+        if direction.dot(self.sun) >= SUN_RADIUS.cos() {
+            sky + self.sun_radiance
+        } else {
+            sky
+        }
+    }
+
+    /// Picks the sun disk half of the time and the sky, cosine weighted
+    /// around the zenith, otherwise.
+    fn sample(&self) -> Option<LightSample> {
+        let direction = if random_f64() < self.sun_probability() {
+            let cos_max = SUN_RADIUS.cos();
+            let cos_theta = 1.0 - random_f64() * (1.0 - cos_max);
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * random_f64();
+            Onb::from_w(self.sun).world(Vector3::new(
+                sin_theta * phi.cos(),
+                sin_theta * phi.sin(),
+                cos_theta,
+            ))
+        } else {
+            Onb::from_w(Vector3::new(0.0, 1.0, 0.0)).world(random_cosine_direction())
+        };
+        let pdf = self.pdf(direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            radiance: self.radiance(direction),
+            pdf,
+            distance: f64::INFINITY,
+        })
+    }
+
+    fn pdf(&self, direction: Vector3) -> f64 {
+        let direction = direction.unit_vector();
+        let sun_probability = self.sun_probability();
+        let mut pdf = (1.0 - sun_probability) * direction.y().max(0.0) / PI;
+        let cos_max = SUN_RADIUS.cos();
+        if direction.dot(self.sun) >= cos_max {
+            pdf += sun_probability / (2.0 * PI * (1.0 - cos_max));
+        }
+        pdf
+    }
+}
@@ -0,0 +1,256 @@
+use crate::vector::Vector3;
+use std::sync::OnceLock;
+
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+/// Number of wavelengths traced together per camera sample.
+pub const HERO_WAVELENGTHS: usize = 4;
+
+/// CIE standard illuminant D65, 380nm to 780nm in 10nm steps.
+const D65: [f64; 41] = [
+    49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861,
+    115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100.0, 96.3342, 95.788,
+    88.6856, 90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842,
+    69.7213, 71.6091, 74.349, 61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054, 63.3828,
+];
+
+fn d65(lambda: f64) -> f64 {
+    let x = ((lambda - LAMBDA_MIN) / 10.0).clamp(0.0, 40.0);
+    let i = (x.floor() as usize).min(39);
+    let t = x - i as f64;
+    D65[i] * (1.0 - t) + D65[i + 1] * t
+}
+
+fn piecewise_gaussian(lambda: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let sigma = if lambda < mu { sigma_low } else { sigma_high };
+    let x = (lambda - mu) / sigma;
+    (-0.5 * x * x).exp()
+}
+
+/// CIE 1931 colour matching functions, using the multi-lobe fit from Wyman,
+/// Sloan and Shirley, "Simple Analytic Approximations to the CIE XYZ Color
+/// Matching Functions".
+pub fn cie_xyz(lambda: f64) -> Vector3 {
+    let x = 1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+        + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+        + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+        + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8);
+    Vector3::new(x, y, z)
+}
+
+pub fn xyz_to_linear_srgb(xyz: Vector3) -> Vector3 {
+    Vector3::new(
+        3.240_454_2 * xyz.x() - 1.537_138_5 * xyz.y() - 0.498_531_4 * xyz.z(),
+        -0.969_266 * xyz.x() + 1.876_010_8 * xyz.y() + 0.041_556 * xyz.z(),
+        0.055_643_4 * xyz.x() - 0.204_025_9 * xyz.y() + 1.057_225_2 * xyz.z(),
+    )
+}
+
+/// `∫ D65(λ) ȳ(λ) dλ`, so that a unit reflectance under D65 has luminance one.
+fn d65_luminance() -> f64 {
+    static NORMALIZATION: OnceLock<f64> = OnceLock::new();
+    *NORMALIZATION.get_or_init(|| {
+        (0..=400)
+            .map(|i| {
+                let lambda = LAMBDA_MIN + i as f64;
+                d65(lambda) * cie_xyz(lambda).y()
+            })
+            .sum()
+    })
+}
+
+/// Sigmoid used by Jakob and Hanika to keep reflectance spectra in `[0, 1]`.
+fn sigmoid(x: f64) -> f64 {
+    if x.is_infinite() {
+        return if x > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+}
+
+fn sigmoid_polynomial(coefficients: [f64; 3], lambda: f64) -> f64 {
+    let x = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+    sigmoid((coefficients[0] * x + coefficients[1]) * x + coefficients[2])
+}
+
+/// Linear sRGB colour of a reflectance spectrum lit by D65.
+fn reflectance_to_rgb(coefficients: [f64; 3]) -> Vector3 {
+    let mut xyz = Vector3::new(0.0, 0.0, 0.0);
+    for i in 0..=40 {
+        let lambda = LAMBDA_MIN + 10.0 * i as f64;
+        xyz += sigmoid_polynomial(coefficients, lambda) * d65(lambda) * cie_xyz(lambda);
+    }
+    xyz_to_linear_srgb(xyz * 10.0 / d65_luminance())
+}
+
+fn solve3(m: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(m);
+    if d.abs() < 1e-14 {
+        return None;
+    }
+    let mut x = [0.0; 3];
+    for (col, value) in x.iter_mut().enumerate() {
+        let mut mc = m;
+        for row in 0..3 {
+            mc[row][col] = b[row];
+        }
+        *value = det(mc) / d;
+    }
+    Some(x)
+}
+
+/// Gauss-Newton fit of sigmoid polynomial coefficients reproducing `target`.
+fn fit(target: Vector3, mut coefficients: [f64; 3]) -> [f64; 3] {
+    let residual = |c: [f64; 3]| reflectance_to_rgb(c) - target;
+    for _ in 0..12 {
+        let r = residual(coefficients);
+        let error = r.squared_length();
+        if error < 1e-10 {
+            break;
+        }
+        let mut jacobian = [[0.0; 3]; 3];
+        for param in 0..3 {
+            let mut shifted = coefficients;
+            shifted[param] += 1e-4;
+            let derivative = (residual(shifted) - r) / 1e-4;
+            for (row, line) in jacobian.iter_mut().enumerate() {
+                line[param] = derivative[row];
+            }
+        }
+        let step = match solve3(jacobian, [-r[0], -r[1], -r[2]]) {
+            Some(step) => step,
+            None => break,
+        };
+        // Halve the step until it actually reduces the error.
+        let mut scale = 1.0;
+        loop {
+            let candidate = [
+                coefficients[0] + scale * step[0],
+                coefficients[1] + scale * step[1],
+                coefficients[2] + scale * step[2],
+            ];
+            if residual(candidate).squared_length() < error {
+                coefficients = candidate;
+                break;
+            }
+            scale *= 0.5;
+            if scale < 1e-3 {
+                return coefficients;
+            }
+        }
+    }
+    coefficients
+}
+
+const TABLE_RESOLUTION: usize = 16;
+
+/// Coefficients over a regular grid of reflectances in linear sRGB. Each fit
+/// walks from the grey of the same mean towards its target, warm starting
+/// every step from the last, which keeps saturated colours converging.
+fn coefficient_table() -> &'static Vec<[f64; 3]> {
+    static TABLE: OnceLock<Vec<[f64; 3]>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let n = TABLE_RESOLUTION;
+        let mut table = Vec::with_capacity(n * n * n);
+        for b in 0..n {
+            for g in 0..n {
+                for r in 0..n {
+                    let channel = |i: usize| (i as f64 / (n - 1) as f64).clamp(0.001, 0.999);
+                    let target = Vector3::new(channel(r), channel(g), channel(b));
+                    let mean = (target.x() + target.y() + target.z()) / 3.0;
+                    let grey = Vector3::new(mean, mean, mean);
+                    let logit = (2.0 * mean - 1.0) / (1.0 - (2.0 * mean - 1.0).powi(2)).sqrt();
+                    let mut coefficients = [0.0, 0.0, logit];
+                    for step in 1..=6 {
+                        let t = step as f64 / 6.0;
+                        coefficients = fit((1.0 - t) * grey + t * target, coefficients);
+                    }
+                    table.push(coefficients);
+                }
+            }
+        }
+        table
+    })
+}
+
+fn reflectance_coefficients(rgb: Vector3) -> [f64; 3] {
+    let n = TABLE_RESOLUTION;
+    let table = coefficient_table();
+    let mut base = [0usize; 3];
+    let mut frac = [0.0; 3];
+    for axis in 0..3 {
+        let x = rgb[axis].clamp(0.0, 1.0) * (n - 1) as f64;
+        base[axis] = (x.floor() as usize).min(n - 2);
+        frac[axis] = x - base[axis] as f64;
+    }
+    let mut coefficients = [0.0; 3];
+    for corner in 0..8 {
+        let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+        let mut weight = 1.0;
+        for axis in 0..3 {
+            weight *= if offset[axis] == 1 {
+                frac[axis]
+            } else {
+                1.0 - frac[axis]
+            };
+        }
+        let index = ((base[2] + offset[2]) * n + base[1] + offset[1]) * n + base[0] + offset[0];
+        for (c, value) in coefficients.iter_mut().zip(table[index].iter()) {
+            *c += weight * value;
+        }
+    }
+    coefficients
+}
+
+/// Value at `lambda` of a smooth spectrum whose colour under D65 is `rgb`.
+/// Colours brighter than one are scaled down to fit, then back up.
+pub fn rgb_to_spectrum(rgb: Vector3, lambda: f64) -> f64 {
+    let rgb = Vector3::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0));
+    let scale = rgb.x().max(rgb.y()).max(rgb.z());
+    if scale <= 0.0 {
+        return 0.0;
+    }
+    let (rgb, scale) = if scale > 1.0 {
+        (rgb / scale, scale)
+    } else {
+        (rgb, 1.0)
+    };
+    scale * sigmoid_polynomial(reflectance_coefficients(rgb), lambda)
+}
+
+/// Like `rgb_to_spectrum`, but for light sources: the spectrum is multiplied
+/// by D65 so that white light stays white.
+pub fn rgb_to_illuminant(rgb: Vector3, lambda: f64) -> f64 {
+    rgb_to_spectrum(rgb, lambda) * d65(lambda) / d65_luminance()
+}
+
+/// Hero wavelength sampling: one uniform wavelength plus evenly rotated
+/// companions across the visible range.
+pub fn sample_wavelengths(u: f64) -> [f64; HERO_WAVELENGTHS] {
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+    let mut wavelengths = [0.0; HERO_WAVELENGTHS];
+    for (j, lambda) in wavelengths.iter_mut().enumerate() {
+        let offset = (u + j as f64 / HERO_WAVELENGTHS as f64).fract();
+        *lambda = LAMBDA_MIN + offset * range;
+    }
+    wavelengths
+}
+
+/// Linear sRGB estimate from radiance carried at uniformly sampled
+/// wavelengths.
+pub fn radiance_to_rgb(wavelengths: &[f64], radiance: &[f64]) -> Vector3 {
+    let pdf = 1.0 / (LAMBDA_MAX - LAMBDA_MIN);
+    let mut xyz = Vector3::new(0.0, 0.0, 0.0);
+    for (lambda, value) in wavelengths.iter().zip(radiance) {
+        xyz += *value * cie_xyz(*lambda) / pdf;
+    }
+    xyz_to_linear_srgb(xyz / wavelengths.len() as f64)
+}
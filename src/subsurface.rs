@@ -0,0 +1,156 @@
+use crate::hittables::{Dielectric, HitRecord, Hittable, Material, Scatter};
+use crate::nested::Interior;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rng::random_f64;
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+
+/// Scattering events after which a random walk is given up on.
+const MAX_WALK_STEPS: usize = 256;
+
+/// Translucent material like wax, marble or skin. Light refracts in through a
+/// smooth boundary and the path tracer random walks it through the scattering
+/// interior until it leaves again.
+pub struct Subsurface {
+    boundary: Dielectric,
+    scattering: Vector3,
+    absorption: Vector3,
+    anisotropy: f64,
+}
+
+impl Subsurface {
+    /// Scattering and absorption coefficients are per unit of distance.
+    pub fn new(ior: f64, scattering: Vector3, absorption: Vector3) -> Subsurface {
+        Subsurface {
+            boundary: Dielectric::new(ior),
+            scattering,
+            absorption,
+            anisotropy: 0.0,
+        }
+    }
+
+    /// Derives the coefficients from the fraction of light surviving each
+    /// scattering event and the mean distance between events, per channel.
+    pub fn from_albedo(ior: f64, albedo: Vector3, mean_free_path: Vector3) -> Subsurface {
+        let extinction = Vector3::new(
+            1.0 / mean_free_path.x(),
+            1.0 / mean_free_path.y(),
+            1.0 / mean_free_path.z(),
+        );
+        Subsurface::new(
+            ior,
+            albedo * extinction,
+            (Vector3::new(1.0, 1.0, 1.0) - albedo) * extinction,
+        )
+    }
+
+    /// Henyey-Greenstein `g`, positive for forward scattering.
+    pub fn with_anisotropy(mut self, anisotropy: f64) -> Subsurface {
+        self.anisotropy = anisotropy.clamp(-0.99, 0.99);
+        self
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        self.boundary.scatter(ray_in, hit_record)
+    }
+
+    /// Fraction of light surviving each scattering event inside.
+    fn albedo(&self, _hit_record: &HitRecord) -> Vector3 {
+        self.scattering / (self.scattering + self.absorption)
+    }
+
+    fn interior(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Interior> {
+        let mut interior = self.boundary.interior(ray_in, hit_record)?;
+        interior.absorption = self.absorption;
+        interior.scattering = self.scattering;
+        interior.anisotropy = self.anisotropy;
+        Some(interior)
+    }
+}
+
+/// Direction scattered from a path travelling along unit `direction`.
+pub fn sample_henyey_greenstein(direction: Vector3, g: f64) -> Vector3 {
+    let xi = random_f64();
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * xi
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        (1.0 + g * g - s * s) / (2.0 * g)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * random_f64();
+    Onb::from_w(direction).world(Vector3::new(
+        sin_theta * phi.cos(),
+        sin_theta * phi.sin(),
+        cos_theta,
+    ))
+}
+
+/// Follows `ray` through the medium it starts in up to the next surface,
+/// scattering along the way. Coefficients are given per channel, and
+/// `weight` is multiplied by the throughput of the walk. Distances are
+/// sampled from a channel picked at random, weighted by the average pdf over
+/// all channels. Returns the final segment and the surface it reaches, or
+/// `None` if the walk went on for too long.
+pub fn walk_to_surface<'w>(
+    mut ray: Ray,
+    world: &'w dyn Hittable,
+    scattering: &[f64],
+    extinction: &[f64],
+    anisotropy: f64,
+    weight: &mut [f64],
+) -> Option<(Ray, Option<HitRecord<'w>>)> {
+    let channels = extinction.len();
+    let scatters = scattering.iter().any(|&s| s > 0.0);
+    for _ in 0..MAX_WALK_STEPS {
+        let hit_record = world.hit(&ray, 0.001, f64::MAX);
+        let length = ray.direction().length();
+        let surface = hit_record
+            .as_ref()
+            .map_or(f64::INFINITY, |rec| rec.t * length);
+
+        let distance = if scatters {
+            let channel = ((random_f64() * channels as f64) as usize).min(channels - 1);
+            -(1.0 - random_f64()).ln() / extinction[channel]
+        } else {
+            f64::INFINITY
+        };
+
+        if distance >= surface || !distance.is_finite() {
+            if surface.is_finite() {
+                let survival: Vec<f64> = extinction.iter().map(|s| (-s * surface).exp()).collect();
+                // Clear media only need Beer-Lambert, nothing was sampled.
+                let pdf = if scatters {
+                    survival.iter().sum::<f64>() / channels as f64
+                } else {
+                    1.0
+                };
+                for (w, survived) in weight.iter_mut().zip(&survival) {
+                    *w *= survived / pdf;
+                }
+            }
+            return Some((ray, hit_record));
+        }
+
+        let survival: Vec<f64> = extinction.iter().map(|s| (-s * distance).exp()).collect();
+        let pdf = extinction
+            .iter()
+            .zip(&survival)
+            .map(|(s, survived)| s * survived)
+            .sum::<f64>()
+            / channels as f64;
+        for ((w, s), survived) in weight.iter_mut().zip(scattering).zip(&survival) {
+            *w *= s * survived / pdf;
+        }
+        let direction = sample_henyey_greenstein(ray.direction().unit_vector(), anisotropy);
+        let next = Ray::new(ray.point_at(distance / length), direction);
+        ray = match ray.wavelength() {
+            Some(wavelength) => next.with_wavelength(wavelength),
+            None => next,
+        };
+    }
+    None
+}
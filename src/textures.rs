@@ -0,0 +1,119 @@
+use crate::ppm;
+use crate::vector::Vector3;
+use std::io;
+use std::path::Path;
+
+/// Spatially varying value looked up by surface coordinates and hit point.
+/// Scalar parameters read the first channel.
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: Vector3) -> Vector3;
+}
+
+pub struct ConstantTexture {
+    color: Vector3,
+}
+
+impl ConstantTexture {
+    pub fn new(color: Vector3) -> ConstantTexture {
+        ConstantTexture { color }
+    }
+
+    pub fn scalar(value: f64) -> ConstantTexture {
+        ConstantTexture::new(Vector3::new(value, value, value))
+    }
+}
+
+impl Texture for ConstantTexture {
+    fn value(&self, _u: f64, _v: f64, _p: Vector3) -> Vector3 {
+        self.color
+    }
+}
+
+/// Blends linearly from `from` at `v = 0` to `to` at `v = 1`.
+pub struct GradientTexture {
+    from: Vector3,
+    to: Vector3,
+}
+
+impl GradientTexture {
+    pub fn new(from: Vector3, to: Vector3) -> GradientTexture {
+        GradientTexture { from, to }
+    }
+}
+
+impl Texture for GradientTexture {
+    fn value(&self, _u: f64, v: f64, _p: Vector3) -> Vector3 {
+        let v = v.clamp(0.0, 1.0);
+        (1.0 - v) * self.from + v * self.to
+    }
+}
+
+/// Alternates between two textures on a `scale` by `scale` grid in uv space.
+pub struct CheckerTexture {
+    even: Box<dyn Texture>,
+    odd: Box<dyn Texture>,
+    scale: f64,
+}
+
+impl CheckerTexture {
+    pub fn new(even: Box<dyn Texture>, odd: Box<dyn Texture>, scale: f64) -> CheckerTexture {
+        CheckerTexture { even, odd, scale }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Vector3) -> Vector3 {
+        let cell = (u * self.scale).floor() as i64 + (v * self.scale).floor() as i64;
+        if cell.rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Bilinearly filtered image that repeats outside `[0, 1]`, with `v = 1` at
+/// the top row. Texels are used as stored, without undoing any gamma, which
+/// is what normal and height maps expect.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<Vector3>,
+}
+
+impl ImageTexture {
+    /// `texels` are given row by row starting from the top.
+    pub fn new(width: usize, height: usize, texels: Vec<Vector3>) -> ImageTexture {
+        assert!(width > 0 && height > 0, "texture must not be empty");
+        assert_eq!(texels.len(), width * height, "texel count must match size");
+        ImageTexture {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<ImageTexture, io::Error> {
+        let (width, height, texels) = ppm::read_ppm(path)?;
+        Ok(ImageTexture::new(width as usize, height as usize, texels))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vector3 {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.texels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Vector3) -> Vector3 {
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = (1.0 - tx) * self.texel(x0, y0) + tx * self.texel(x0 + 1, y0);
+        let bottom = (1.0 - tx) * self.texel(x0, y0 + 1) + tx * self.texel(x0 + 1, y0 + 1);
+        (1.0 - ty) * top + ty * bottom
+    }
+}
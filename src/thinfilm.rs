@@ -0,0 +1,205 @@
+use crate::hittables::HitRecord;
+use crate::spectrum;
+use crate::textures::{ConstantTexture, Texture};
+use crate::vector::Vector3;
+use std::f64::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+
+/// Wavelengths in nanometers standing in for the red, green and blue
+/// channels when rendering in RGB.
+const RGB_WAVELENGTHS: [f64; 3] = [650.0, 532.0, 450.0];
+
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    fn real(re: f64) -> Complex {
+        Complex::new(re, 0.0)
+    }
+
+    fn norm_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    fn sqrt(self) -> Complex {
+        let r = self.norm_squared().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    fn exp_i(phase: f64) -> Complex {
+        Complex::new(phase.cos(), phase.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Complex) -> Complex {
+        let d = rhs.norm_squared();
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / d,
+            (self.im * rhs.re - self.re * rhs.im) / d,
+        )
+    }
+}
+
+/// Cosine of the transmitted angle from Snell's law, complex past the
+/// critical angle or inside a conductor.
+fn transmitted_cosine(n_i: Complex, n_t: Complex, cos_i: Complex) -> Complex {
+    let ratio = n_i / n_t;
+    let sin2_i = Complex::real(1.0) - cos_i * cos_i;
+    (Complex::real(1.0) - ratio * ratio * sin2_i).sqrt()
+}
+
+/// Fresnel amplitude coefficients `(r_s, r_p)`.
+fn amplitudes(n_i: Complex, n_t: Complex, cos_i: Complex, cos_t: Complex) -> (Complex, Complex) {
+    let rs = (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t);
+    let rp = (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t);
+    (rs, rp)
+}
+
+/// Complex index of refraction of a metal with normal incidence reflectance
+/// `r`, following Gulbrandsen, "Artist Friendly Metallic Fresnel", with the
+/// edge tint equal to the reflectance.
+fn conductor_from_reflectance(r: f64) -> Complex {
+    let r = r.clamp(0.0, 0.99);
+    let g = r;
+    let sqrt_r = r.sqrt();
+    let n = g * (1.0 - r) / (1.0 + r) + (1.0 - g) * (1.0 + sqrt_r) / (1.0 - sqrt_r);
+    let k2 = (r * (n + 1.0) * (n + 1.0) - (n - 1.0) * (n - 1.0)) / (1.0 - r);
+    Complex::new(n, k2.max(0.0).sqrt())
+}
+
+/// A thin transparent layer on top of a surface. Light reflected off the top
+/// and bottom of the film interferes, giving the colours of soap bubbles and
+/// oil slicks.
+pub struct ThinFilm {
+    ior: f64,
+    /// Thickness in nanometers, read from the first channel.
+    thickness: Box<dyn Texture>,
+}
+
+impl ThinFilm {
+    pub fn new(ior: f64, thickness: f64) -> ThinFilm {
+        ThinFilm {
+            ior,
+            thickness: Box::new(ConstantTexture::scalar(thickness)),
+        }
+    }
+
+    pub fn with_thickness_texture<T: Texture + 'static>(mut self, texture: T) -> ThinFilm {
+        self.thickness = Box::new(texture);
+        self
+    }
+
+    /// Reflectance at `wavelength` of the film lying between an incident
+    /// medium `n_incident` and a substrate `n_substrate`, using the Airy
+    /// summation over the multiple reflections inside the film.
+    fn reflectance(
+        &self,
+        hit_record: &HitRecord,
+        cos_i: f64,
+        n_incident: f64,
+        n_substrate: Complex,
+        wavelength: f64,
+    ) -> f64 {
+        let thickness = self
+            .thickness
+            .value(hit_record.u, hit_record.v, hit_record.p)
+            .x()
+            .max(0.0);
+        let n1 = Complex::real(n_incident);
+        let n2 = Complex::real(self.ior);
+        let cos1 = Complex::real(cos_i.abs().min(1.0));
+        let cos2 = transmitted_cosine(n1, n2, cos1);
+        let cos3 = transmitted_cosine(n2, n_substrate, cos2);
+
+        let (r12_s, r12_p) = amplitudes(n1, n2, cos1, cos2);
+        let (r23_s, r23_p) = amplitudes(n2, n_substrate, cos2, cos3);
+        // Round trip phase through the film, 2 * (2 pi / lambda) n d cos.
+        let delta = Complex::real(4.0 * PI * thickness / wavelength) * n2 * cos2;
+        let phase = Complex::exp_i(delta.re) * Complex::real((-delta.im).exp());
+        let airy = |r12: Complex, r23: Complex| {
+            let r = (r12 + r23 * phase) / (Complex::real(1.0) + r12 * r23 * phase);
+            r.norm_squared()
+        };
+        (0.5 * (airy(r12_s, r23_s) + airy(r12_p, r23_p))).clamp(0.0, 1.0)
+    }
+
+    /// Reflectance per colour channel, either at the single wavelength a
+    /// spectral ray carries or at representative RGB wavelengths.
+    pub fn reflectance_rgb(
+        &self,
+        hit_record: &HitRecord,
+        cos_i: f64,
+        n_incident: f64,
+        substrate: Substrate,
+        wavelength: Option<f64>,
+    ) -> Vector3 {
+        match wavelength {
+            Some(lambda) => {
+                let n = substrate.ior(|rgb| spectrum::rgb_to_spectrum(rgb, lambda));
+                let r = self.reflectance(hit_record, cos_i, n_incident, n, lambda);
+                Vector3::new(r, r, r)
+            }
+            None => {
+                let mut channels = [0.0; 3];
+                for (i, channel) in channels.iter_mut().enumerate() {
+                    let n = substrate.ior(|rgb| rgb[i]);
+                    *channel =
+                        self.reflectance(hit_record, cos_i, n_incident, n, RGB_WAVELENGTHS[i]);
+                }
+                Vector3::from_array(channels)
+            }
+        }
+    }
+}
+
+/// What lies underneath a thin film.
+#[derive(Clone, Copy)]
+pub enum Substrate {
+    Dielectric(f64),
+    /// A metal given by its reflectance at normal incidence.
+    Conductor(Vector3),
+}
+
+impl Substrate {
+    fn ior<F: Fn(Vector3) -> f64>(&self, channel: F) -> Complex {
+        match self {
+            Substrate::Dielectric(ior) => Complex::real(*ior),
+            Substrate::Conductor(reflectance) => conductor_from_reflectance(channel(*reflectance)),
+        }
+    }
+}
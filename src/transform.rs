@@ -0,0 +1,177 @@
+use crate::aabb::Aabb;
+use crate::hittables::{HitId, HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// Affine map of points: a linear part, given by its rows, followed by a
+/// translation.
+#[derive(Clone, Copy)]
+pub struct Transform {
+    rows: [Vector3; 3],
+    translation: Vector3,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform::scaling(Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Vector3) -> Transform {
+        Transform {
+            translation: offset,
+            ..Transform::identity()
+        }
+    }
+
+    pub fn scaling(scale: Vector3) -> Transform {
+        Transform {
+            rows: [
+                Vector3::new(scale.x(), 0.0, 0.0),
+                Vector3::new(0.0, scale.y(), 0.0),
+                Vector3::new(0.0, 0.0, scale.z()),
+            ],
+            translation: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Turns `degrees` counterclockwise around `axis`, looking down it.
+    pub fn rotation(axis: Vector3, degrees: f64) -> Transform {
+        let k = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let row = |i: usize, cross: Vector3| {
+            let mut unit = [0.0; 3];
+            unit[i] = cos;
+            Vector3::from_array(unit) + sin * cross + (1.0 - cos) * k[i] * k
+        };
+        Transform {
+            rows: [
+                row(0, Vector3::new(0.0, -k.z(), k.y())),
+                row(1, Vector3::new(k.z(), 0.0, -k.x())),
+                row(2, Vector3::new(-k.y(), k.x(), 0.0)),
+            ],
+            translation: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Rotation by Euler angles in degrees, turning around x first, then y
+    /// and then z.
+    pub fn euler(degrees: Vector3) -> Transform {
+        Transform::rotation(Vector3::new(1.0, 0.0, 0.0), degrees.x())
+            .then(&Transform::rotation(
+                Vector3::new(0.0, 1.0, 0.0),
+                degrees.y(),
+            ))
+            .then(&Transform::rotation(
+                Vector3::new(0.0, 0.0, 1.0),
+                degrees.z(),
+            ))
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        let row = |i: usize| {
+            let r = next.rows[i];
+            r.x() * self.rows[0] + r.y() * self.rows[1] + r.z() * self.rows[2]
+        };
+        Transform {
+            rows: [row(0), row(1), row(2)],
+            translation: next.point(self.translation),
+        }
+    }
+
+    /// The transform undoing this one, which must not flatten space.
+    pub fn inverse(&self) -> Transform {
+        let [a, b, c] = self.rows;
+        let (bc, ca, ab) = (b.cross(c), c.cross(a), a.cross(b));
+        let det = a.dot(bc);
+        // The inverse's columns are the cross products over the determinant.
+        let rows = [
+            Vector3::new(bc.x(), ca.x(), ab.x()) / det,
+            Vector3::new(bc.y(), ca.y(), ab.y()) / det,
+            Vector3::new(bc.z(), ca.z(), ab.z()) / det,
+        ];
+        let linear = Transform {
+            rows,
+            translation: Vector3::new(0.0, 0.0, 0.0),
+        };
+        Transform {
+            rows,
+            translation: -linear.vector(self.translation),
+        }
+    }
+
+    pub fn point(&self, p: Vector3) -> Vector3 {
+        self.vector(p) + self.translation
+    }
+
+    pub fn vector(&self, v: Vector3) -> Vector3 {
+        Vector3::new(
+            self.rows[0].dot(v),
+            self.rows[1].dot(v),
+            self.rows[2].dot(v),
+        )
+    }
+
+    /// Maps a normal through the transform whose inverse this is, which
+    /// keeps it perpendicular to the surface under scaling and shearing.
+    fn normal_through_inverse(&self, n: Vector3) -> Vector3 {
+        n.x() * self.rows[0] + n.y() * self.rows[1] + n.z() * self.rows[2]
+    }
+}
+
+/// An object moved, turned and scaled by a transform. Rays are taken into
+/// the object's own space, where their parameter `t` stays the same.
+pub struct Transformed<H> {
+    object: H,
+    to_world: Transform,
+    to_object: Transform,
+}
+
+impl<H: Hittable> Transformed<H> {
+    pub fn new(object: H, transform: Transform) -> Transformed<H> {
+        Transformed {
+            object,
+            to_world: transform,
+            to_object: transform.inverse(),
+        }
+    }
+
+    fn object_ray(&self, ray: &Ray) -> Ray {
+        ray.redirected(
+            self.to_object.point(ray.origin()),
+            self.to_object.vector(ray.direction()),
+        )
+    }
+}
+
+impl<H: Hittable> Hittable for Transformed<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut hit_record = self.object.hit(&self.object_ray(ray), t_min, t_max)?;
+        hit_record.p = ray.point_at(hit_record.t);
+        hit_record.normal = self
+            .to_object
+            .normal_through_inverse(hit_record.normal)
+            .unit_vector();
+        hit_record.geometric_normal = self
+            .to_object
+            .normal_through_inverse(hit_record.geometric_normal)
+            .unit_vector();
+        hit_record.dpdu = self.to_world.vector(hit_record.dpdu);
+        hit_record.dpdv = self.to_world.vector(hit_record.dpdv);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = self.object.bounding_box()?;
+        Some(bounds.transformed(|p| self.to_world.point(p)))
+    }
+
+    fn ids(&self) -> HitId {
+        self.object.ids()
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.object
+            .transmittance(&self.object_ray(ray), t_min, t_max)
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+use std::ops::{Add, AddAssign, Div, Index, Mul, Neg, Sub};
 
 #[derive(Copy, Clone)]
 pub struct Vector3 {
@@ -14,6 +14,10 @@ impl Vector3 {
         Vector3 { v }
     }
 
+    pub fn to_array(self) -> [f64; 3] {
+        self.v
+    }
+
     pub fn x(&self) -> f64 {
         self.v[0]
     }
@@ -64,10 +68,20 @@ impl Vector3 {
         v[0] * v[0] + v[1] * v[1] + v[2] * v[2]
     }
 
+    /// Relative luminance of a linear sRGB colour.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.v[0] + 0.7152 * self.v[1] + 0.0722 * self.v[2]
+    }
+
     pub fn square_root(self) -> Vector3 {
         let v = self.v;
         Vector3::new(v[0].sqrt(), v[1].sqrt(), v[2].sqrt())
     }
+
+    pub fn exp(self) -> Vector3 {
+        let v = self.v;
+        Vector3::new(v[0].exp(), v[1].exp(), v[2].exp())
+    }
 }
 
 impl Neg for Vector3 {
@@ -157,3 +171,11 @@ impl Div<f64> for Vector3 {
         Vector3::new(self.v[0] / rhs, self.v[1] / rhs, self.v[2] / rhs)
     }
 }
+
+impl Index<usize> for Vector3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        &self.v[axis]
+    }
+}
@@ -0,0 +1,484 @@
+use crate::aabb::Aabb;
+use crate::hittables::{HitId, HitRecord, Hittable, Material, Scatter};
+use crate::ray::Ray;
+use crate::rng;
+use crate::vector::Vector3;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Dense voxel grid with a density channel and optional emission and
+/// temperature (Kelvin) channels. Voxels are cell centred inside `bounds` and
+/// stored x fastest, then y, then z.
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    bounds: Aabb,
+    density: Vec<f32>,
+    emission: Option<Vec<f32>>,
+    temperature: Option<Vec<f32>>,
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], bounds: Aabb, density: Vec<f32>) -> VoxelGrid {
+        assert!(resolution.iter().all(|&n| n > 0));
+        assert_eq!(density.len(), resolution[0] * resolution[1] * resolution[2]);
+        VoxelGrid {
+            resolution,
+            bounds,
+            density,
+            emission: None,
+            temperature: None,
+        }
+    }
+
+    pub fn with_emission(mut self, emission: Vec<f32>) -> VoxelGrid {
+        assert_eq!(emission.len(), self.density.len());
+        self.emission = Some(emission);
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: Vec<f32>) -> VoxelGrid {
+        assert_eq!(temperature.len(), self.density.len());
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    pub fn max_density(&self) -> f64 {
+        self.density.iter().cloned().fold(0.0, f32::max) as f64
+    }
+
+    pub fn density(&self, p: Vector3) -> f64 {
+        self.lookup(&self.density, p)
+    }
+
+    pub fn emission(&self, p: Vector3) -> f64 {
+        match &self.emission {
+            Some(channel) => self.lookup(channel, p),
+            None => 0.0,
+        }
+    }
+
+    pub fn temperature(&self, p: Vector3) -> f64 {
+        match &self.temperature {
+            Some(channel) => self.lookup(channel, p),
+            None => 0.0,
+        }
+    }
+
+    fn voxel(&self, channel: &[f32], x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        channel[(z * ny + y) * nx + x] as f64
+    }
+
+    /// Trilinear lookup of a channel at a world space point. Points outside
+    /// the grid bounds read as zero.
+    fn lookup(&self, channel: &[f32], p: Vector3) -> f64 {
+        let min = self.bounds.min();
+        let extent = self.bounds.extent();
+        let mut base = [0usize; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let local = (p[axis] - min[axis]) / extent[axis];
+            if !(0.0..=1.0).contains(&local) {
+                return 0.0;
+            }
+            let n = self.resolution[axis];
+            let g = (local * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            base[axis] = (g.floor() as usize).min(n.saturating_sub(2));
+            frac[axis] = if n > 1 { g - base[axis] as f64 } else { 0.0 };
+        }
+
+        let step = |axis: usize, i: usize| (base[axis] + i).min(self.resolution[axis] - 1);
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let (i, j, k) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let weight = (if i == 1 { frac[0] } else { 1.0 - frac[0] })
+                * (if j == 1 { frac[1] } else { 1.0 - frac[1] })
+                * (if k == 1 { frac[2] } else { 1.0 - frac[2] });
+            if weight > 0.0 {
+                value += weight * self.voxel(channel, step(0, i), step(1, j), step(2, k));
+            }
+        }
+        value
+    }
+}
+
+/// Approximate sRGB chromaticity of a black body at the given temperature.
+fn blackbody_color(kelvin: f64) -> Vector3 {
+    let t = kelvin / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2)
+    };
+    let green = if t <= 66.0 {
+        99.470_802_586_1 * t.ln() - 161.119_568_166_1
+    } else {
+        288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7
+    };
+    let clamp = |c: f64| (c / 255.0).clamp(0.0, 1.0);
+    Vector3::new(clamp(red), clamp(green), clamp(blue))
+}
+
+/// Isotropic phase function of a grid medium. Emission and temperature are
+/// looked up at the collision point.
+pub struct GridPhase {
+    grid: Arc<VoxelGrid>,
+    albedo: Vector3,
+    emission_color: Vector3,
+    temperature_scale: f64,
+}
+
+impl Material for GridPhase {
+    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<Scatter> {
+        let scattered = Ray::new(hit_record.p, rng::random_in_unit_sphere());
+        Some(Scatter::new(self.albedo, scattered))
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Vector3 {
+        let mut radiance = self.grid.emission(hit_record.p) * self.emission_color;
+        let kelvin = self.grid.temperature(hit_record.p);
+        if self.temperature_scale > 0.0 && kelvin > 500.0 {
+            // Stefan-Boltzmann falloff relative to a 6500K emitter.
+            let intensity = self.temperature_scale * (kelvin / 6500.0).powi(4);
+            radiance += intensity * blackbody_color(kelvin);
+        }
+        // Only the absorbed fraction of collisions emits.
+        (Vector3::new(1.0, 1.0, 1.0) - self.albedo) * radiance
+    }
+}
+
+/// Heterogeneous participating medium backed by a voxel grid. Free flights
+/// are sampled with delta tracking and shadow transmittance is estimated with
+/// ratio tracking, both against the grid's maximum density as majorant.
+pub struct GridMedium {
+    grid: Arc<VoxelGrid>,
+    density_scale: f64,
+    majorant: f64,
+    phase: GridPhase,
+}
+
+impl GridMedium {
+    pub fn new(grid: VoxelGrid, density_scale: f64, albedo: Vector3) -> GridMedium {
+        let majorant = grid.max_density() * density_scale;
+        let grid = Arc::new(grid);
+        GridMedium {
+            grid: Arc::clone(&grid),
+            density_scale,
+            majorant,
+            phase: GridPhase {
+                grid,
+                albedo,
+                emission_color: Vector3::new(0.0, 0.0, 0.0),
+                temperature_scale: 0.0,
+            },
+        }
+    }
+
+    pub fn with_emission(mut self, emission_color: Vector3) -> GridMedium {
+        self.phase.emission_color = emission_color;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature_scale: f64) -> GridMedium {
+        self.phase.temperature_scale = temperature_scale;
+        self
+    }
+
+    fn sigma_t(&self, p: Vector3) -> f64 {
+        self.grid.density(p) * self.density_scale
+    }
+
+    fn free_flight(&self, ray: &Ray) -> f64 {
+        -(1.0 - rng::random_f64()).ln() / (self.majorant * ray.direction().length())
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+        let (t0, t1) = self.grid.bounds().hit(ray, t_min, t_max)?;
+        let mut t = t0;
+        loop {
+            t += self.free_flight(ray);
+            if t >= t1 {
+                return None;
+            }
+            let point = ray.point_at(t);
+            if rng::random_f64() < self.sigma_t(point) / self.majorant {
+                return Some(HitRecord {
+                    t,
+                    p: point,
+                    normal: Vector3::new(1.0, 0.0, 0.0),
+                    geometric_normal: Vector3::new(1.0, 0.0, 0.0),
+                    dpdu: Vector3::new(0.0, 1.0, 0.0),
+                    dpdv: Vector3::new(0.0, 0.0, 1.0),
+                    u: 0.0,
+                    v: 0.0,
+                    material: &self.phase,
+                    id: HitId::default(),
+                });
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.grid.bounds())
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if self.majorant <= 0.0 {
+            return 1.0;
+        }
+        let (t0, t1) = match self.grid.bounds().hit(ray, t_min, t_max) {
+            Some(interval) => interval,
+            None => return 1.0,
+        };
+        let mut transmittance = 1.0;
+        let mut t = t0;
+        loop {
+            t += self.free_flight(ray);
+            if t >= t1 {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.sigma_t(ray.point_at(t)) / self.majorant;
+            // Russian roulette once the estimate gets small.
+            if transmittance < 0.1 {
+                if rng::random_f64() < 0.5 {
+                    return 0.0;
+                }
+                transmittance *= 2.0;
+            }
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn parse_numbers(line: Option<&str>, count: usize) -> Result<Vec<f64>, io::Error> {
+    let line = line.ok_or_else(|| invalid_data("unexpected end of grid header"))?;
+    let numbers: Vec<f64> = line
+        .split_whitespace()
+        .map(|token| token.parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid_data("malformed number in grid header"))?;
+    if numbers.len() != count {
+        return Err(invalid_data("wrong number of values in grid header"));
+    }
+    Ok(numbers)
+}
+
+/// Voxel counts along each axis, which must all be at least one, and the
+/// number of voxels they make.
+fn grid_resolution(counts: [f64; 3]) -> Result<([usize; 3], usize), io::Error> {
+    if counts.iter().any(|&n| n.is_nan() || n < 1.0) {
+        return Err(invalid_data("grid dimensions must be positive"));
+    }
+    let resolution = [counts[0] as usize, counts[1] as usize, counts[2] as usize];
+    let voxels = resolution
+        .iter()
+        .try_fold(1usize, |voxels, &n| voxels.checked_mul(n))
+        .ok_or_else(|| invalid_data("grid is too large"))?;
+    Ok((resolution, voxels))
+}
+
+/// Loads a raw grid. The file starts with two text lines
+///
+/// ```text
+/// RAYGRID <nx> <ny> <nz> <channels>
+/// <min x> <min y> <min z> <max x> <max y> <max z>
+/// ```
+///
+/// followed by `nx * ny * nz * channels` little endian `f32`s, x fastest, with
+/// channels interleaved per voxel as density, emission, temperature.
+pub fn load_raw_grid(path: &Path) -> Result<VoxelGrid, io::Error> {
+    let bytes = fs::read(path)?;
+    let mut lines_end = 0;
+    for _ in 0..2 {
+        let newline = bytes[lines_end..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid_data("missing raw grid header"))?;
+        lines_end += newline + 1;
+    }
+    let header = std::str::from_utf8(&bytes[..lines_end])
+        .map_err(|_| invalid_data("raw grid header is not text"))?;
+    let mut lines = header.lines();
+    let first = lines.next().unwrap_or("");
+    let dims = parse_numbers(first.strip_prefix("RAYGRID"), 4)?;
+    let bounds = parse_numbers(lines.next(), 6)?;
+
+    let (resolution, voxels) = grid_resolution([dims[0], dims[1], dims[2]])?;
+    let channels = dims[3] as usize;
+    if channels == 0 || channels > 3 {
+        return Err(invalid_data(
+            "raw grids carry between one and three channels",
+        ));
+    }
+    let payload = &bytes[lines_end..];
+    if voxels.checked_mul(channels * 4) != Some(payload.len()) {
+        return Err(invalid_data("raw grid payload does not match its header"));
+    }
+
+    let mut data = vec![Vec::with_capacity(voxels); channels];
+    for (i, chunk) in payload.chunks_exact(4).enumerate() {
+        let value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        data[i % channels].push(value);
+    }
+
+    let bounds = Aabb::new(
+        Vector3::new(bounds[0], bounds[1], bounds[2]),
+        Vector3::new(bounds[3], bounds[4], bounds[5]),
+    );
+    let mut channels = data.into_iter();
+    let mut grid = VoxelGrid::new(resolution, bounds, channels.next().unwrap());
+    if let Some(emission) = channels.next() {
+        grid = grid.with_emission(emission);
+    }
+    if let Some(temperature) = channels.next() {
+        grid = grid.with_temperature(temperature);
+    }
+    Ok(grid)
+}
+
+/// Loads a text dump of OpenVDB dense grids, one section per grid:
+///
+/// ```text
+/// grid <density|emission|temperature>
+/// bbox <i0> <j0> <k0> <i1> <j1> <k1>
+/// voxel_size <size>
+/// <values...>
+/// ```
+///
+/// The index space bounding box is inclusive and values are laid out z
+/// fastest, matching `openvdb::tools::Dense` with `LayoutZYX`. All grids must
+/// share the density grid's bounding box. Lines starting with `#` are ignored.
+pub fn load_dense_dump(path: &Path) -> Result<VoxelGrid, io::Error> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    let mut header: Option<([i64; 6], f64)> = None;
+    let mut grids: Vec<(String, Vec<f32>)> = vec![];
+    while let Some(line) = lines.next() {
+        let name = line
+            .strip_prefix("grid")
+            .ok_or_else(|| invalid_data("expected a grid section"))?
+            .trim()
+            .to_string();
+        let bbox = parse_numbers(lines.next().and_then(|l| l.strip_prefix("bbox")), 6)?;
+        let voxel_size =
+            parse_numbers(lines.next().and_then(|l| l.strip_prefix("voxel_size")), 1)?[0];
+        let bbox = [
+            bbox[0] as i64,
+            bbox[1] as i64,
+            bbox[2] as i64,
+            bbox[3] as i64,
+            bbox[4] as i64,
+            bbox[5] as i64,
+        ];
+        match header {
+            Some((first, _)) if first != bbox => {
+                return Err(invalid_data("dense grids must share a bounding box"))
+            }
+            _ => header = Some((bbox, voxel_size)),
+        }
+
+        let ([nx, ny, nz], voxels) = grid_resolution([
+            bbox[3] as f64 - bbox[0] as f64 + 1.0,
+            bbox[4] as f64 - bbox[1] as f64 + 1.0,
+            bbox[5] as f64 - bbox[2] as f64 + 1.0,
+        ])?;
+        let mut zyx = vec![];
+        while zyx.len() < voxels {
+            let line = lines
+                .next()
+                .ok_or_else(|| invalid_data("dense grid ended early"))?;
+            for token in line.split_whitespace() {
+                let value = token
+                    .parse::<f32>()
+                    .map_err(|_| invalid_data("malformed dense grid value"))?;
+                zyx.push(value);
+            }
+        }
+        if zyx.len() != voxels {
+            return Err(invalid_data("dense grid has too many values"));
+        }
+
+        let mut xyz = vec![0.0; voxels];
+        for x in 0..nx {
+            for y in 0..ny {
+                for z in 0..nz {
+                    xyz[(z * ny + y) * nx + x] = zyx[(x * ny + y) * nz + z];
+                }
+            }
+        }
+        grids.push((name, xyz));
+    }
+
+    let (bbox, voxel_size) = header.ok_or_else(|| invalid_data("no grids in dense dump"))?;
+    let (resolution, _) = grid_resolution([
+        bbox[3] as f64 - bbox[0] as f64 + 1.0,
+        bbox[4] as f64 - bbox[1] as f64 + 1.0,
+        bbox[5] as f64 - bbox[2] as f64 + 1.0,
+    ])?;
+    let bounds = Aabb::new(
+        voxel_size * Vector3::new(bbox[0] as f64, bbox[1] as f64, bbox[2] as f64),
+        voxel_size
+            * Vector3::new(
+                bbox[3] as f64 + 1.0,
+                bbox[4] as f64 + 1.0,
+                bbox[5] as f64 + 1.0,
+            ),
+    );
+
+    let mut density = None;
+    let mut emission = None;
+    let mut temperature = None;
+    for (name, values) in grids {
+        match name.as_ref() {
+            "density" => density = Some(values),
+            "emission" | "flames" => emission = Some(values),
+            "temperature" => temperature = Some(values),
+            _ => {}
+        }
+    }
+    let density = density.ok_or_else(|| invalid_data("dense dump has no density grid"))?;
+    let mut grid = VoxelGrid::new(resolution, bounds, density);
+    if let Some(emission) = emission {
+        grid = grid.with_emission(emission);
+    }
+    if let Some(temperature) = temperature {
+        grid = grid.with_temperature(temperature);
+    }
+    Ok(grid)
+}
+
+/// Picks a loader from the file extension: `.dense` and `.txt` files are
+/// OpenVDB style dense dumps, anything else is a raw grid.
+pub fn load_grid(path: &str) -> Result<VoxelGrid, io::Error> {
+    let path = Path::new(path);
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("dense") | Some("txt") => load_dense_dump(path),
+        _ => load_raw_grid(path),
+    }
+}